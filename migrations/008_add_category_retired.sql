@@ -0,0 +1 @@
+ALTER TABLE category ADD COLUMN retired BOOLEAN NOT NULL DEFAULT FALSE;
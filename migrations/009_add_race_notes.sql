@@ -0,0 +1 @@
+ALTER TABLE race ADD COLUMN notes TEXT NULL;
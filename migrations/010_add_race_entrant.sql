@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS race_entrant
+(
+    id       INTEGER PRIMARY KEY NOT NULL,
+    race_id  INTEGER NOT NULL,
+    user_id  TEXT NOT NULL,
+    kind     TEXT NOT NULL,
+    status   TEXT NOT NULL,
+    created  INTEGER NOT NULL,
+    updated  INTEGER NOT NULL,
+
+    FOREIGN KEY(race_id) REFERENCES race(id),
+    CONSTRAINT race_user_kind UNIQUE (race_id, user_id, kind)
+);
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS setting
+(
+    name   TEXT PRIMARY KEY NOT NULL,
+    value  TEXT NOT NULL
+);
@@ -0,0 +1 @@
+ALTER TABLE race_entrant ADD COLUMN assigned BOOLEAN NOT NULL DEFAULT FALSE;
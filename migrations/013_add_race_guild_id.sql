@@ -0,0 +1,45 @@
+-- races belong to a guild now, and two guilds can race the same thing at the same time.
+-- sqlite can't alter a constraint, so the race table gets rebuilt. race_entrant has to be
+-- rebuilt along with it so that its foreign key survives the swap.
+CREATE TABLE race_new
+(
+    id                     INTEGER PRIMARY KEY,
+    guild_id               TEXT NULL,
+    game_id                INTEGER NOT NULL,
+    category_id            INTEGER NOT NULL,
+    occurs                 INTEGER NOT NULL,
+    state                  TEXT NOT NULL,
+    scheduling_message_id  TEXT NULL,
+    active_message_id      TEXT NULL,
+    notes                  TEXT NULL,
+
+    FOREIGN KEY(game_id) REFERENCES game(id),
+    FOREIGN KEY(category_id) REFERENCES category(id),
+    CONSTRAINT guild_game_cat_time UNIQUE (guild_id, game_id, category_id, occurs)
+);
+
+INSERT INTO race_new (id, game_id, category_id, occurs, state, scheduling_message_id, active_message_id, notes)
+SELECT id, game_id, category_id, occurs, state, scheduling_message_id, active_message_id, notes FROM race;
+
+CREATE TABLE race_entrant_new
+(
+    id       INTEGER PRIMARY KEY NOT NULL,
+    race_id  INTEGER NOT NULL,
+    user_id  TEXT NOT NULL,
+    kind     TEXT NOT NULL,
+    status   TEXT NOT NULL,
+    created  INTEGER NOT NULL,
+    updated  INTEGER NOT NULL,
+    assigned BOOLEAN NOT NULL DEFAULT FALSE,
+
+    FOREIGN KEY(race_id) REFERENCES race_new(id),
+    CONSTRAINT race_user_kind UNIQUE (race_id, user_id, kind)
+);
+
+INSERT INTO race_entrant_new (id, race_id, user_id, kind, status, created, updated, assigned)
+SELECT id, race_id, user_id, kind, status, created, updated, assigned FROM race_entrant;
+
+DROP TABLE race_entrant;
+DROP TABLE race;
+ALTER TABLE race_new RENAME TO race;
+ALTER TABLE race_entrant_new RENAME TO race_entrant;
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS guild_settings
+(
+    guild_id TEXT NOT NULL,
+    name     TEXT NOT NULL,
+    value    TEXT NOT NULL,
+    PRIMARY KEY (guild_id, name)
+);
@@ -0,0 +1,2 @@
+-- races scheduled before signup buttons keep using reactions
+ALTER TABLE race ADD COLUMN signup_buttons BOOLEAN NOT NULL DEFAULT FALSE;
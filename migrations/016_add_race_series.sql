@@ -0,0 +1,22 @@
+-- a race that happens on a regular schedule. cron creates the actual races a few days ahead.
+-- dates and times are local to the series' timezone so that races stay at the same wall-clock
+-- time across DST changes.
+CREATE TABLE IF NOT EXISTS race_series
+(
+    id                 INTEGER PRIMARY KEY,
+    guild_id           TEXT NOT NULL,
+    game_id            INTEGER NOT NULL,
+    category_id        INTEGER NOT NULL,
+    recurrence         TEXT NOT NULL,
+    first_date         TEXT NOT NULL,
+    time_of_day        TEXT NOT NULL,
+    timezone           TEXT NOT NULL,
+    notes              TEXT NULL,
+    active             BOOLEAN NOT NULL DEFAULT TRUE,
+    scheduled_through  INTEGER NULL,
+
+    FOREIGN KEY(game_id) REFERENCES game(id),
+    FOREIGN KEY(category_id) REFERENCES category(id)
+);
+
+ALTER TABLE race ADD COLUMN series_id INTEGER NULL REFERENCES race_series(id);
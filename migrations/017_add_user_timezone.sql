@@ -0,0 +1,6 @@
+-- the timezone each person types race times in. anyone who hasn't picked one gets US/Eastern.
+CREATE TABLE IF NOT EXISTS user_timezone
+(
+    user_id   TEXT PRIMARY KEY NOT NULL,
+    timezone  TEXT NOT NULL
+);
@@ -0,0 +1,17 @@
+-- how each racer did. finish_time is in seconds and is null for forfeits. placement gets
+-- recalculated from the finish times whenever a result changes; ties share a placement.
+CREATE TABLE IF NOT EXISTS race_result
+(
+    id           INTEGER PRIMARY KEY NOT NULL,
+    race_id      INTEGER NOT NULL,
+    user_id      TEXT NOT NULL,
+    finish_time  INTEGER NULL,
+    forfeit      BOOLEAN NOT NULL DEFAULT FALSE,
+    placement    INTEGER NULL,
+    comment      TEXT NULL,
+    created      INTEGER NOT NULL,
+    updated      INTEGER NOT NULL,
+
+    FOREIGN KEY(race_id) REFERENCES race(id),
+    CONSTRAINT race_user UNIQUE (race_id, user_id)
+);
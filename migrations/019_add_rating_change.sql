@@ -0,0 +1,17 @@
+-- every rating change from every rated race, per guild and category. someone's current rating
+-- is their latest row. the whole table can be thrown away and rebuilt from race_result.
+CREATE TABLE IF NOT EXISTS rating_change
+(
+    id             INTEGER PRIMARY KEY NOT NULL,
+    guild_id       TEXT NOT NULL,
+    category_id    INTEGER NOT NULL,
+    race_id        INTEGER NOT NULL,
+    user_id        TEXT NOT NULL,
+    rating_before  REAL NOT NULL,
+    rating_after   REAL NOT NULL,
+    created        INTEGER NOT NULL,
+
+    FOREIGN KEY(category_id) REFERENCES category(id),
+    FOREIGN KEY(race_id) REFERENCES race(id),
+    CONSTRAINT race_user UNIQUE (race_id, user_id)
+);
@@ -0,0 +1,14 @@
+-- which racetime.gg category (the slug in its URLs) and goal a category's races get rooms in.
+-- categories without a racetime category don't get rooms.
+ALTER TABLE category ADD COLUMN racetime_category TEXT NULL;
+ALTER TABLE category ADD COLUMN racetime_goal TEXT NULL;
+
+-- full URL of the race's racetime.gg room, if one was opened
+ALTER TABLE race ADD COLUMN racetime_room TEXT NULL;
+
+-- lets results from racetime.gg rooms find their way back to discord users
+CREATE TABLE IF NOT EXISTS racetime_user
+(
+    user_id       TEXT PRIMARY KEY NOT NULL,
+    racetime_name TEXT NOT NULL
+);
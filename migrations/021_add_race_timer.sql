@@ -0,0 +1,11 @@
+-- how long a category's races usually take, and how long they can go before the bot ends them,
+-- in seconds. races in categories without a max_duration get ended after 3 hours.
+ALTER TABLE category ADD COLUMN expected_duration INTEGER NULL;
+ALTER TABLE category ADD COLUMN max_duration INTEGER NULL;
+
+-- when someone used !start, in seconds-since-epoch. races nobody started are timed from `occurs`.
+ALTER TABLE race ADD COLUMN started INTEGER NULL;
+
+-- FFX any% takes most of a day
+UPDATE category SET expected_duration = 11 * 3600, max_duration = 14 * 3600
+    WHERE name = 'any_pc' AND game_id = (SELECT id FROM game WHERE name = 'ffx');
@@ -9,6 +9,22 @@ use quote::quote;
 use quote::TokenStreamExt;
 use syn::parse_macro_input;
 
+/// `RaceEntrant` lives in the `race_entrant` table
+fn table_name(struct_name: &str) -> String {
+    let mut table = String::with_capacity(struct_name.len() + 4);
+    for (i, c) in struct_name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                table.push('_');
+            }
+            table.push(c.to_ascii_lowercase());
+        } else {
+            table.push(c);
+        }
+    }
+    table
+}
+
 #[proc_macro]
 pub fn model(input: TokenStream) -> TokenStream {
     let tokens = parse_macro_input!(input as syn::ItemStruct);
@@ -32,9 +48,10 @@ pub fn model(input: TokenStream) -> TokenStream {
         field_params.append(comma_tt.clone());
     }
 
+    let table = table_name(&name.to_string());
     let values_str = format!(" {} ", field_names.join(", "));
-    let update_str = format!(" UPDATE {} SET {} WHERE id = ?", name, values_str);
-    let query_str = format!("SELECT * FROM {} WHERE id = ?", name);
+    let update_str = format!(" UPDATE {} SET {} WHERE id = ?", table, values_str);
+    let query_str = format!("SELECT * FROM {} WHERE id = ?", table);
 
     let expanded = quote! {
         #[derive(sqlx::FromRow)]
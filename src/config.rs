@@ -0,0 +1,382 @@
+use std::fs;
+
+use custom_error::custom_error;
+use serde::Deserialize;
+use tokio::time::Duration;
+
+use crate::constants::{
+    ACTIVE_CHANNEL_NAME, CHANNEL_CATEGORY_ID, CLIENT_ID, FOXLISK_USER_ID, GUILD_NAME,
+    MODERATOR_CHANNEL_NAME, MODERATOR_ROLE_NAMES, NAG_TIMES, NOTIFY_BEFORE_RACE_SECS,
+    RACETIME_URL, RACING_EMOJI_NAME, SCHEDULING_CHANNEL_NAME, SERIES_DAYS_AHEAD,
+};
+
+custom_error! { pub(crate) ConfigError
+    Read{path: String, err: String} = "Error reading config file {path}: {err}",
+    Parse{path: String, err: String} = "Error parsing config file {path}: {err}",
+    Invalid{problems: String} = "Invalid config: {problems}",
+}
+
+/// Everything that differs between running against the real server and a test server.
+/// Anything left out of the config file falls back to the values in `constants.rs`.
+/// Most of these are just the defaults for each guild's `GuildSettings`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Config {
+    pub(crate) client_id: u64,
+    /// This user gets added to every role the bot manages
+    pub(crate) owner_user_id: u64,
+    /// The bot ignores any guild not named in here. The first one is where races from before
+    /// the bot supported multiple guilds end up.
+    pub(crate) guild_names: Vec<String>,
+    pub(crate) scheduling_channel_name: String,
+    pub(crate) active_channel_name: String,
+    pub(crate) moderator_channel_name: String,
+    /// Channel category to create the scheduling channel under, if it doesn't exist yet
+    pub(crate) channel_category_id: Option<u64>,
+    pub(crate) notify_before_race_secs: u64,
+    pub(crate) racing_emoji_name: String,
+    /// Minutes before race time at which unconfirmed racers get nagged, smallest to largest
+    pub(crate) nag_times: Vec<i64>,
+    /// People with any of these roles can run moderator commands
+    pub(crate) moderator_role_names: Vec<String>,
+    /// How many days ahead of time races in a series get created
+    pub(crate) series_days_ahead: u64,
+    /// Where race rooms get opened. The OAuth2 credentials come from the environment, see README.
+    pub(crate) racetime_url: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            client_id: CLIENT_ID,
+            owner_user_id: FOXLISK_USER_ID,
+            guild_names: vec![GUILD_NAME.to_string()],
+            scheduling_channel_name: SCHEDULING_CHANNEL_NAME.to_string(),
+            active_channel_name: ACTIVE_CHANNEL_NAME.to_string(),
+            moderator_channel_name: MODERATOR_CHANNEL_NAME.to_string(),
+            channel_category_id: CHANNEL_CATEGORY_ID,
+            notify_before_race_secs: NOTIFY_BEFORE_RACE_SECS,
+            racing_emoji_name: RACING_EMOJI_NAME.to_string(),
+            nag_times: NAG_TIMES.to_vec(),
+            moderator_role_names: MODERATOR_ROLE_NAMES.iter().map(|s| s.to_string()).collect(),
+            series_days_ahead: SERIES_DAYS_AHEAD,
+            racetime_url: RACETIME_URL.to_string(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config file named by `CONFIG_FILE`, or the defaults if that isn't set
+    pub(crate) fn load() -> Result<Config, ConfigError> {
+        match dotenv::var("CONFIG_FILE") {
+            Ok(path) => Config::from_file(&path),
+            Err(_) => {
+                info!("CONFIG_FILE not set, using the default config");
+                Ok(Config::default())
+            }
+        }
+    }
+
+    pub(crate) fn from_file(path: &str) -> Result<Config, ConfigError> {
+        let contents = fs::read_to_string(path).map_err(|e| ConfigError::Read {
+            path: path.to_string(),
+            err: e.to_string(),
+        })?;
+        let config = Config::from_toml(&contents).map_err(|e| ConfigError::Parse {
+            path: path.to_string(),
+            err: e.to_string(),
+        })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_toml(contents: &str) -> Result<Config, toml::de::Error> {
+        toml::from_str(contents)
+    }
+
+    /// Checks for values that would parse fine but make the bot misbehave.
+    /// Reports every problem at once, rather than making you fix them one at a time.
+    pub(crate) fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = vec![];
+        if self.client_id == 0 {
+            problems.push("client_id must be set".to_string());
+        }
+        if self.guild_names.is_empty() {
+            problems.push("guild_names must not be empty".to_string());
+        }
+        if self.guild_names.iter().any(|n| n.trim().is_empty()) {
+            problems.push("guild_names must not contain empty names".to_string());
+        }
+        if !self.racetime_url.starts_with("http://") && !self.racetime_url.starts_with("https://") {
+            problems.push("racetime_url must start with http:// or https://".to_string());
+        }
+        problems.extend(GuildSettings::from_config(self).problems());
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid {
+                problems: problems.join("; "),
+            })
+        }
+    }
+
+}
+
+/// The settings each guild can change for itself with `!settings`. Anything a guild hasn't set
+/// comes from the config file.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct GuildSettings {
+    pub(crate) scheduling_channel_name: String,
+    pub(crate) active_channel_name: String,
+    pub(crate) moderator_channel_name: String,
+    pub(crate) racing_emoji_name: String,
+    pub(crate) moderator_role_names: Vec<String>,
+    pub(crate) notify_before_race_secs: u64,
+    pub(crate) nag_times: Vec<i64>,
+    pub(crate) series_days_ahead: u64,
+}
+
+/// Upper bounds for the time settings. Anything bigger is surely a typo, and big enough values
+/// overflow when they get turned into dates.
+const MAX_NOTIFY_BEFORE_RACE_SECS: u64 = 3 * 24 * 60 * 60;
+const MAX_SERIES_DAYS_AHEAD: u64 = 365;
+
+/// Everything `GuildSettings::set` accepts, in the order `!settings show` lists them
+pub(crate) const SETTING_KEYS: [&str; 8] = [
+    "scheduling_channel_name",
+    "active_channel_name",
+    "moderator_channel_name",
+    "racing_emoji_name",
+    "moderator_role_names",
+    "notify_before_race_secs",
+    "nag_times",
+    "series_days_ahead",
+];
+
+impl GuildSettings {
+    pub(crate) fn from_config(config: &Config) -> Self {
+        GuildSettings {
+            scheduling_channel_name: config.scheduling_channel_name.clone(),
+            active_channel_name: config.active_channel_name.clone(),
+            moderator_channel_name: config.moderator_channel_name.clone(),
+            racing_emoji_name: config.racing_emoji_name.clone(),
+            moderator_role_names: config.moderator_role_names.clone(),
+            notify_before_race_secs: config.notify_before_race_secs,
+            nag_times: config.nag_times.clone(),
+            series_days_ahead: config.series_days_ahead,
+        }
+    }
+
+    /// Gets the value of a setting, formatted the way `set` takes it
+    pub(crate) fn get(&self, key: &str) -> Option<String> {
+        let value = match key {
+            "scheduling_channel_name" => self.scheduling_channel_name.clone(),
+            "active_channel_name" => self.active_channel_name.clone(),
+            "moderator_channel_name" => self.moderator_channel_name.clone(),
+            "racing_emoji_name" => self.racing_emoji_name.clone(),
+            "moderator_role_names" => self.moderator_role_names.join(","),
+            "notify_before_race_secs" => self.notify_before_race_secs.to_string(),
+            "nag_times" => self
+                .nag_times
+                .iter()
+                .map(|t| t.to_string())
+                .collect::<Vec<String>>()
+                .join(","),
+            "series_days_ahead" => self.series_days_ahead.to_string(),
+            _ => {
+                return None;
+            }
+        };
+        Some(value)
+    }
+
+    /// Parses and applies a setting. Lists are comma separated, e.g. `nag_times 15,30,60`.
+    /// Leaves the settings untouched and explains what's wrong if the value isn't valid.
+    pub(crate) fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        let mut updated = self.clone();
+        match key {
+            "scheduling_channel_name" => updated.scheduling_channel_name = value.to_string(),
+            "active_channel_name" => updated.active_channel_name = value.to_string(),
+            "moderator_channel_name" => updated.moderator_channel_name = value.to_string(),
+            "racing_emoji_name" => updated.racing_emoji_name = value.to_string(),
+            "moderator_role_names" => {
+                updated.moderator_role_names = value
+                    .split(',')
+                    .map(|r| r.trim().to_string())
+                    .filter(|r| !r.is_empty())
+                    .collect();
+            }
+            "notify_before_race_secs" => {
+                updated.notify_before_race_secs = value
+                    .parse::<u64>()
+                    .map_err(|_| format!("{} must be a whole number of seconds", key))?;
+            }
+            "nag_times" => {
+                updated.nag_times = if value.is_empty() {
+                    vec![]
+                } else {
+                    value
+                        .split(',')
+                        .map(|t| t.trim().parse::<i64>())
+                        .collect::<Result<Vec<i64>, _>>()
+                        .map_err(|_| format!("{} must be a list of minutes, like 15,30,60", key))?
+                };
+            }
+            "series_days_ahead" => {
+                updated.series_days_ahead = value
+                    .parse::<u64>()
+                    .map_err(|_| format!("{} must be a whole number of days", key))?;
+            }
+            _ => {
+                return Err(format!(
+                    "Unknown setting {}. Valid settings are: {}",
+                    key,
+                    SETTING_KEYS.join(", ")
+                ));
+            }
+        }
+        let problems = updated.problems();
+        if !problems.is_empty() {
+            return Err(problems.join("; "));
+        }
+        *self = updated;
+        Ok(())
+    }
+
+    fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        for (name, value) in [
+            ("scheduling_channel_name", &self.scheduling_channel_name),
+            ("active_channel_name", &self.active_channel_name),
+            ("moderator_channel_name", &self.moderator_channel_name),
+            ("racing_emoji_name", &self.racing_emoji_name),
+        ]
+        .iter()
+        {
+            if value.trim().is_empty() {
+                problems.push(format!("{} must not be empty", name));
+            }
+        }
+        if self.moderator_role_names.is_empty() {
+            problems.push("moderator_role_names must not be empty".to_string());
+        }
+        if self.notify_before_race_secs == 0 {
+            problems.push("notify_before_race_secs must be positive".to_string());
+        }
+        if self.notify_before_race_secs > MAX_NOTIFY_BEFORE_RACE_SECS {
+            problems.push(format!(
+                "notify_before_race_secs must be at most {}",
+                MAX_NOTIFY_BEFORE_RACE_SECS
+            ));
+        }
+        if self.nag_times.iter().any(|t| *t <= 0) {
+            problems.push("nag_times must all be positive".to_string());
+        }
+        if self.nag_times.windows(2).any(|w| w[0] >= w[1]) {
+            problems.push("nag_times must go from smallest to largest".to_string());
+        }
+        if self.series_days_ahead == 0 {
+            problems.push("series_days_ahead must be positive".to_string());
+        }
+        if self.series_days_ahead > MAX_SERIES_DAYS_AHEAD {
+            problems.push(format!(
+                "series_days_ahead must be at most {}",
+                MAX_SERIES_DAYS_AHEAD
+            ));
+        }
+        problems
+    }
+
+    pub(crate) fn notify_before_race(&self) -> Duration {
+        Duration::from_secs(self.notify_before_race_secs)
+    }
+
+    pub(crate) fn series_lookahead(&self) -> Duration {
+        Duration::from_secs(self.series_days_ahead * 24 * 60 * 60)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::{Config, GuildSettings, SETTING_KEYS};
+
+    #[test]
+    fn test_defaults_are_valid() {
+        assert!(Config::default().validate().is_ok());
+        assert_eq!(Config::default(), Config::from_toml("").unwrap());
+    }
+
+    #[test]
+    fn test_from_toml() {
+        let config = Config::from_toml(
+            r#"
+guild_names = ["Fox's test server", "RetroSpeedRuns"]
+scheduling_channel_name = "schedule-test"
+channel_category_id = 1234
+nag_times = [1, 4]
+"#,
+        )
+        .unwrap();
+        assert_eq!(vec!["Fox's test server", "RetroSpeedRuns"], config.guild_names);
+        assert_eq!("schedule-test", config.scheduling_channel_name);
+        assert_eq!(Some(1234), config.channel_category_id);
+        assert_eq!(vec![1, 4], config.nag_times);
+        // unspecified stuff keeps its default
+        assert_eq!(Config::default().active_channel_name, config.active_channel_name);
+
+        assert!(Config::from_toml("guild_nmaes = [\"typo\"]").is_err());
+        assert!(Config::from_toml("nag_times = 15").is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = Config {
+            guild_names: vec![],
+            nag_times: vec![30, 15],
+            racetime_url: "racetime.gg".to_string(),
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err().to_string();
+        assert_eq!(
+            "Invalid config: guild_names must not be empty; racetime_url must start with http:// or \
+            https://; nag_times must go from smallest to largest",
+            err
+        );
+    }
+
+    #[test]
+    fn test_guild_settings() {
+        let mut settings = GuildSettings::from_config(&Config::default());
+        for key in SETTING_KEYS.iter() {
+            let value = settings.get(key).unwrap();
+            assert_eq!(Ok(()), settings.set(key, &value), "{} doesn't round trip", key);
+        }
+        assert_eq!(GuildSettings::from_config(&Config::default()), settings);
+
+        assert_eq!(Ok(()), settings.set("nag_times", " 5, 10 "));
+        assert_eq!(vec![5, 10], settings.nag_times);
+        assert_eq!(Ok(()), settings.set("nag_times", ""));
+        assert!(settings.nag_times.is_empty());
+        assert_eq!(Ok(()), settings.set("moderator_role_names", "Mods, Race Organizer"));
+        assert_eq!(vec!["Mods", "Race Organizer"], settings.moderator_role_names);
+
+        let before = settings.clone();
+        assert_eq!(
+            Err("nag_times must go from smallest to largest".to_string()),
+            settings.set("nag_times", "30,15")
+        );
+        assert!(settings.set("notify_before_race_secs", "soon").is_err());
+        assert_eq!(
+            Err("notify_before_race_secs must be at most 259200".to_string()),
+            settings.set("notify_before_race_secs", "18446744073709551615")
+        );
+        assert_eq!(
+            Err("series_days_ahead must be at most 365".to_string()),
+            settings.set("series_days_ahead", "366")
+        );
+        assert!(settings.set("racing_emoji_name", " ").is_err());
+        assert!(settings.set("guild_names", "Somewhere").is_err());
+        assert_eq!(before, settings);
+    }
+}
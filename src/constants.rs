@@ -1,8 +1,19 @@
+// Defaults for anything not set in the config file. See config.rs
 pub const CLIENT_ID: u64 = 842994569204334602;
 pub const FOXLISK_USER_ID: u64 = 255676979460702210;
+pub const GUILD_NAME: &str = "RetroSpeedRuns";
 // pub const SCHEDULING_CHANNEL_NAME: &str = "🤖schedule-test";
 // pub const ACTIVE_CHANNEL_NAME: &str = "🦊fox-hole";
 pub const SCHEDULING_CHANNEL_NAME: &str = "📅schedule";
 pub const ACTIVE_CHANNEL_NAME: &str = "📅schedule";
+pub const MODERATOR_CHANNEL_NAME: &str = "moderators";
+pub const CHANNEL_CATEGORY_ID: Option<u64> = Some(798390141496197212);
 pub const NOTIFY_BEFORE_RACE_SECS: u64 = 60 * 30;
 pub const RACING_EMOJI_NAME: &str = "raisinghand";
+// times, in minutes, from start-time at which racers should be alerted
+pub const NAG_TIMES: [i64; 3] = [15, 30, 60];
+pub const MODERATOR_ROLE_NAMES: [&str; 2] = ["Moderator", "Admin"];
+// how far ahead of time recurring races get posted
+pub const SERIES_DAYS_AHEAD: u64 = 7;
+// racetime.gg rooms get opened here; see racetime.rs
+pub const RACETIME_URL: &str = "https://racetime.gg";
@@ -1,5 +1,6 @@
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use custom_error::custom_error;
@@ -9,101 +10,164 @@ use tokio::sync::RwLock;
 use twilight_cache_inmemory::{InMemoryCache, ResourceType};
 use twilight_command_parser::{Arguments, CaseSensitivity, Command, CommandParserConfig, Parser};
 use twilight_gateway::cluster::ShardScheme;
-use twilight_gateway::{Cluster, Event};
+use twilight_gateway::{Cluster, Event, EventTypeFlags};
 use twilight_http::request::channel::reaction::RequestReactionType;
 use twilight_http::{Client as HttpClient, Client};
 use twilight_model::gateway::payload::{GuildCreate, MessageCreate};
 use twilight_model::gateway::Intents;
-use twilight_model::guild::{Emoji, PartialMember, Permissions, Role};
+use twilight_model::guild::{Emoji, Permissions, Role};
 use twilight_model::id::{ChannelId, EmojiId, GuildId, MessageId, RoleId, UserId};
 
-use crate::constants::{
-    ACTIVE_CHANNEL_NAME, FOXLISK_USER_ID, NOTIFY_BEFORE_RACE_SECS, RACING_EMOJI_NAME,
-    SCHEDULING_CHANNEL_NAME,
+use crate::config::{Config, GuildSettings, SETTING_KEYS};
+use crate::time_parser::{self, find_timezone, format_finish_time, parse_finish_time};
+use crate::interactions::{
+    self, ButtonStyle, CommandData, Interaction, APPLICATION_COMMAND,
+    APPLICATION_COMMAND_AUTOCOMPLETE, MESSAGE_COMPONENT,
 };
+use serde_json::Value;
 use twilight_http::request::guild::role::CreateRole;
 
-use chrono::{DateTime, Duration as CDuration, Local, LocalResult, NaiveDateTime, TimeZone};
-use chrono_tz::Tz;
+use chrono::{DateTime, Datelike, Duration as CDuration, Local, TimeZone, Utc};
+use chrono_tz::{Tz, TZ_VARIANTS};
 use chrono_tz::US::Eastern;
 use futures::TryStreamExt;
 use sqlx::sqlite::SqlitePoolOptions;
-use sqlx::{SqlitePool, Row};
+use sqlx::{Row, Sqlite, SqlitePool, Transaction};
 use std::iter::FromIterator;
+use std::str::FromStr;
 use tokio::time::Duration;
-use twilight_model::channel::{ChannelType, ReactionType};
+use twilight_model::channel::{ChannelType, GuildChannel, Reaction, ReactionType};
 use twilight_model::user::User;
 
-use crate::models::{Category, Game, Race, RaceState};
+use crate::models::{
+    Category, EntrantKind, EntrantStatus, Game, Race, RaceEntrant, RaceResult, RaceSeries,
+    RaceState, RatingChange, Recurrence,
+};
+use crate::racetime::{HttpRacetimeClient, NewRoom, RacetimeClient, RoomData};
+use crate::ratings::{rate_race, Outcome, INITIAL_RATING};
 use lru::LruCache;
 use sqlx::migrate::Migrator;
 use std::path::Path;
 
 struct BotState {
+    config: Config,
     http: Client,
     cluster: Cluster,
     cache: InMemoryCache,
     parser: Parser<'static>,
-    // these should be split by guild
-    roles: RwLock<HashMap<String, Role>>,
-    channels: RwLock<HashMap<String, ChannelId>>,
-    emojis: RwLock<HashMap<String, Emoji>>,
-    // TODO: this can't possibly be the best way to do this lol
-    guild_id: RwLock<Option<GuildId>>,
-    racers: RwLock<HashMap<i64, HashSet<UserId>>>,
+    // all of these are keyed by guild, then by name
+    roles: RwLock<HashMap<GuildId, HashMap<String, Role>>>,
+    channels: RwLock<HashMap<GuildId, HashMap<String, ChannelId>>>,
+    emojis: RwLock<HashMap<GuildId, HashMap<String, Emoji>>>,
+    // each guild's settings, as stored in the guild_settings table
+    settings: RwLock<HashMap<GuildId, GuildSettings>>,
+    // guilds we've finished setting up
+    guild_ids: RwLock<HashSet<GuildId>>,
+    // None unless racetime.gg credentials are set
+    racetime: Option<Box<dyn RacetimeClient + Send + Sync>>,
 }
 
 enum Reactions {
     CONFIRMING,
     COMMENTATING,
     RESTREAMING,
+    CLEANSING,
 }
 
 impl Reactions {
     fn get_reaction_type(&self) -> ReactionType {
         ReactionType::Unicode {
-            name: self.get_name(),
+            name: self.get_emoji(),
         }
     }
 
+    /// The name to use in message text, i.e. `:name:`
     fn get_name(&self) -> String {
         match self {
             Reactions::CONFIRMING => "✅".to_string(),
 
             Reactions::COMMENTATING => "microphone2".to_string(),
             Reactions::RESTREAMING => "tv".to_string(),
+            Reactions::CLEANSING => "broom".to_string(),
+        }
+    }
+
+    /// The actual unicode emoji, which is what the API deals in
+    fn get_emoji(&self) -> String {
+        match self {
+            Reactions::CONFIRMING => "✅".to_string(),
+
+            Reactions::COMMENTATING => "🎙️".to_string(),
+            Reactions::RESTREAMING => "📺".to_string(),
+            Reactions::CLEANSING => "🧹".to_string(),
         }
     }
 }
 
+/// The role volunteers get while a race they volunteered for is active. Racers have two roles
+/// (unconfirmed and active), so they aren't covered here.
+fn volunteer_role_name(kind: EntrantKind) -> Option<&'static str> {
+    match kind {
+        EntrantKind::RACER => None,
+        EntrantKind::COMMENTATOR => Some("active-commentator"),
+        EntrantKind::RESTREAMER => Some("active-restreamer"),
+    }
+}
+
 impl BotState {
-    async fn get_guild_id(&self) -> Option<GuildId> {
-        let lock = self.guild_id.read().await;
-        (*lock).clone()
+    /// The guild's settings, or the config file defaults if we haven't loaded them yet
+    async fn get_settings(&self, guild_id: GuildId) -> GuildSettings {
+        let lock = self.settings.read().await;
+        lock.get(&guild_id)
+            .cloned()
+            .unwrap_or_else(|| GuildSettings::from_config(&self.config))
+    }
+
+    async fn get_racing_reaction(&self, guild_id: GuildId) -> Option<ReactionType> {
+        let emoji_name = self.get_settings(guild_id).await.racing_emoji_name;
+        let lock = self.emojis.read().await;
+        lock.get(&guild_id)
+            .and_then(|emojis| emojis.get(&emoji_name))
+            .map(|e| ReactionType::Custom {
+                animated: false,
+                id: e.id,
+                name: Some(e.name.clone()),
+            })
+    }
+
+    async fn get_guild_ids(&self) -> Vec<GuildId> {
+        let lock = self.guild_ids.read().await;
+        lock.iter().cloned().collect()
     }
 
-    async fn get_role(&self, name: &str) -> Option<Role> {
+    /// Gets one of the roles we manage
+    async fn get_role(&self, guild_id: GuildId, name: &str) -> Option<Role> {
         let lock = self.roles.read().await;
-        match lock.get(name) {
-            Some(r) => Some(r.clone()),
-            None => None,
-        }
+        lock.get(&guild_id).and_then(|roles| roles.get(name)).cloned()
     }
-}
 
-macro_rules! loop_until_success {
-    ($e:expr) => {
-        loop {
-            match $e {
-                Some(c) => {
-                    break c;
-                }
-                None => {}
-            };
+    /// Gets every role we manage in the guild
+    async fn get_roles(&self, guild_id: GuildId) -> Vec<Role> {
+        let lock = self.roles.read().await;
+        lock.get(&guild_id)
+            .map(|roles| roles.values().cloned().collect())
+            .unwrap_or_default()
+    }
 
-            tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
-        }
-    };
+    async fn get_channel(&self, guild_id: GuildId, name: &str) -> Option<ChannelId> {
+        let lock = self.channels.read().await;
+        lock.get(&guild_id).and_then(|channels| channels.get(name)).cloned()
+    }
+
+    /// Finds any role in the guild by name, including ones we don't manage
+    fn find_guild_role(&self, guild_id: GuildId, name: &str) -> Option<Role> {
+        self.cache
+            .guild_roles(guild_id)?
+            .into_iter()
+            .filter_map(|r| self.cache.role(r))
+            .find(|r| r.name == name)
+            .map(|r| (*r).clone())
+    }
 }
 
 /*
@@ -167,7 +231,7 @@ impl<'a> DesiredRole {
     }
 }
 
-pub async fn run_bot() -> Result<(), Box<dyn Error + Send + Sync>> {
+pub async fn run_bot(config: Config) -> Result<(), Box<dyn Error + Send + Sync>> {
     // This is the default scheme. It will automatically create as many
     // shards as is suggested by Discord.
     let intents = Intents::GUILD_MESSAGES | Intents::GUILDS | Intents::GUILD_MESSAGE_REACTIONS;
@@ -200,20 +264,57 @@ pub async fn run_bot() -> Result<(), Box<dyn Error + Send + Sync>> {
 
     let mut command_config = CommandParserConfig::new();
 
-    // TODO: manage games and categories via command
-
     // TODO: use a higher-powered command parser
     command_config.add_command("bot", true);
     command_config.add_command("listgames", true);
     command_config.add_command("listcategories", true);
+    command_config.add_command("addgame", true);
+    command_config.add_command("addcategory", true);
+    command_config.add_command("editgame", true);
+    command_config.add_command("editcategory", true);
+    command_config.add_command("retirecategory", true);
     command_config.add_command("newrace", true);
+    command_config.add_command("racenote", true);
     command_config.add_command("endrace", true);
+    command_config.add_command("cancelrace", true);
+    command_config.add_command("reschedule", true);
+    command_config.add_command("newseries", true);
+    command_config.add_command("listseries", true);
+    command_config.add_command("endseries", true);
+    command_config.add_command("resync", true);
+    command_config.add_command("races", true);
+    command_config.add_command("race", true);
+    command_config.add_command("start", true);
+    command_config.add_command("done", true);
+    command_config.add_command("forfeit", true);
+    command_config.add_command("setresult", true);
+    command_config.add_command("leaderboard", true);
+    command_config.add_command("pb", true);
+    command_config.add_command("rating", true);
+    command_config.add_command("ratings", true);
+    command_config.add_command("crew", true);
+    command_config.add_command("assign", true);
+    command_config.add_command("settings", true);
+    command_config.add_command("timezone", true);
+    command_config.add_command("racetime", true);
     command_config.add_command("commands", true);
     command_config.add_prefix("!");
 
     let parser = Parser::new(command_config);
 
+    let racetime: Option<Box<dyn RacetimeClient + Send + Sync>> = match (
+        dotenv::var("RACETIME_CLIENT_ID"),
+        dotenv::var("RACETIME_CLIENT_SECRET"),
+    ) {
+        (Ok(id), Ok(secret)) => Some(Box::new(HttpRacetimeClient::new(&config.racetime_url, id, secret))),
+        _ => {
+            info!("RACETIME_CLIENT_ID or RACETIME_CLIENT_SECRET not set, not opening racetime.gg rooms");
+            None
+        }
+    };
+
     let bot_state = Arc::new(BotState {
+        config,
         http: http_client,
         cluster,
         cache,
@@ -221,8 +322,9 @@ pub async fn run_bot() -> Result<(), Box<dyn Error + Send + Sync>> {
         roles: Default::default(),
         channels: Default::default(),
         emojis: Default::default(),
-        guild_id: Default::default(),
-        racers: Default::default(),
+        settings: Default::default(),
+        guild_ids: Default::default(),
+        racetime,
     });
 
     // let foxhole_msgs = bot_state
@@ -286,14 +388,12 @@ async fn get_pool() -> Result<SqlitePool, sqlx::Error> {
 }
 
 // TODO: make this configurable?
-fn nag_times(max: i64) -> Vec<i64> {
+fn nag_times(times: &[i64], max: i64) -> Vec<i64> {
     // times, in minutes, from start-time at which racers should be alerted
     // must go from smallest to largest
-    // i.e. vec![15, 30] means racers should be alerted once at 30 minutes-to-race-time and again
+    // i.e. [15, 30] means racers should be alerted once at 30 minutes-to-race-time and again
     // at 15-minutes-to-race-time
-    let times = vec![15, 30, 60];
-    // let times = vec![1, 4];
-    times.into_iter().take_while(|i| *i < max).collect()
+    times.iter().copied().take_while(|i| *i < max).collect()
 }
 
 async fn cron(bot_state: Arc<BotState>, pool: SqlitePool) {
@@ -320,68 +420,128 @@ async fn cron(bot_state: Arc<BotState>, pool: SqlitePool) {
      */
     let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60 * 1));
     // honestly, 5 would be fine, but who wants to worry?
-    let mut sent_nags: LruCache<i64, Vec<i64>> = LruCache::new(100);
-    let scheduling_channel: ChannelId =
-        loop_until_success!(get_scheduling_channel(bot_state.clone()).await);
-    let active_channel: ChannelId =
-        loop_until_success!(get_active_channel(bot_state.clone()).await);
-    let racing_react: ReactionType = loop_until_success!({
-        let lock = bot_state.emojis.read().await;
-        lock.get(RACING_EMOJI_NAME).map(|e| ReactionType::Custom {
-            animated: false,
-            id: e.id,
-            name: Some(e.name.clone()),
-        })
-    });
+    // keyed by (race id, start time) so that rescheduled races get a fresh set of nags
+    let mut sent_nags: LruCache<(i64, i64), Vec<i64>> = LruCache::new(100);
+    // same keying as sent_nags; we only want to bug the mods once per race
+    let mut restream_warnings: LruCache<(i64, i64), ()> = LruCache::new(100);
+    // keyed by (race id, when it started), so a race that gets !start-ed late gets warned again
+    let mut end_warnings: LruCache<(i64, i64), ()> = LruCache::new(100);
+    // how many times in a row each race's racetime.gg room has failed to load
+    let mut room_failures: HashMap<i64, u32> = HashMap::new();
 
-    let unconfirmed_racer_role: Role =
-        loop_until_success!({ bot_state.get_role("unconfirmed-racer").await });
-
-    let confirmed_racer_role: Role =
-        loop_until_success!({ bot_state.get_role("active-racer").await });
-
-    debug!("Cron has found necessary state");
     loop {
         interval.tick().await;
         debug!("Starting cron tick");
-
-        let start_time_eastern = Local::now().with_timezone(&Eastern);
-
-        let races = get_upcoming_races(Duration::from_secs(NOTIFY_BEFORE_RACE_SECS), &pool).await;
-        for race in races {
-            debug!("Handling upcoming race {}", race);
-            handle_upcoming_race(
+        for gid in bot_state.get_guild_ids().await {
+            cron_guild(
+                gid,
                 bot_state.clone(),
                 &pool,
-                scheduling_channel,
-                active_channel,
-                &racing_react,
-                &unconfirmed_racer_role,
-                race,
+                &mut sent_nags,
+                &mut restream_warnings,
+                &mut end_warnings,
+                &mut room_failures,
             )
             .await;
         }
+    }
+}
+
+/// One cron tick's worth of work for a single guild. Guilds that aren't fully set up yet are
+/// skipped until they are.
+async fn cron_guild(
+    gid: GuildId,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+    sent_nags: &mut LruCache<(i64, i64), Vec<i64>>,
+    restream_warnings: &mut LruCache<(i64, i64), ()>,
+    end_warnings: &mut LruCache<(i64, i64), ()>,
+    room_failures: &mut HashMap<i64, u32>,
+) {
+    let (scheduling_channel, active_channel, unconfirmed_racer_role, confirmed_racer_role) = match (
+        get_scheduling_channel(gid, bot_state.clone()).await,
+        get_active_channel(gid, bot_state.clone()).await,
+        bot_state.get_role(gid, "unconfirmed-racer").await,
+        bot_state.get_role(gid, "active-racer").await,
+    ) {
+        (Some(sc), Some(ac), Some(ur), Some(cr)) => (sc, ac, ur, cr),
+        _ => {
+            debug!("Guild {} is missing state needed for cron, skipping", gid);
+            return;
+        }
+    };
+
+    schedule_series(gid, bot_state.clone(), pool).await;
+
+    let start_time_eastern = Local::now().with_timezone(&Eastern);
+    let settings = bot_state.get_settings(gid).await;
+
+    let races = get_upcoming_races(gid, settings.notify_before_race(), pool).await;
+    for race in races {
+        debug!("Handling upcoming race {}", race);
+        handle_upcoming_race(
+            gid,
+            bot_state.clone(),
+            pool,
+            scheduling_channel,
+            active_channel,
+            &unconfirmed_racer_role,
+            race,
+        )
+        .await;
+    }
 
-        let active_races = get_active_races(&pool).await;
+    let active_races = get_active_races(gid, pool).await;
 
-        for active_race in active_races {
-            debug!("Handling active race {}", active_race);
+    for mut active_race in active_races {
+        debug!("Handling active race {}", active_race);
 
-            // races shouldn't last 3 hours!
-            // unless we start doing chrono trigger or something
-            let time_til_start = active_race.get_occurs() - start_time_eastern;
-            let minutes_til_start = time_til_start.num_minutes();
-            if time_til_start.num_hours() < -2 {
-                // long past
-                _end_race(Some(active_race.id), &pool, bot_state.clone()).await;
+        if let Some(client) = &bot_state.racetime {
+            if let Some(ended) =
+                check_racetime_room(gid, &mut active_race, client.as_ref(), room_failures, pool).await
+            {
+                remove_race_roles(gid, &ended, bot_state.clone(), pool).await;
+                post_results(gid, &ended, bot_state.clone(), pool).await;
                 continue;
             }
-            let active_message_id = active_race.get_active_message_id();
-            if active_message_id.is_none() {
-                warn!("Race {} is supposed to have an active message id but doesn't", active_race.id);
+        }
+        let time_til_start = active_race.get_occurs() - start_time_eastern;
+        let minutes_til_start = time_til_start.num_minutes();
+
+        // races with a racetime.gg room normally end when the room does, but still get ended here
+        // if the room never finishes
+        let timer_key = (active_race.id, active_race.start_timestamp());
+        match race_timer(&active_race, start_time_eastern.timestamp(), pool).await {
+            RaceTimer::Over => {
+                if let (_, Some(ended)) = _end_race(gid, Some(active_race.id), pool).await {
+                    remove_race_roles(gid, &ended, bot_state.clone(), pool).await;
+                    post_results(gid, &ended, bot_state.clone(), pool).await;
+                }
                 continue;
             }
+            RaceTimer::EndingSoon(ends) if !end_warnings.contains(&timer_key) => {
+                warn_race_ending(gid, &active_race, ends, bot_state.clone(), pool).await;
+                end_warnings.put(timer_key, ());
+            }
+            _ => {}
+        }
+
+        let race_key = (active_race.id, active_race.occurs);
+        if minutes_til_start >= 0 && !restream_warnings.contains(&race_key) {
+            if get_assigned_crew(active_race.id, EntrantKind::RESTREAMER, pool).await.is_empty() {
+                warn_no_restreamer(gid, &active_race, bot_state.clone(), pool).await;
+            }
+            restream_warnings.put(race_key, ());
+        }
 
+        let active_message_id = active_race.get_active_message_id();
+        if active_message_id.is_none() {
+            warn!("Race {} is supposed to have an active message id but doesn't", active_race.id);
+            continue;
+        }
+
+        // button races get confirmed when the button is pressed instead
+        if !active_race.signup_buttons {
             let confirmed_reactions = match get_reactions_for(
                 bot_state.clone(),
                 active_channel,
@@ -402,54 +562,235 @@ async fn cron(bot_state: Arc<BotState>, pool: SqlitePool) {
                 if user.id == my_id {
                     continue;
                 }
-                debug!("Removing unconfirmed role and setting active role for {}", user.name);
-                remove_role(&user.id, &unconfirmed_racer_role, bot_state.clone()).await;
-                add_role(&user, &confirmed_racer_role, bot_state.clone()).await;
-            }
-            {
-                let mut lock = bot_state.racers.write().await;
-                let set = lock.entry(active_race.id).or_insert(Default::default());
-                for user in &confirmed_reactions {
-                    if user.id != my_id {
-                        set.insert(user.id);
+                let kinds = set_confirmation(active_race.id, user.id, true, pool).await;
+                if kinds.contains(&EntrantKind::RACER) {
+                    debug!("Removing unconfirmed role and setting active role for {}", user.name);
+                    if let Err(e) =
+                        remove_role(gid, &user.id, &unconfirmed_racer_role, bot_state.clone()).await
+                    {
+                        warn!("{}", e);
+                    }
+                    if let Err(e) =
+                        add_role(gid, &user.id, &confirmed_racer_role, bot_state.clone()).await
+                    {
+                        warn!("{}", e);
                     }
                 }
             }
+        }
 
-            if !sent_nags.contains(&active_race.id) {
-                sent_nags.put(active_race.id, nag_times(time_til_start.num_minutes()));
-            }
-            let nags = sent_nags.get_mut(&active_race.id).unwrap();
-            let do_nag = match nags.pop() {
-                Some(time) => {
-                    if minutes_til_start < time {
-                        true
-                    } else {
-                        nags.push(time);
-                        false
-                    }
+        if !sent_nags.contains(&race_key) {
+            sent_nags.put(
+                race_key,
+                nag_times(&settings.nag_times, time_til_start.num_minutes()),
+            );
+        }
+        let nags = sent_nags.get_mut(&race_key).unwrap();
+        let do_nag = match nags.pop() {
+            Some(time) => {
+                if minutes_til_start < time {
+                    true
+                } else {
+                    nags.push(time);
+                    false
                 }
-                None => false,
-            };
+            }
+            None => false,
+        };
+
+        if do_nag {
+            debug!("Sending nag re: current race");
+            if let Err(e) = bot_state
+                .http
+                .create_message(active_channel)
+                .content(format!(
+                     "<@&{}> You reported interest in the upcoming {} - {} race and have yet to confirm. \
+                    Please {} above!" ,
+                    unconfirmed_racer_role.id,
+                    Game::get_by_id(active_race.game_id, pool).await.unwrap().name_pretty,
+                    Category::get_by_id(active_race.category_id, pool).await.unwrap().name_pretty,
+                    if active_race.signup_buttons { "press Confirm" } else { "react" }
+                ))
+                .unwrap()
+                .await
+            {
+                warn!("Error nagging unconfirmed racers for {}: {}", active_race, e);
+            }
+        }
+        debug!("Finished with active race");
+    }
+    debug!("Finished with all active races");
+}
+
+// races in categories without a max_duration get ended this long after they start
+const DEFAULT_MAX_RACE_SECS: i64 = 3 * 60 * 60;
+// how long before a race gets ended automatically that everyone gets warned
+const END_WARNING_MINS: i64 = 15;
+
+/// Where an active race is compared to its category's max duration
+#[derive(Debug, PartialEq)]
+enum RaceTimer {
+    Running,
+    /// Gets ended at this timestamp, less than END_WARNING_MINS from now
+    EndingSoon(i64),
+    Over,
+}
+
+async fn race_timer(race: &Race, now: i64, pool: &SqlitePool) -> RaceTimer {
+    let max = Category::get_by_id(race.category_id, pool)
+        .await
+        .and_then(|c| c.max_duration)
+        .unwrap_or(DEFAULT_MAX_RACE_SECS);
+    let ends = race.start_timestamp().saturating_add(max);
+    if now >= ends {
+        RaceTimer::Over
+    } else if ends - now <= END_WARNING_MINS * 60 {
+        RaceTimer::EndingSoon(ends)
+    } else {
+        RaceTimer::Running
+    }
+}
 
-            if do_nag {
-                debug!("Sending nag re: current race");
-                bot_state
-                    .http
-                    .create_message(active_channel)
-                    .content(format!(
-                         "<@&{}> You reported interest in the upcoming {} - {} race and have yet to confirm. \
-                        Please react above!" ,
-                        unconfirmed_racer_role.id,
-                        Game::get_by_id(active_race.game_id, &pool).await.unwrap().name_pretty,
-                        Category::get_by_id(active_race.category_id, &pool).await.unwrap().name_pretty
-                    ))
-                    .unwrap()
-                    .await;
+/// Tells the racers who haven't reported a result yet, and the moderators, that a race is about
+/// to get ended automatically
+async fn warn_race_ending(gid: GuildId, race: &Race, ends: i64, bot_state: Arc<BotState>, pool: &SqlitePool) {
+    let results = get_results(race.id, pool).await;
+    let racers: Vec<String> = get_entrants(race.id, EntrantKind::RACER, pool)
+        .await
+        .iter()
+        .filter(|e| e.get_status() != EntrantStatus::WITHDRAWN)
+        .filter(|e| !results.iter().any(|r| r.user_id == e.user_id))
+        .map(|e| format!("<@{}>", e.user_id))
+        .collect();
+    match (racers.is_empty(), get_active_channel(gid, bot_state.clone()).await) {
+        (false, Some(cid)) => {
+            if let Err(e) = bot_state
+                .http
+                .create_message(cid)
+                .content(format!(
+                    "{} {} gets ended automatically <t:{}:R>. Report how it went with !done <time> or \
+                    !forfeit before then.",
+                    racers.join(" "),
+                    race,
+                    ends
+                ))
+                .unwrap()
+                .allowed_mentions()
+                .parse_users()
+                .build()
+                .await
+            {
+                warn!("Error warning racers that {} is ending: {}", race, e);
             }
-            debug!("Finished with active race");
         }
-        debug!("Finished with all active races");
+        (false, None) => {
+            warn!("No active channel found to warn racers that {} is ending", race);
+        }
+        (true, _) => {}
+    }
+
+    let cid = match get_moderator_channel(gid, bot_state.clone()).await {
+        Some(c) => c,
+        None => {
+            warn!("No moderator channel found to warn that {} is ending", race);
+            return;
+        }
+    };
+    let mod_mention = moderator_mention(gid, &bot_state).await;
+    let (game, cat) = match (
+        Game::get_by_id(race.game_id, pool).await,
+        Category::get_by_id(race.category_id, pool).await,
+    ) {
+        (Some(g), Some(c)) => (g, c),
+        _ => {
+            warn!("Can't find the game or category for {}, not warning that it's ending", race);
+            return;
+        }
+    };
+    if let Err(e) = bot_state
+        .http
+        .create_message(cid)
+        .content(format!(
+            "{}{} ({} - {}) gets ended automatically <t:{}:R>, {} after it started. If it needs \
+            longer, use `!editcategory {} {} max <duration>`.",
+            mod_mention,
+            race,
+            game.name_pretty,
+            cat.name_pretty,
+            ends,
+            format_finish_time(ends - race.start_timestamp()),
+            game.name,
+            cat.name,
+        ))
+        .unwrap()
+        .allowed_mentions()
+        .parse_roles()
+        .build()
+        .await
+    {
+        warn!("Error warning moderators that {} is ending: {}", race, e);
+    }
+}
+
+/// Mentions of the guild's moderator roles to start a message with, or nothing if there aren't any
+async fn moderator_mention(gid: GuildId, bot_state: &BotState) -> String {
+    bot_state
+        .get_settings(gid)
+        .await
+        .moderator_role_names
+        .iter()
+        .filter_map(|name| bot_state.find_guild_role(gid, name))
+        .map(|r| format!("<@&{}> ", r.id))
+        .collect()
+}
+
+/// Lets the moderators know that a race is coming up soon and nobody is set to restream it
+async fn warn_no_restreamer(gid: GuildId, race: &Race, bot_state: Arc<BotState>, pool: &SqlitePool) {
+    let cid = match get_moderator_channel(gid, bot_state.clone()).await {
+        Some(c) => c,
+        None => {
+            warn!("No moderator channel found to warn about {}", race);
+            return;
+        }
+    };
+    let mod_mention = moderator_mention(gid, &bot_state).await;
+    let volunteers: Vec<String> = get_entrants(race.id, EntrantKind::RESTREAMER, pool)
+        .await
+        .iter()
+        .filter(|e| e.get_status() != EntrantStatus::WITHDRAWN)
+        .map(|e| format!("<@{}>", e.user_id))
+        .collect();
+    let volunteers = if volunteers.is_empty() {
+        "Nobody has volunteered.".to_string()
+    } else {
+        format!(
+            "Volunteers: {}. Use `!assign restream {} @user` to pick one.",
+            volunteers.join(", "),
+            race.id
+        )
+    };
+    let game = Game::get_by_id(race.game_id, pool).await.unwrap();
+    let cat = Category::get_by_id(race.category_id, pool).await.unwrap();
+    // ping the mods, but not the volunteers - it's not their job to sort this out
+    if let Err(e) = bot_state
+        .http
+        .create_message(cid)
+        .content(format!(
+            "{}{} ({} - {}) starts <t:{}:R> and nobody is assigned to restream it. {}",
+            mod_mention,
+            race,
+            game.name_pretty,
+            cat.name_pretty,
+            race.occurs,
+            volunteers,
+        ))
+        .unwrap()
+        .allowed_mentions()
+        .parse_roles()
+        .build()
+        .await
+    {
+        warn!("Error warning moderators about {}: {}", race, e);
     }
 }
 
@@ -475,11 +816,11 @@ async fn get_reactions_for(
 // this is a lot of parameters, but it's also annoying to get the reacts and channel ids and stuff
 // in here. idk man.
 async fn handle_upcoming_race(
+    gid: GuildId,
     bot_state: Arc<BotState>,
     pool: &SqlitePool,
     scheduling_channel: ChannelId,
     active_channel: ChannelId,
-    racing_react: &ReactionType,
     unconfirmed_racer_role: &Role,
     mut race: Race,
 ) {
@@ -489,58 +830,113 @@ async fn handle_upcoming_race(
        @message ppl (where? do we want a dedicated channel for this?)
        set race to ACTIVE
     */
-    // NB: as noted when building the cache, the msg.reactions field is not actually useful here
-    let racing_reactions = match get_reactions_for(
-        bot_state.clone(),
-        scheduling_channel,
-        race.get_scheduling_message_id().unwrap(),
-        racing_react.clone(),
-    )
-    .await
-    {
-        Some(users) => users,
-        None => {
-            return;
-        }
-    };
-
     let my_id = bot_state.cache.current_user().unwrap().id;
+    // races from before signup buttons keep their signups in the reactions
+    if !race.signup_buttons
+        && !record_reaction_signups(gid, bot_state.clone(), pool, scheduling_channel, &race, my_id)
+            .await
+    {
+        return;
+    }
 
-    for user in &racing_reactions {
-        if user.id == my_id {
+    for entrant in get_entrants(race.id, EntrantKind::RACER, pool).await {
+        if entrant.get_status() == EntrantStatus::WITHDRAWN {
             continue;
         }
-        add_role(user, &unconfirmed_racer_role, bot_state.clone()).await;
+        if let Some(user) = entrant.get_user_id() {
+            if let Err(e) = add_role(gid, &user, &unconfirmed_racer_role, bot_state.clone()).await {
+                warn!("{}", e);
+            }
+        }
     }
-    {
-        let mut lock = bot_state.racers.write().await;
-        let set = lock.entry(race.id).or_insert(Default::default());
-        for user in &racing_reactions {
-            if user.id != my_id {
-                set.insert(user.id);
+
+    let mut volunteer_roles = vec![];
+    for kind in [EntrantKind::COMMENTATOR, EntrantKind::RESTREAMER] {
+        let role = bot_state.get_role(gid, volunteer_role_name(kind).unwrap()).await;
+        let mut any_volunteers = false;
+        for entrant in get_entrants(race.id, kind, pool).await {
+            if entrant.get_status() == EntrantStatus::WITHDRAWN {
+                continue;
+            }
+            let user = match entrant.get_user_id() {
+                Some(u) => u,
+                None => {
+                    continue;
+                }
+            };
+            any_volunteers = true;
+            if let Some(r) = &role {
+                if let Err(e) = add_role(gid, &user, r, bot_state.clone()).await {
+                    warn!("{}", e);
+                }
             }
         }
+        if let (true, Some(r)) = (any_volunteers, role) {
+            volunteer_roles.push(r.id);
+        }
+    }
+
+    let (confirm_how, also_confirm_how) = if race.signup_buttons {
+        ("Press Confirm below".to_string(), "press Confirm".to_string())
+    } else {
+        let react = format!("react with :{}:", Reactions::CONFIRMING.get_name());
+        (format!("React with :{}:", Reactions::CONFIRMING.get_name()), react)
+    };
+    let mut content = format!(
+        "<@&{}> You reported interest in the {} - {} race on {}. {} to confirm please.",
+        unconfirmed_racer_role.id,
+        Game::get_by_id(race.game_id, pool).await.unwrap().name_pretty,
+        Category::get_by_id(race.category_id, pool).await.unwrap().name_pretty,
+        datetime_to_discord_format(&race.get_occurs()),
+        confirm_how
+    );
+    if !volunteer_roles.is_empty() {
+        content.push_str(&format!(
+            "\n{} Thanks for volunteering to help out with this race! Please {} too, so we know you can make it.",
+            volunteer_roles
+                .iter()
+                .map(|r| format!("<@&{}>", r))
+                .collect::<Vec<String>>()
+                .join(" "),
+            also_confirm_how
+        ));
+    }
+    if let Some(notes) = &race.notes {
+        content.push_str(&format!("\nNotes: {}", notes));
+    }
+    if let Some(client) = &bot_state.racetime {
+        if let Some(url) = open_racetime_room(&mut race, client.as_ref(), pool).await {
+            content.push_str(&format!("\nRace room: {}", url));
+        }
     }
 
     match bot_state
         .http
         .create_message(active_channel)
-        .content(format!(
-            "<@&{}> You reported interest in the {} - {} race on {}. React with :{}: to confirm please.",
-            unconfirmed_racer_role.id,
-            Game::get_by_id(race.game_id, pool).await.unwrap().name_pretty,
-            Category::get_by_id(race.category_id, pool).await.unwrap().name_pretty,
-            race.get_occurs().format("%B %d at %I:%M%P"),
-            Reactions::CONFIRMING.get_name()
-        ))
+        .content(content)
         .unwrap()
         .await
     {
         Ok(m) => {
             race.set_active_message_id(m.id);
-            bot_state.http
-                .create_reaction(active_channel, m.id, RequestReactionType::from(Reactions::CONFIRMING.get_reaction_type())).await;
-
+            if race.signup_buttons {
+                if let Err(e) = interactions::set_components(
+                    &bot_state.http,
+                    active_channel,
+                    m.id,
+                    confirm_buttons(race.id),
+                )
+                .await
+                {
+                    warn!("Error adding confirm button for {}: {}", race, e);
+                }
+            } else if let Err(e) = bot_state
+                .http
+                .create_reaction(active_channel, m.id, RequestReactionType::from(Reactions::CONFIRMING.get_reaction_type()))
+                .await
+            {
+                warn!("Error adding confirm reaction for {}: {}", race, e);
+            }
         }
         Err(e) => {
             warn!("Error creating confirmation message: {}", e);
@@ -548,31 +944,175 @@ async fn handle_upcoming_race(
     }
 
     race.set_state(RaceState::ACTIVE);
-    race.save(&pool).await;
+    if let Err(e) = race.save(&pool).await {
+        warn!("Error activating {}: {:?}", race, e);
+    }
+}
+
+/// Opens a racetime.gg room for the race if its category has a racetime.gg category, and remembers
+/// it on the race without saving. Returns the room's URL.
+async fn open_racetime_room(
+    race: &mut Race,
+    client: &(dyn RacetimeClient + Send + Sync),
+    pool: &SqlitePool,
+) -> Option<String> {
+    let cat = Category::get_by_id(race.category_id, pool).await?;
+    let game = Game::get_by_id(race.game_id, pool).await?;
+    let mut info = format!("{}: {} - {}", race, game.name_pretty, cat.name_pretty);
+    if let Some(notes) = &race.notes {
+        info.push_str(&format!(" ({})", notes));
+    }
+    let room = NewRoom {
+        category: cat.racetime_category?,
+        goal: cat.racetime_goal,
+        custom_goal: cat.name_pretty,
+        info,
+    };
+    match client.start_race(&room).await {
+        Ok(url) => {
+            race.racetime_room = Some(url.clone());
+            Some(url)
+        }
+        Err(e) => {
+            warn!("Error opening a racetime.gg room for {}: {}", race, e);
+            None
+        }
+    }
+}
+
+// how many cron ticks in a row a race's room can fail to load before we stop waiting on it
+const MAX_ROOM_FAILURES: u32 = 10;
+
+/// Checks on an active race's racetime.gg room. Once the room finishes, everyone in it who has
+/// linked their account gets their result recorded and the race is ended; the ended race is
+/// returned. If the room got cancelled, or can't be fetched MAX_ROOM_FAILURES times in a row,
+/// the race forgets about it and ends on its own schedule. `room_failures` counts those misses.
+async fn check_racetime_room(
+    gid: GuildId,
+    race: &mut Race,
+    client: &(dyn RacetimeClient + Send + Sync),
+    room_failures: &mut HashMap<i64, u32>,
+    pool: &SqlitePool,
+) -> Option<Race> {
+    let data = match client.get_race(race.racetime_room.as_ref()?).await {
+        Ok(d) => d,
+        Err(e) => {
+            warn!("Error checking the racetime.gg room for {}: {}", race, e);
+            let failures = room_failures.entry(race.id).or_default();
+            *failures += 1;
+            if *failures >= MAX_ROOM_FAILURES {
+                // the room is probably gone, so stop waiting on it and let the race end on its own
+                warn!("Giving up on the racetime.gg room for {} after {} tries", race, failures);
+                room_failures.remove(&race.id);
+                race.racetime_room = None;
+                if let Err(e) = race.save(pool).await {
+                    warn!("Error forgetting the racetime.gg room for {}: {:?}", race, e);
+                }
+            }
+            return None;
+        }
+    };
+    room_failures.remove(&race.id);
+    if !data.is_over() {
+        return None;
+    }
+    if data.is_cancelled() {
+        info!("The racetime.gg room for {} was cancelled", race);
+        race.racetime_room = None;
+        if let Err(e) = race.save(pool).await {
+            warn!("Error forgetting the racetime.gg room for {}: {:?}", race, e);
+        }
+        return None;
+    }
+    record_racetime_results(race, &data, pool).await;
+    _end_race(gid, Some(race.id), pool).await.1
+}
+
+/// Records the results of everyone in the room who finished or forfeited and has linked their
+/// racetime.gg account with !racetime
+async fn record_racetime_results(race: &Race, data: &RoomData, pool: &SqlitePool) {
+    for entrant in &data.entrants {
+        let finish_time = entrant.finish_seconds();
+        if finish_time.is_none() && !entrant.is_forfeit() {
+            continue;
+        }
+        let user_id = match get_racetime_link(&entrant.user.full_name, pool).await {
+            Some(u) => u,
+            None => {
+                debug!("Nobody has linked {} on racetime.gg", entrant.user.full_name);
+                continue;
+            }
+        };
+        if let Err(e) = set_result(race.id, user_id, finish_time, entrant.comment.as_deref(), pool).await {
+            warn!("Error recording the racetime.gg result for {} in {}: {}", user_id, race, e);
+        }
+    }
+}
+
+/// Marks everyone who reacted to a race's reaction-based scheduling message as interested.
+/// Returns false if the reactions couldn't be fetched.
+async fn record_reaction_signups(
+    gid: GuildId,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+    scheduling_channel: ChannelId,
+    race: &Race,
+    my_id: UserId,
+) -> bool {
+    let racing_react = match bot_state.get_racing_reaction(gid).await {
+        Some(r) => r,
+        None => {
+            warn!("Can't find racing emoji for guild {}", gid);
+            return false;
+        }
+    };
+    for (kind, react) in [
+        (EntrantKind::RACER, racing_react),
+        (EntrantKind::COMMENTATOR, Reactions::COMMENTATING.get_reaction_type()),
+        (EntrantKind::RESTREAMER, Reactions::RESTREAMING.get_reaction_type()),
+    ] {
+        // NB: as noted when building the cache, the msg.reactions field is not actually useful here
+        let users = match get_reactions_for(
+            bot_state.clone(),
+            scheduling_channel,
+            race.get_scheduling_message_id().unwrap(),
+            react,
+        )
+        .await
+        {
+            Some(users) => users,
+            // no point going ahead without the racers, but volunteers are optional
+            None if kind == EntrantKind::RACER => {
+                return false;
+            }
+            None => vec![],
+        };
+        for user in &users {
+            if user.id != my_id {
+                set_entrant_status(race.id, user.id, kind, EntrantStatus::INTERESTED, pool).await;
+            }
+        }
+    }
+    true
 }
 
-custom_error! { AddRoleError{err: String} = "Error adding role to user: {err}" }
+custom_error! { RoleError{err: String} = "Error changing roles: {err}" }
 
 // TODO: add/remove roles are identical except for the http function they call
 // this is not a hard problem to solve
 // although probably it should be solved by taking an FnMut or something
 // the easy ("easy") way is to just do a macro
 
-// this should take GuildId but since this is single-guild for now we can sneak it off of the role
-async fn add_role(user: &User, role: &Role, bot_state: Arc<BotState>) -> Result<(), AddRoleError> {
-    let gid = match bot_state.get_guild_id().await {
-        Some(g) => g,
-        None => {
-            debug!("can't find guild id in add_role");
-            return Err(AddRoleError {
-                err: "Cant find guild id???".to_string(),
-            });
-        }
-    };
+async fn add_role(
+    gid: GuildId,
+    user_id: &UserId,
+    role: &Role,
+    bot_state: Arc<BotState>,
+) -> Result<(), RoleError> {
     //
     // if let Some(has_role) = bot_state
     //     .cache
-    //     .member(gid.clone(), user.id.clone())
+    //     .member(gid.clone(), user_id.clone())
     //     .map(|m| m.roles.contains(&role.id))
     // {
     //     debug!("add_role: roles, found present: {}", has_role);
@@ -585,34 +1125,22 @@ async fn add_role(user: &User, role: &Role, bot_state: Arc<BotState>) -> Result<
 
     match bot_state
         .http
-        .add_guild_member_role(gid, user.id, role.id.clone())
+        .add_guild_member_role(gid, user_id.clone(), role.id.clone())
         .await
     {
         Ok(()) => Ok(()),
-        Err(e) => {
-            warn!("Error adding role: {}", e);
-            Err(AddRoleError {
-                err: "Error adding role".to_string(),
-            })
-        }
+        Err(e) => Err(RoleError {
+            err: format!("couldn't give {} the {} role: {}", user_id, role.name, e),
+        }),
     }
 }
 
-// this should take GuildId but since this is single-guild for now we can sneak it off of the role
 async fn remove_role(
+    gid: GuildId,
     user_id: &UserId,
     role: &Role,
     bot_state: Arc<BotState>,
-) -> Result<(), AddRoleError> {
-    let gid = match bot_state.get_guild_id().await {
-        Some(g) => g,
-        None => {
-            debug!("Can't find guild id in remove_role");
-            return Err(AddRoleError {
-                err: "Cant find guild id???".to_string(),
-            });
-        }
-    };
+) -> Result<(), RoleError> {
     //
     // if let Some(has_role) = bot_state
     //     .cache
@@ -633,12 +1161,9 @@ async fn remove_role(
         .await
     {
         Ok(()) => Ok(()),
-        Err(e) => {
-            warn!("Error adding role: {}", e);
-            Err(AddRoleError {
-                err: "Error adding role".to_string(),
-            })
-        }
+        Err(e) => Err(RoleError {
+            err: format!("couldn't take the {} role from {}: {}", role.name, user_id, e),
+        }),
     }
 }
 
@@ -655,14 +1180,19 @@ async fn get_reactions(
         .await
 }
 
-async fn get_scheduling_channel(bot_state: Arc<BotState>) -> Option<ChannelId> {
-    let lock = bot_state.channels.read().await;
-    lock.get(SCHEDULING_CHANNEL_NAME).map(|f| f.clone())
+async fn get_scheduling_channel(guild_id: GuildId, bot_state: Arc<BotState>) -> Option<ChannelId> {
+    let name = bot_state.get_settings(guild_id).await.scheduling_channel_name;
+    bot_state.get_channel(guild_id, &name).await
+}
+
+async fn get_active_channel(guild_id: GuildId, bot_state: Arc<BotState>) -> Option<ChannelId> {
+    let name = bot_state.get_settings(guild_id).await.active_channel_name;
+    bot_state.get_channel(guild_id, &name).await
 }
 
-async fn get_active_channel(bot_state: Arc<BotState>) -> Option<ChannelId> {
-    let lock = bot_state.channels.read().await;
-    lock.get(ACTIVE_CHANNEL_NAME).map(|f| f.clone())
+async fn get_moderator_channel(guild_id: GuildId, bot_state: Arc<BotState>) -> Option<ChannelId> {
+    let name = bot_state.get_settings(guild_id).await.moderator_channel_name;
+    bot_state.get_channel(guild_id, &name).await
 }
 
 async fn handle_events(
@@ -670,7 +1200,10 @@ async fn handle_events(
     pool: SqlitePool,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
     {
-        let mut events = bot_state.cluster.events();
+        // interactions only show up as raw payloads, see interactions.rs
+        let mut events = bot_state
+            .cluster
+            .some_events(EventTypeFlags::default() | EventTypeFlags::SHARD_PAYLOAD);
         while let Some((_, event)) = events.next().await {
             bot_state.cache.update(&event);
             handle_wrapper(event, bot_state.clone(), &pool).await;
@@ -680,6 +1213,96 @@ async fn handle_events(
     Ok(())
 }
 
+/// Where a command came from - a `!` message or a slash command - so replies can go back the
+/// same way.
+struct CommandContext {
+    guild_id: Option<GuildId>,
+    channel_id: ChannelId,
+    // whoever ran the command
+    user_id: Option<UserId>,
+    // roles of whoever ran the command. Empty outside of guilds.
+    roles: Vec<RoleId>,
+    interaction: Option<Interaction>,
+    // slash commands are deferred, so the first reply fills in the deferred response; anything
+    // after that has to be a followup
+    responded: AtomicBool,
+}
+
+impl CommandContext {
+    fn from_message(msg: &MessageCreate) -> Self {
+        CommandContext {
+            guild_id: msg.guild_id,
+            channel_id: msg.channel_id,
+            user_id: Some(msg.author.id),
+            roles: msg.member.as_ref().map(|m| m.roles.clone()).unwrap_or_default(),
+            interaction: None,
+            responded: AtomicBool::new(false),
+        }
+    }
+
+    fn from_interaction(interaction: Interaction) -> Option<Self> {
+        Some(CommandContext {
+            guild_id: interaction.guild_id,
+            channel_id: interaction.channel_id?,
+            user_id: interaction.member.as_ref().map(|m| m.user.id),
+            roles: interaction
+                .member
+                .as_ref()
+                .map(|m| m.roles.clone())
+                .unwrap_or_default(),
+            interaction: Some(interaction),
+            responded: AtomicBool::new(false),
+        })
+    }
+
+    /// The timezone whoever ran the command types times in
+    async fn timezone(&self, pool: &SqlitePool) -> Tz {
+        match self.user_id {
+            Some(u) => get_user_timezone(u, pool).await,
+            None => Eastern,
+        }
+    }
+
+    async fn reply(&self, content: impl Into<String>, bot_state: &BotState) {
+        self.send_reply(content.into(), false, bot_state).await;
+    }
+
+    /// Replies without pinging anyone mentioned in `content`
+    async fn reply_quietly(&self, content: impl Into<String>, bot_state: &BotState) {
+        self.send_reply(content.into(), true, bot_state).await;
+    }
+
+    async fn send_reply(&self, content: String, quiet: bool, bot_state: &BotState) {
+        if let Some(interaction) = &self.interaction {
+            let res = if self.responded.swap(true, Ordering::SeqCst) {
+                interactions::followup(&bot_state.http, interaction, &content, quiet).await
+            } else {
+                interactions::edit_response(&bot_state.http, interaction, &content, quiet).await
+            };
+            if let Err(e) = res {
+                warn!("Error replying to interaction: {}", e);
+            }
+            return;
+        }
+
+        let create = match bot_state.http.create_message(self.channel_id).content(content) {
+            Ok(c) => c,
+            Err(e) => {
+                warn!("Error building reply: {}", e);
+                return;
+            }
+        };
+        let res = if quiet {
+            create.allowed_mentions().build().await
+        } else {
+            create.await
+        };
+        if let Err(e) = res {
+            warn!("Error replying to message: {}", e);
+        }
+    }
+}
+
 async fn handle_wrapper(event: Event, bot_state: Arc<BotState>, pool: &SqlitePool) {
     match handle_event(event, bot_state, pool).await {
         Ok(()) => {}
@@ -694,41 +1317,42 @@ async fn handle_event(
     bot_state: Arc<BotState>,
     pool: &SqlitePool,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let commands = bot_state
-        .parser
-        .config()
-        .commands()
-        .map(|c| {
-            format!(
-                "{}{}",
-                bot_state.parser.config().prefixes().next().unwrap(),
-                match c {
-                    CaseSensitivity::Insensitive(cmd) => {
-                        cmd.clone().into_inner()
-                    }
-                    CaseSensitivity::Sensitive(cmd) => {
-                        cmd.to_string()
-                    }
-                }
-            )
-        })
-        .collect::<Vec<String>>()
-        .join(" ");
-    let commands_resp = format!("Available commands: {}", commands);
     match event {
         Event::GuildCreate(msg) => {
-            if msg.name != "RetroSpeedRuns" {
+            if !bot_state.config.guild_names.contains(&msg.name) {
                 warn!("Unexpected guild found! {}", msg.name);
                 return Ok(());
             }
+            if bot_state.config.guild_names.first() == Some(&msg.name) {
+                claim_legacy_races(msg.id, pool).await;
+            }
             {
-                let mut lock = bot_state.guild_id.write().await;
-                *lock = Some(msg.id.clone());
-                debug!("Set guild id to {}", msg.id);
+                let settings = load_guild_settings(msg.id, &bot_state.config, pool).await;
+                let mut lock = bot_state.settings.write().await;
+                lock.insert(msg.id, settings);
             }
             setup_roles(&msg, bot_state.clone()).await;
-            setup_channels(&msg, bot_state.clone()).await;
-            setup_emojis(&msg, bot_state.clone()).await;
+            setup_channels(msg.id, &msg.channels, bot_state.clone()).await;
+            setup_emojis(msg.id, &msg.emojis, bot_state.clone()).await;
+            setup_cleanse_message(msg.id, bot_state.clone(), pool).await;
+            if let Err(e) = interactions::register_commands(
+                &bot_state.http,
+                bot_state.config.client_id,
+                msg.id,
+                &interactions::slash_commands(&SETTING_KEYS),
+            )
+            .await
+            {
+                warn!("Error registering slash commands for {}: {}", msg.name, e);
+            }
+            {
+                let mut lock = bot_state.guild_ids.write().await;
+                lock.insert(msg.id);
+                debug!("Finished setting up guild {} ({})", msg.name, msg.id);
+            }
+            // if we crashed or were restarted, roles probably don't match reality anymore
+            let summary = reconcile_roles(msg.id, bot_state.clone(), pool).await;
+            info!("Startup role reconciliation for {}: {}", msg.name, summary);
         }
         Event::ChannelUpdate(cu) => {
             // probably we could iterate thru bot_state.channels and change the key on the one
@@ -736,44 +1360,20 @@ async fn handle_event(
             // instead of iterating. otherwise *shrug*
             debug!("Channel update - should i care? {:?}", cu);
         }
-        Event::MessageCreate(msg) => match bot_state.parser.parse(msg.content.as_str()) {
-            Some(Command { name: "bot", .. }) => {
-                bot_state
-                    .http
-                    .create_message(msg.channel_id)
-                    .content("Help, I'm alive!")?
-                    .await?;
-            }
-            Some(Command {
-                name: "listgames", ..
-            }) => list_games(&msg, bot_state.clone(), pool).await,
-            Some(Command {
-                arguments,
-                name: "listcategories",
-                ..
-            }) => list_categories(&msg, arguments, bot_state.clone(), pool).await,
-            Some(Command {
-                arguments,
-                name: "newrace",
-                ..
-            }) => add_race(&msg, arguments, bot_state.clone(), pool).await,
-            Some(Command {
-                arguments,
-                name: "endrace",
-                ..
-            }) => end_race(&msg, arguments, bot_state.clone(), pool).await,
-            Some(Command {
-                name: "commands", ..
-            }) => {
-                bot_state
-                    .http
-                    .create_message(msg.channel_id)
-                    .content(commands_resp)
-                    .unwrap()
-                    .await;
+        Event::MessageCreate(msg) => {
+            if let Some(Command {
+                name, arguments, ..
+            }) = bot_state.parser.parse(msg.content.as_str())
+            {
+                let ctx = CommandContext::from_message(&msg);
+                run_command(name, arguments, &ctx, bot_state.clone(), pool).await;
             }
-            _ => {}
-        },
+        }
+        Event::ShardPayload(payload) => {
+            if let Some(interaction) = interactions::parse_interaction(&payload.bytes) {
+                handle_interaction(interaction, bot_state.clone(), pool).await;
+            }
+        }
         Event::ShardConnected(_) => {
             debug!("Discord: Shard connected!");
         }
@@ -782,26 +1382,11 @@ async fn handle_event(
         // Check if these are on races that are *active* - if so, add/remove roles
         Event::ReactionAdd(ra) => {
             debug!("Reaction added: {:?}", ra);
-            /*
-            if ra.emoji is one of the ones we care about:
-                let msg_id = get_msg(ra.message_id);
-                // this is a bit confusing because we're going to get the same *race* object in either case,
-                // the difference is just if we got it via scheduling msg or via ready-up msg.
-                if let Some(upcoming_race) = get_race_by_scheduling_msg_id(msg_id):
-                    // this is a scheduling message, players are interested
-                    add interested-* role to player if they don't have it or active-* role
-                else if let Some(active_race) = get_race_by_active_msg_id(msg_id):
-                    // this is an active message, players are readying up
-                    remove interested-* role from player
-                    add active-* role if they don't have it
-
-                the ReactionRemove code should be very similar to this, with the addition that if
-                someone removes their ready emoji it's unclear if they should be moved back to interested-
-                or if they should be removed entirely.
-             */
+            handle_reaction(&ra.0, true, bot_state.clone(), pool).await;
         }
         Event::ReactionRemove(rr) => {
             debug!("Reaction removed: {:?}", rr);
+            handle_reaction(&rr.0, false, bot_state.clone(), pool).await;
         }
         _ => {}
     }
@@ -809,833 +1394,6715 @@ async fn handle_event(
     Ok(())
 }
 
-async fn list_categories(
-    msg: &Box<MessageCreate>,
+/// Runs a `!` command or the slash command of the same name
+async fn run_command(
+    name: &str,
     args: Arguments<'_>,
+    ctx: &CommandContext,
     bot_state: Arc<BotState>,
     pool: &SqlitePool,
 ) {
-    let contents = _list_categories(args, pool).await;
-    bot_state
-        .http
-        .create_message(msg.channel_id)
-        .content(contents)
-        .unwrap()
-        .await;
+    match name {
+        "bot" => ctx.reply("Help, I'm alive!", &bot_state).await,
+        "listgames" => list_games(ctx, bot_state.clone(), pool).await,
+        "listcategories" => list_categories(ctx, args, bot_state.clone(), pool).await,
+        "addgame" | "addcategory" | "editgame" | "editcategory" | "retirecategory" => {
+            manage_games(ctx, name, args, bot_state.clone(), pool).await
+        }
+        "newrace" => add_race(ctx, args, bot_state.clone(), pool).await,
+        "racenote" => race_note(ctx, args, bot_state.clone(), pool).await,
+        "endrace" => end_race(ctx, args, bot_state.clone(), pool).await,
+        "cancelrace" => cancel_race(ctx, args, bot_state.clone(), pool).await,
+        "reschedule" => reschedule_race(ctx, args, bot_state.clone(), pool).await,
+        "newseries" => new_series(ctx, args, bot_state.clone(), pool).await,
+        "listseries" => list_series(ctx, bot_state.clone(), pool).await,
+        "endseries" => end_series(ctx, args, bot_state.clone(), pool).await,
+        "resync" => resync(ctx, bot_state.clone(), pool).await,
+        "races" => races(ctx, args, bot_state.clone(), pool).await,
+        "race" => race(ctx, args, bot_state.clone(), pool).await,
+        "start" => start(ctx, args, bot_state.clone(), pool).await,
+        "done" => done(ctx, args, bot_state.clone(), pool).await,
+        "forfeit" => forfeit(ctx, args, bot_state.clone(), pool).await,
+        "setresult" => set_result_command(ctx, args, bot_state.clone(), pool).await,
+        "leaderboard" => leaderboard(ctx, args, bot_state.clone(), pool).await,
+        "pb" => pb(ctx, args, bot_state.clone(), pool).await,
+        "rating" => rating(ctx, args, bot_state.clone(), pool).await,
+        "ratings" => ratings(ctx, args, bot_state.clone(), pool).await,
+        "crew" => crew(ctx, args, bot_state.clone(), pool).await,
+        "assign" => assign(ctx, args, bot_state.clone(), pool).await,
+        "settings" => settings(ctx, args, bot_state.clone(), pool).await,
+        "timezone" => timezone(ctx, args, bot_state.clone(), pool).await,
+        "racetime" => racetime(ctx, args, bot_state.clone(), pool).await,
+        "commands" => ctx.reply(commands_list(&bot_state), &bot_state).await,
+        _ => {
+            warn!("Unknown command {}", name);
+        }
+    }
 }
 
-async fn _list_categories(mut args: Arguments<'_>, pool: &SqlitePool) -> String {
-    let game_name = match args.next() {
-        Some(game) => game,
+fn commands_list(bot_state: &BotState) -> String {
+    let commands = bot_state
+        .parser
+        .config()
+        .commands()
+        .map(|c| {
+            format!(
+                "{}{}",
+                bot_state.parser.config().prefixes().next().unwrap(),
+                match c {
+                    CaseSensitivity::Insensitive(cmd) => {
+                        cmd.clone().into_inner()
+                    }
+                    CaseSensitivity::Sensitive(cmd) => {
+                        cmd.to_string()
+                    }
+                }
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(" ");
+    format!("Available commands: {}", commands)
+}
+
+async fn handle_interaction(interaction: Interaction, bot_state: Arc<BotState>, pool: &SqlitePool) {
+    let data = match &interaction.data {
+        Some(d) => d.clone(),
         None => {
-            return "Please specify game: !listcategories <game>".to_owned();
+            return;
         }
     };
-    let game = match get_game(game_name, pool).await {
-        Some(g) => g,
+    match interaction.kind {
+        APPLICATION_COMMAND => {
+            let command = match interactions::slash_commands(&SETTING_KEYS)
+                .into_iter()
+                .find(|c| c.name == data.name)
+            {
+                Some(c) => c,
+                None => {
+                    warn!("Unknown slash command {}", data.name);
+                    return;
+                }
+            };
+            let args = command.to_args(&data);
+            // answer straight away so slow commands like /resync don't run out of time
+            if let Err(e) = interactions::defer(&bot_state.http, &interaction).await {
+                warn!("Error acknowledging /{}: {}", command.name, e);
+                return;
+            }
+            let ctx = match CommandContext::from_interaction(interaction) {
+                Some(c) => c,
+                None => {
+                    return;
+                }
+            };
+            run_command(command.name, Arguments::new(&args), &ctx, bot_state.clone(), pool).await;
+            if !ctx.responded.load(Ordering::SeqCst) {
+                // discord keeps showing "thinking..." if we never answer
+                ctx.reply("Nothing to do here.", &bot_state).await;
+            }
+        }
+        MESSAGE_COMPONENT => handle_button(&interaction, &data, bot_state.clone(), pool).await,
+        APPLICATION_COMMAND_AUTOCOMPLETE => {
+            let choices = _autocomplete(&data, pool).await;
+            if let Err(e) = interactions::suggest(&bot_state.http, &interaction, choices).await {
+                warn!("Error sending autocomplete suggestions: {}", e);
+            }
+        }
+        _ => {
+            debug!("Ignoring interaction of type {}", interaction.kind);
+        }
+    }
+}
+
+/// Suggests game or category aliases for whichever option is being typed in, as
+/// `(name shown, alias)` pairs
+async fn _autocomplete(data: &CommandData, pool: &SqlitePool) -> Vec<(String, String)> {
+    let focused = match data.focused() {
+        Some(f) => f,
         None => {
-            return "No game found with that name".to_owned();
+            return vec![];
         }
     };
-
-    let categories = get_categories(&game, pool).await;
-
-    let mut msg_parts = vec![format!("Available categories for {}:", game.name_pretty)];
-    msg_parts.extend(
-        categories
+    let typed = focused
+        .value
+        .as_ref()
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let matches = |alias: &str, name: &str| {
+        alias.contains(&typed) || name.to_lowercase().contains(&typed)
+    };
+    match focused.name.as_str() {
+        "game" => get_games(pool)
+            .await
+            .into_iter()
+            .filter(|g| matches(&g.name, &g.name_pretty))
+            .map(|g| (format!("{} ({})", g.name_pretty, g.name), g.name))
+            .collect(),
+        "category" => {
+            let game = match data.get("game") {
+                Some(g) => g,
+                None => {
+                    return vec![];
+                }
+            };
+            let game = match get_game(&game, pool).await {
+                Some(g) => g,
+                None => {
+                    return vec![];
+                }
+            };
+            get_categories(&game, pool)
+                .await
+                .into_iter()
+                .filter(|c| matches(&c.name, &c.name_pretty))
+                .map(|c| (format!("{} ({})", c.name_pretty, c.name), c.name))
+                .collect()
+        }
+        "timezone" => TZ_VARIANTS
             .iter()
-            .map(|c| format!("* {} ({})", c.name_pretty, c.name)),
-    );
+            .filter(|tz| tz.name().to_lowercase().contains(&typed))
+            .map(|tz| (tz.name().to_string(), tz.name().to_string()))
+            .collect(),
+        _ => vec![],
+    }
+}
 
-    msg_parts.join("\n")
+fn same_reaction(a: &ReactionType, b: &ReactionType) -> bool {
+    match (a, b) {
+        (ReactionType::Custom { id: a, .. }, ReactionType::Custom { id: b, .. }) => a == b,
+        (ReactionType::Unicode { name: a }, ReactionType::Unicode { name: b }) => a == b,
+        _ => false,
+    }
 }
 
-async fn has_any_role(
-    user: PartialMember,
-    guild_id: GuildId,
+/// Keeps signups and roles up to date as people react to races, rather than waiting for the next
+/// cron tick.
+///
+/// We get the same race either way; the difference is whether the reaction was on the scheduling
+/// message (people are signing up) or the confirmation message (people are readying up).
+/// Only races that are ACTIVE have roles - the scheduling reactions on a race that hasn't
+/// started yet get picked up by `handle_upcoming_race`.
+async fn handle_reaction(
+    reaction: &Reaction,
+    added: bool,
     bot_state: Arc<BotState>,
-    roles: Vec<&str>,
-) -> bool {
-    // note: this is mega annoying and probably pretty slow to do in here.
-    let user_roles: HashSet<RoleId> = HashSet::from_iter(user.roles.iter().cloned());
-    let guild_roles = bot_state.cache.guild_roles(guild_id).unwrap();
-    let roles = roles.iter().map(|s| s.to_string()).collect::<Vec<String>>();
-    // i do not understand why the ::<_> bullshit is required here
-    // stolen from https://stackoverflow.com/questions/62949404/cannot-infer-type-for-type-parameter-s-when-using-hashsetfrom-iter
-    let valid_role_names = HashSet::<_>::from_iter(roles);
+    pool: &SqlitePool,
+) {
+    if let Some(me) = bot_state.cache.current_user() {
+        if me.id == reaction.user_id {
+            return;
+        }
+    }
+    // nothing we care about happens in DMs
+    let gid = match reaction.guild_id {
+        Some(g) => g,
+        None => {
+            return;
+        }
+    };
 
-    let mut permitted = false;
-    for r in &guild_roles {
-        let role = bot_state.cache.role(r.clone()).unwrap();
-        if valid_role_names.contains(&role.name) && user_roles.contains(&role.id) {
-            permitted = true;
-            break;
+    if is_cleanse_message(gid, reaction.message_id, pool).await {
+        if added && same_reaction(&reaction.emoji, &Reactions::CLEANSING.get_reaction_type()) {
+            cleanse_user(gid, reaction, bot_state.clone(), pool).await;
+        }
+        return;
+    }
+
+    if let Some(race) = get_race_by_scheduling_message_id(reaction.message_id, pool).await {
+        // people sign up for these with the buttons, not whatever they react with
+        if race.signup_buttons {
+            return;
+        }
+        let kind = match signup_kind(&reaction.emoji, gid, bot_state.clone()).await {
+            Some(k) => k,
+            None => {
+                return;
+            }
+        };
+        update_signup(gid, &race, reaction.user_id, kind, added, bot_state.clone(), pool).await;
+    } else if let Some(race) = get_race_by_active_message_id(reaction.message_id, pool).await {
+        if race.signup_buttons
+            || !same_reaction(&reaction.emoji, &Reactions::CONFIRMING.get_reaction_type())
+        {
+            return;
         }
+        update_confirmation(gid, &race, reaction.user_id, added, bot_state.clone(), pool).await;
     }
-    permitted
 }
 
-async fn add_race(
-    msg: &Box<MessageCreate>,
-    args: Arguments<'_>,
+/// Signs the user up for `race` as `kind` (or withdraws them), and sorts out their roles if the
+/// race is already ACTIVE. Signups come from both reactions and buttons.
+async fn update_signup(
+    gid: GuildId,
+    race: &Race,
+    user_id: UserId,
+    kind: EntrantKind,
+    added: bool,
     bot_state: Arc<BotState>,
     pool: &SqlitePool,
 ) {
-    let permitted = has_any_role(
-        msg.member.clone().unwrap(),
-        msg.guild_id.unwrap(),
-        bot_state.clone(),
-        vec!["Moderator", "Admin"],
-    )
-    .await;
-
-    if !permitted {
-        bot_state
-            .http
-            .create_message(msg.channel_id)
-            .content("You are not authorized to create races.")
-            .unwrap()
-            .await;
+    let state = race.get_state();
+    if !(state == RaceState::SCHEDULED || state == RaceState::ACTIVE) {
         return;
     }
+    let existing = get_entrant(race.id, user_id, kind, pool).await;
+    let already_confirmed = existing
+        .as_ref()
+        .map(|e| e.get_status() == EntrantStatus::CONFIRMED)
+        .unwrap_or(false);
+
+    if added {
+        if !already_confirmed {
+            set_entrant_status(race.id, user_id, kind, EntrantStatus::INTERESTED, pool).await;
+        }
+    } else {
+        set_entrant_status(race.id, user_id, kind, EntrantStatus::WITHDRAWN, pool).await;
+        // backing out drops the assignment, so the crew listed on the race is out of date
+        if existing.map(|e| e.assigned).unwrap_or(false) {
+            update_scheduling_message(gid, race, bot_state.clone(), pool).await;
+        }
+    }
 
-    let (reply, schedule, race) = _add_race(args, bot_state.clone(), pool).await;
-    bot_state
-        .http
-        .create_message(msg.channel_id)
-        .content(reply)
-        .unwrap()
-        .await;
+    if state != RaceState::ACTIVE {
+        return;
+    }
 
-    if let Some(schedule_message) = schedule {
-        let schedule_channel_id = {
-            let lock = bot_state.channels.read().await;
-            match lock.get(SCHEDULING_CHANNEL_NAME) {
-                None => {
-                    warn!("No scheduling channel found");
-                    None
-                }
-                Some(cid) => Some(cid.clone()),
+    if let Some(role_name) = volunteer_role_name(kind) {
+        let role = match bot_state.get_role(gid, role_name).await {
+            Some(r) => r,
+            None => {
+                warn!("Missing role {} while updating signup", role_name);
+                return;
             }
         };
-        if let Some(cid) = schedule_channel_id {
-            match bot_state
-                .http
-                .create_message(cid)
-                .content(schedule_message)
-                .unwrap()
-                .await
-            {
-                Ok(ok) => {
-                    if let Some(mut r) = race {
-                        r.set_scheduling_message_id(ok.id);
-                        r.save(pool).await;
-                    }
+        if added {
+            if let Err(e) = add_role(gid, &user_id, &role, bot_state.clone()).await {
+                warn!("{}", e);
+            }
+        } else {
+            if let Err(e) = remove_role(gid, &user_id, &role, bot_state.clone()).await {
+                warn!("{}", e);
+            }
+        }
+        return;
+    }
 
-                    let racer_react_type = {
-                        let lock = bot_state.emojis.read().await;
-                        match lock.get("raisinghand") {
-                            None => {
-                                warn!("Can't find raising hand emoji");
-                                None
-                            }
-                            Some(e) => Some(ReactionType::Custom {
-                                animated: false,
-                                id: e.id,
-                                name: Some(e.name.clone()),
-                            }),
-                        }
-                    };
-
-                    for r in vec![
-                        racer_react_type,
-                        Some(Reactions::COMMENTATING.get_reaction_type()),
-                        Some(Reactions::RESTREAMING.get_reaction_type()),
-                    ] {
-                        match r {
-                            Some(reac) => {
-                                bot_state
-                                    .http
-                                    .create_reaction(cid, ok.id, RequestReactionType::from(reac))
-                                    .await;
-                            }
-                            None => {}
-                        }
-                    }
-                }
-                Err(e) => {
-                    warn!("Error creating scheduling message: {}", e);
-                }
+    let (unconfirmed_racer_role, confirmed_racer_role) = match (
+        bot_state.get_role(gid, "unconfirmed-racer").await,
+        bot_state.get_role(gid, "active-racer").await,
+    ) {
+        (Some(u), Some(c)) => (u, c),
+        _ => {
+            warn!("Missing racer roles while updating signup");
+            return;
+        }
+    };
+
+    if added {
+        // late signup for a race that's already sending out confirmations
+        if !already_confirmed {
+            debug!("Adding unconfirmed role to {} for {}", user_id, race);
+            if let Err(e) = add_role(gid, &user_id, &unconfirmed_racer_role, bot_state.clone()).await {
+                warn!("{}", e);
             }
         }
+    } else {
+        debug!("Removing {} from {}", user_id, race);
+        if let Err(e) = remove_role(gid, &user_id, &unconfirmed_racer_role, bot_state.clone()).await {
+            warn!("{}", e);
+        }
+        if let Err(e) = remove_role(gid, &user_id, &confirmed_racer_role, bot_state.clone()).await {
+            warn!("{}", e);
+        }
     }
 }
 
-// TODO this is kind of a shitty return value at this point lol
-async fn _add_race(
-    mut args: Arguments<'_>,
+/// Readies the user up for an ACTIVE race, or takes it back
+async fn update_confirmation(
+    gid: GuildId,
+    race: &Race,
+    user_id: UserId,
+    confirmed: bool,
     bot_state: Arc<BotState>,
     pool: &SqlitePool,
-) -> (String, Option<String>, Option<Race>) {
-    let syntax_error = "Please use the following format: !newrace <game alias> <category alias> <time>. For example: `!newrace alttp ms 6/9/2021 11:00pm. *Convert to Eastern time first*";
-    let game_name = match args.next() {
-        Some(game) => game,
-        None => {
-            return (syntax_error.to_owned(), None, None);
-        }
-    };
+) {
+    if race.get_state() != RaceState::ACTIVE {
+        return;
+    }
 
-    let cat_name = match args.next() {
-        Some(cat) => cat,
-        None => {
-            return (syntax_error.to_owned(), None, None);
+    // they're still interested if they take it back, they just aren't ready
+    debug!("Setting {} confirmed to {} for {}", user_id, confirmed, race);
+    let kinds = set_confirmation(race.id, user_id, confirmed, pool).await;
+    if !kinds.contains(&EntrantKind::RACER) {
+        return;
+    }
+    let (unconfirmed_racer_role, confirmed_racer_role) = match (
+        bot_state.get_role(gid, "unconfirmed-racer").await,
+        bot_state.get_role(gid, "active-racer").await,
+    ) {
+        (Some(u), Some(c)) => (u, c),
+        _ => {
+            warn!("Missing racer roles while updating confirmation");
+            return;
         }
     };
 
-    let time = match args.into_remainder() {
-        Some(t) => t,
-        None => {
-            return (syntax_error.to_owned(), None, None);
+    if confirmed {
+        if let Err(e) = remove_role(gid, &user_id, &unconfirmed_racer_role, bot_state.clone()).await {
+            warn!("{}", e);
         }
-    };
-
-    let occurs = match parse_time(time) {
-        Some(dt) => dt,
-        None => {
-            return (syntax_error.to_owned(), None, None);
+        if let Err(e) = add_role(gid, &user_id, &confirmed_racer_role, bot_state.clone()).await {
+            warn!("{}", e);
         }
-    };
-    // TODO: don't create races in the past
+    } else {
+        if let Err(e) = remove_role(gid, &user_id, &confirmed_racer_role, bot_state.clone()).await {
+            warn!("{}", e);
+        }
+        if let Err(e) = add_role(gid, &user_id, &unconfirmed_racer_role, bot_state.clone()).await {
+            warn!("{}", e);
+        }
+    }
+}
 
-    let game = match get_game(game_name, pool).await {
-        Some(g) => g,
-        None => {
-            return (
-                "No game found with that name. Try !listgames".to_owned(),
-                None,
-                None,
-            );
+/// What one of the buttons on a race's messages does
+#[derive(Debug, PartialEq)]
+enum ButtonAction {
+    SignUp(EntrantKind),
+    Withdraw,
+    Confirm,
+}
+
+fn button_id(race_id: i64, action: &ButtonAction) -> String {
+    match action {
+        ButtonAction::SignUp(kind) => format!("signup:{}:{}", race_id, kind),
+        ButtonAction::Withdraw => format!("withdraw:{}", race_id),
+        ButtonAction::Confirm => format!("confirm:{}", race_id),
+    }
+}
+
+fn parse_button_id(custom_id: &str) -> Option<(i64, ButtonAction)> {
+    let parts: Vec<&str> = custom_id.split(':').collect();
+    let race_id = parts.get(1)?.parse::<i64>().ok()?;
+    let action = match parts.as_slice() {
+        ["signup", _, kind] => ButtonAction::SignUp(kind.parse().ok()?),
+        ["withdraw", _] => ButtonAction::Withdraw,
+        ["confirm", _] => ButtonAction::Confirm,
+        _ => {
+            return None;
         }
     };
+    Some((race_id, action))
+}
 
-    let cat = match get_category(&game, cat_name, pool).await {
-        Some(c) => c,
+/// The buttons on a race's scheduling message
+fn signup_buttons(race_id: i64) -> Vec<Value> {
+    let button = |action, label, style| interactions::button(button_id(race_id, &action), label, style);
+    vec![interactions::action_row(vec![
+        button(ButtonAction::SignUp(EntrantKind::RACER), "Join as racer", ButtonStyle::Primary),
+        button(ButtonAction::SignUp(EntrantKind::COMMENTATOR), "Commentate", ButtonStyle::Secondary),
+        button(ButtonAction::SignUp(EntrantKind::RESTREAMER), "Restream", ButtonStyle::Secondary),
+        button(ButtonAction::Withdraw, "Withdraw", ButtonStyle::Danger),
+    ])]
+}
+
+/// The button on a race's confirmation message
+fn confirm_buttons(race_id: i64) -> Vec<Value> {
+    vec![interactions::action_row(vec![interactions::button(
+        button_id(race_id, &ButtonAction::Confirm),
+        "Confirm",
+        ButtonStyle::Success,
+    )])]
+}
+
+/// Someone pressed one of the buttons on a race. Their signups and roles get updated right away,
+/// and they get told (privately) where they stand.
+async fn handle_button(
+    interaction: &Interaction,
+    data: &CommandData,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let (gid, user_id) = match (&interaction.guild_id, &interaction.member) {
+        (Some(g), Some(m)) => (*g, m.user.id),
+        _ => {
+            return;
+        }
+    };
+    let reply = match data.custom_id.as_deref().and_then(parse_button_id) {
+        Some((race_id, action)) => {
+            press_button(gid, race_id, action, user_id, bot_state.clone(), pool).await
+        }
         None => {
-            return (
-                format!(
-                    "No matching category found. try !listcategories {}",
-                    game.name
-                ),
-                None,
-                None,
-            );
+            warn!("Unknown button {:?}", data.custom_id);
+            "That button doesn't do anything anymore.".to_string()
         }
     };
+    if let Err(e) = interactions::respond_privately(&bot_state.http, interaction, &reply).await {
+        warn!("Error replying to button press: {}", e);
+    }
+}
 
-    let r = match create_race(&game, &cat, occurs, pool).await {
+async fn press_button(
+    gid: GuildId,
+    race_id: i64,
+    action: ButtonAction,
+    user_id: UserId,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) -> String {
+    let race = match get_race(gid, race_id, pool).await {
         Some(r) => r,
         None => {
-            return (
-                "Unknown error creating the race. Bug Fox about it.".to_owned(),
-                None,
-                None,
-            );
+            return "That race doesn't exist anymore.".to_string();
         }
     };
+    match (race.get_state(), &action) {
+        (RaceState::SCHEDULED, ButtonAction::Confirm) => {
+            return format!("{} isn't taking confirmations yet.", race);
+        }
+        (RaceState::SCHEDULED, _) | (RaceState::ACTIVE, _) => {}
+        (RaceState::COMPLETED, _) | (RaceState::CANCELLED, _) => {
+            return format!("{} is already over.", race);
+        }
+    }
 
-    let (racer_react_name, racer_react_id) = {
-        let lock = bot_state.emojis.read().await;
-        match lock.get("raisinghand") {
-            None => {
-                warn!("Can't find raising hand emoji");
-                (":thumbup:".to_owned(), EmojiId(0))
+    match action {
+        ButtonAction::SignUp(kind) => {
+            update_signup(gid, &race, user_id, kind, true, bot_state.clone(), pool).await;
+        }
+        ButtonAction::Withdraw => {
+            for entrant in get_user_entrants(race.id, user_id, pool).await {
+                if entrant.get_status() != EntrantStatus::WITHDRAWN {
+                    let kind = entrant.get_kind();
+                    update_signup(gid, &race, user_id, kind, false, bot_state.clone(), pool).await;
+                }
             }
-            Some(e) => ("raisinghand".to_string(), e.id),
         }
-    };
-
-    fn datetime_to_discord_format(datetime: &DateTime<Tz>) -> String {
-        format!("<t:{}:F>", datetime.timestamp())
+        ButtonAction::Confirm => {
+            update_confirmation(gid, &race, user_id, true, bot_state.clone(), pool).await;
+        }
     }
+    signup_status(&race, user_id, pool).await
+}
 
-    let schedule_content = format!(
-        "There will be a race of {} - {} on {} (note that this time is *already localized for you*).
-
-If you are interested in racing, react with <:{}:{}>
-If you are available to commentate, react with :{}:
-If you are able to restream, react with :{}:
-
-(Mod note: This is Race #{})
-",
-        game.name_pretty,
-        cat.name_pretty,
-        datetime_to_discord_format(&occurs),
-        racer_react_name,
-        racer_react_id,
-        Reactions::COMMENTATING.get_name(),
-        Reactions::RESTREAMING.get_name(),
-        r.id,
-    );
-
-    ("Race created!".to_string(), Some(schedule_content), Some(r))
+/// Describes everything the user is signed up for in the race
+async fn signup_status(race: &Race, user_id: UserId, pool: &SqlitePool) -> String {
+    let signups: Vec<String> = get_user_entrants(race.id, user_id, pool)
+        .await
+        .iter()
+        .filter(|e| e.get_status() != EntrantStatus::WITHDRAWN)
+        .map(|e| {
+            let mut signup = e.get_kind().to_string().to_lowercase();
+            if e.assigned {
+                signup.push_str(" (assigned)");
+            }
+            if e.get_status() == EntrantStatus::CONFIRMED {
+                signup.push_str(" (confirmed)");
+            }
+            signup
+        })
+        .collect();
+    if signups.is_empty() {
+        format!("You're not signed up for {}.", race)
+    } else {
+        format!("You're signed up for {} as: {}.", race, signups.join(", "))
+    }
 }
 
-async fn end_race(
-    msg: &Box<MessageCreate>,
-    mut args: Arguments<'_>,
+/// Which kind of signup a reaction on a scheduling message is, if any
+async fn signup_kind(
+    emoji: &ReactionType,
+    gid: GuildId,
     bot_state: Arc<BotState>,
-    pool: &SqlitePool,
-) {
-    let permitted = has_any_role(
-        msg.member.clone().unwrap(),
-        msg.guild_id.unwrap(),
-        bot_state.clone(),
-        vec!["Moderator", "Admin"],
-    )
-    .await;
+) -> Option<EntrantKind> {
+    if same_reaction(emoji, &Reactions::COMMENTATING.get_reaction_type()) {
+        return Some(EntrantKind::COMMENTATOR);
+    }
+    if same_reaction(emoji, &Reactions::RESTREAMING.get_reaction_type()) {
+        return Some(EntrantKind::RESTREAMER);
+    }
+    match bot_state.get_racing_reaction(gid).await {
+        Some(r) if same_reaction(emoji, &r) => Some(EntrantKind::RACER),
+        _ => None,
+    }
+}
 
-    if !permitted {
-        bot_state
-            .http
-            .create_message(msg.channel_id)
-            .content("You are not authorized to end races.")
-            .unwrap()
-            .await;
-        return;
+/// The reaction people use on the scheduling message to sign up as `kind`
+async fn signup_reaction(
+    kind: EntrantKind,
+    gid: GuildId,
+    bot_state: Arc<BotState>,
+) -> Option<ReactionType> {
+    match kind {
+        EntrantKind::RACER => bot_state.get_racing_reaction(gid).await,
+        EntrantKind::COMMENTATOR => Some(Reactions::COMMENTATING.get_reaction_type()),
+        EntrantKind::RESTREAMER => Some(Reactions::RESTREAMING.get_reaction_type()),
     }
+}
 
-    let id = match args.next() {
-        Some(arg) => match arg.parse::<i64>() {
-            Ok(_id) => Some(_id),
-            Err(_) => {
-                bot_state.http
-                    .create_message(msg.channel_id)
-                    .content("Please specify a race id, or nothing if you want to try to end the currently active race").unwrap().await;
-                return;
-            }
-        },
-        None => None,
+/// Marks everything the user signed up for in this race as confirmed (or back to just interested).
+/// Someone who readies up without having signed up first is treated as a racer.
+/// Returns the kinds of signup the user has for this race.
+async fn set_confirmation(
+    race_id: i64,
+    user_id: UserId,
+    confirmed: bool,
+    pool: &SqlitePool,
+) -> Vec<EntrantKind> {
+    let signups: Vec<RaceEntrant> = get_user_entrants(race_id, user_id, pool)
+        .await
+        .into_iter()
+        .filter(|e| e.get_status() != EntrantStatus::WITHDRAWN)
+        .collect();
+    let (status, kinds) = if confirmed {
+        let kinds = if signups.is_empty() {
+            vec![EntrantKind::RACER]
+        } else {
+            signups.iter().map(|e| e.get_kind()).collect()
+        };
+        (EntrantStatus::CONFIRMED, kinds)
+    } else {
+        (
+            EntrantStatus::INTERESTED,
+            signups.iter().map(|e| e.get_kind()).collect(),
+        )
     };
 
-    let content = _end_race(id, pool, bot_state.clone()).await;
+    for kind in &kinds {
+        let unchanged = signups
+            .iter()
+            .any(|e| e.get_kind() == *kind && e.get_status() == status);
+        if !unchanged {
+            set_entrant_status(race_id, user_id, *kind, status, pool).await;
+        }
+    }
+    kinds
+}
 
-    bot_state
-        .http
-        .create_message(msg.channel_id)
-        .content(content)
-        .unwrap()
-        .await;
+const CLEANSE_MESSAGE_SETTING: &str = "cleanse_message_id";
+
+/// Each guild has its own cleanse message
+fn cleanse_message_setting(gid: GuildId) -> String {
+    format!("{}:{}", CLEANSE_MESSAGE_SETTING, gid)
 }
 
-async fn _end_race(oid: Option<i64>, pool: &SqlitePool, bot_state: Arc<BotState>) -> String {
-    let orace = match oid {
-        Some(rid) => Race::get_by_id(rid, pool).await,
-        None => get_active_race(pool).await,
+/// Makes sure the "react here to drop your roles" message exists in the scheduling channel,
+/// posting a new one if it's never been posted or has been deleted.
+async fn setup_cleanse_message(gid: GuildId, bot_state: Arc<BotState>, pool: &SqlitePool) {
+    let cid = match get_scheduling_channel(gid, bot_state.clone()).await {
+        Some(c) => c,
+        None => {
+            warn!("No scheduling channel found, can't set up cleanse message");
+            return;
+        }
     };
 
-    match orace {
-        Some(mut race) => match race.get_state() {
-            RaceState::ACTIVE => {
-                race.set_state(RaceState::COMPLETED);
-                race.save(pool).await;
-                if let Some(real_id) = oid {
-                    let mut roles_to_remove = vec![];
-                    if let Some(unconfirmed_racer_role) =
-                        bot_state.get_role("unconfirmed-racer").await
-                    {
-                        roles_to_remove.push(unconfirmed_racer_role);
-                    }
-                    if let Some(confirmed_racer_role) = bot_state.get_role("active-racer").await {
-                        roles_to_remove.push(confirmed_racer_role);
-                    }
-                    let mut lock = bot_state.racers.write().await;
-
-                    if let Some(set) = lock.get_mut(&real_id) {
-                        for user in set.iter() {
-                            for role in &roles_to_remove {
-                                remove_role(user, role, bot_state.clone()).await;
-                            }
-                        }
-                    }
-                }
-
-                format!("{} completed.", race)
+    if let Some(mid) = get_cleanse_message_id(gid, pool).await {
+        match bot_state.http.message(cid, mid).await {
+            Ok(Some(_)) => {
+                return;
             }
-            _ => {
-                format!("{} is not currently active.", race)
+            Ok(None) => {
+                info!("Cleanse message {} is gone, posting a new one", mid);
             }
-        },
-        None => "No valid race found.".to_string(),
+            Err(e) => {
+                warn!("Error fetching cleanse message {}: {}", mid, e);
+                return;
+            }
+        }
     }
-}
 
-/// Gets the currently active race. If more than one is found, returns None
-// this is just to make the types line up more easily but it might suck?
-async fn get_active_race(pool: &SqlitePool) -> Option<Race> {
-    let mut races = get_active_races(pool).await;
-    let ret = races.pop();
-    if !races.is_empty() {
-        info!("Found multiple active races");
-        return None;
+    match bot_state
+        .http
+        .create_message(cid)
+        .content(format!(
+            "Stuck with race roles you don't want? React here with :{}: to remove all of your race roles and withdraw from every race you've signed up for.",
+            Reactions::CLEANSING.get_name()
+        ))
+        .unwrap()
+        .await
+    {
+        Ok(m) => {
+            set_setting(&cleanse_message_setting(gid), &m.id.to_string(), pool).await;
+            if let Err(e) = bot_state
+                .http
+                .create_reaction(
+                    cid,
+                    m.id,
+                    RequestReactionType::from(Reactions::CLEANSING.get_reaction_type()),
+                )
+                .await
+            {
+                warn!("Error adding cleanse reaction: {}", e);
+            }
+        }
+        Err(e) => {
+            warn!("Error creating cleanse message: {}", e);
+        }
     }
-    ret
 }
 
-/// Gets all currently active races.
-async fn get_active_races(pool: &SqlitePool) -> Vec<Race> {
-    let state = RaceState::ACTIVE.to_string();
-    let q = sqlx::query_as::<_, Race>("SELECT * FROM race WHERE state = ?").bind(state);
-    let rows = q.fetch(pool);
+async fn get_cleanse_message_id(gid: GuildId, pool: &SqlitePool) -> Option<MessageId> {
+    get_setting(&cleanse_message_setting(gid), pool)
+        .await
+        .and_then(|s| s.parse::<u64>().ok())
+        .map(MessageId)
+}
 
-    rows.map(|r| r.unwrap()).collect::<Vec<Race>>().await
+async fn is_cleanse_message(gid: GuildId, mid: MessageId, pool: &SqlitePool) -> bool {
+    get_cleanse_message_id(gid, pool).await == Some(mid)
 }
 
-// TODO: This creates a race with null message_id and state SCHEDULED, always. Is that bad?
-async fn create_race(
-    game: &Game,
-    category: &Category,
-    occurs: DateTime<Tz>,
+/// Takes every role we manage away from the user who reacted, withdraws them from everything
+/// they've signed up for, and takes their reaction back off so they can do it again later.
+async fn cleanse_user(
+    gid: GuildId,
+    reaction: &Reaction,
+    bot_state: Arc<BotState>,
     pool: &SqlitePool,
-) -> Option<Race> {
-    let ts = occurs.timestamp();
-    let state = RaceState::SCHEDULED.to_string();
-    let q = sqlx::query(
-        "INSERT INTO race (game_id, category_id, occurs, state) VALUES (?, ?, ?, ?); \
-        SELECT last_insert_rowid() as rowid;").bind(game.id).bind(category.id).bind(ts).bind(state.clone());
-    match q.fetch_one(pool).await {
-        Ok(e) => {
-            Some(Race {
-                id: e.get::<i64, &str>("rowid") as i64,
-                game_id: game.id,
-                category_id: category.id,
-                occurs: ts,
-                scheduling_message_id: None,
-                active_message_id: None,
-                state,
-            })
-        }
-        Err(e) => {
-            error!("error creating race: {:?}", e);
-            None
+) {
+    let user_id = reaction.user_id;
+    info!("Cleansing roles from {} in {}", user_id, gid);
+
+    for role in &bot_state.get_roles(gid).await {
+        let has_role = match &reaction.member {
+            Some(m) => m.roles.contains(&role.id),
+            None => true,
+        };
+        if has_role {
+            if let Err(e) = remove_role(gid, &user_id, role, bot_state.clone()).await {
+                warn!("{}", e);
+            }
         }
     }
-}
 
-fn parse_time(time_str: &str) -> Option<DateTime<Tz>> {
-    let normalized = time_str.to_ascii_lowercase();
-    println!("Parsing date from {}", normalized);
-    match NaiveDateTime::parse_from_str(&normalized, "%m/%d/%Y %I:%M%P") {
-        Ok(dt) => match Eastern.from_local_datetime(&dt) {
-            LocalResult::Single(single) => Some(single),
-            LocalResult::Ambiguous(a, b) => {
-                println!("Ambiguous result... what are the values??? {:?} {:?}", a, b);
-                warn!("Ambiguous result... what are the values??? {:?} {:?}", a, b);
-                Some(a)
+    let scheduling_channel = get_scheduling_channel(gid, bot_state.clone()).await;
+    let active_channel = get_active_channel(gid, bot_state.clone()).await;
+    for entrant in get_current_signups(gid, user_id, pool).await {
+        set_entrant_status(
+            entrant.race_id,
+            user_id,
+            entrant.get_kind(),
+            EntrantStatus::WITHDRAWN,
+            pool,
+        )
+        .await;
+
+        // otherwise the next sweep of the reactions would just sign them back up
+        let race = match Race::get_by_id(entrant.race_id, pool).await {
+            Some(r) if !r.signup_buttons => r,
+            _ => {
+                continue;
             }
-            LocalResult::None => {
-                println!("Failed to parse");
-                warn!("Failed to parse");
-                None
+        };
+        if let (Some(cid), Some(mid), Some(react)) = (
+            scheduling_channel,
+            race.get_scheduling_message_id(),
+            signup_reaction(entrant.get_kind(), gid, bot_state.clone()).await,
+        ) {
+            if let Err(e) = bot_state
+                .http
+                .delete_reaction(cid, mid, RequestReactionType::from(react), user_id)
+                .await
+            {
+                warn!("Error removing {}'s signup reaction for {}: {}", user_id, race, e);
+            }
+        }
+        if let (Some(cid), Some(mid)) = (active_channel, race.get_active_message_id()) {
+            if let Err(e) = bot_state
+                .http
+                .delete_reaction(
+                    cid,
+                    mid,
+                    RequestReactionType::from(Reactions::CONFIRMING.get_reaction_type()),
+                    user_id,
+                )
+                .await
+            {
+                warn!("Error removing {}'s confirm reaction for {}: {}", user_id, race, e);
             }
-        },
-        Err(e) => {
-            println!("Error parsing date: {}", e);
-            info!("Error parsing date: {}", e);
-            None
         }
     }
-}
-
-async fn list_games(msg: &Box<MessageCreate>, bot_state: Arc<BotState>, pool: &SqlitePool) {
-    let games = get_games(pool).await;
-    let mut msg_parts = vec!["Available games:".to_owned()];
-    msg_parts.extend(
-        games
-            .iter()
-            .map(|g| format!("* {} ({})", g.name_pretty, g.name)),
-    );
 
-    let contents = msg_parts.join("\n");
-    // TODO: actually check content length if we get enough games
-    bot_state
+    if let Err(e) = bot_state
         .http
-        .create_message(msg.channel_id)
-        .content(contents)
-        .unwrap()
-        .await;
+        .delete_reaction(
+            reaction.channel_id,
+            reaction.message_id,
+            RequestReactionType::from(Reactions::CLEANSING.get_reaction_type()),
+            user_id,
+        )
+        .await
+    {
+        warn!("Error removing {}'s cleanse reaction: {}", user_id, e);
+    }
 }
 
-async fn get_game(name: &str, pool: &SqlitePool) -> Option<Game> {
-    let q = sqlx::query_as::<_, Game>(
-        "SELECT id, name, name_pretty FROM game WHERE name = ?",
-    ).bind(name);
-    match q.fetch_one(pool).await {
-        Ok(r) => Some(r),
-        Err(e) => {
-            warn!("Error fetching game: {:?}", e);
-            None
+async fn crew(
+    ctx: &CommandContext,
+    args: Arguments<'_>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let gid = match ctx.guild_id {
+        Some(g) => g,
+        None => {
+            return;
         }
-    }
+    };
+    let contents = _crew(gid, args, pool).await;
+    // this lists a bunch of people, but there's no reason to ping them all
+    ctx.reply_quietly(contents, &bot_state).await;
 }
 
-async fn get_category(game: &Game, name: &str, pool: &SqlitePool) -> Option<Category> {
-    debug!(
-        "Getting category {} for game (name {} id {}) ",
-        name, game.name, game.id
-    );
-    let q = sqlx::query_as::<_, Category>(
-        "SELECT id, game_id, name, name_pretty FROM category WHERE name = ? AND game_id = ?",
-    ).bind(name).bind(game.id);
+async fn _crew(gid: GuildId, mut args: Arguments<'_>, pool: &SqlitePool) -> String {
+    let id = match args.next().map(|a| a.parse::<i64>()) {
+        Some(Ok(id)) => id,
+        _ => {
+            return "Please use the following format: !crew <race id>".to_string();
+        }
+    };
+    let race = match get_race(gid, id, pool).await {
+        Some(r) => r,
+        None => {
+            return "No valid race found.".to_string();
+        }
+    };
 
-    match q.fetch_one(pool).await {
-        Ok(r) => Some(r),
-        Err(e) => {
-            warn!("Error fetching category: {:?}", e);
-            None
+    let mut msg_parts = vec![format!("Crew for {}:", race)];
+    for (kind, heading) in [
+        (EntrantKind::RACER, "Racers"),
+        (EntrantKind::COMMENTATOR, "Commentators"),
+        (EntrantKind::RESTREAMER, "Restreamers"),
+    ] {
+        let entrants = entrant_mentions(race.id, kind, pool).await;
+        if entrants.is_empty() {
+            msg_parts.push(format!("{}: nobody yet", heading));
+        } else {
+            msg_parts.push(format!("{}: {}", heading, entrants.join(", ")));
         }
     }
+    msg_parts.join("\n")
 }
 
-async fn get_games(pool: &SqlitePool) -> Vec<Game> {
-    let q = sqlx::query_as::<_, Game>("SELECT id, name, name_pretty FROM game");
-    let mut rows = q.fetch(pool);
-    let mut games = vec![];
-    while let r = rows.try_next().await {
-        match r {
-            Ok(mg) => match mg {
-                Some(g) => {
-                    games.push(g);
-                }
-                None => {
-                    break;
-                }
-            },
-            Err(e) => {
-                warn!("Error fetching row: {:?}", e);
+/// Mentions everyone still signed up for the race as `kind`, noting who's assigned or confirmed
+async fn entrant_mentions(race_id: i64, kind: EntrantKind, pool: &SqlitePool) -> Vec<String> {
+    get_entrants(race_id, kind, pool)
+        .await
+        .iter()
+        .filter(|e| e.get_status() != EntrantStatus::WITHDRAWN)
+        .map(|e| {
+            let mut tags = vec![];
+            if e.assigned {
+                tags.push("assigned");
             }
+            if e.get_status() == EntrantStatus::CONFIRMED {
+                tags.push("confirmed");
+            }
+            if tags.is_empty() {
+                format!("<@{}>", e.user_id)
+            } else {
+                format!("<@{}> ({})", e.user_id, tags.join(", "))
+            }
+        })
+        .collect()
+}
+
+async fn assign(
+    ctx: &CommandContext,
+    args: Arguments<'_>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let gid = match moderator_guild(ctx, bot_state.clone()).await {
+        Some(g) => g,
+        None => {
+            ctx.reply("You are not authorized to assign crew.", &bot_state).await;
+            return;
         }
-    }
+    };
 
-    games
+    let (reply, assigned) = _assign(gid, args, pool).await;
+    ctx.reply_quietly(reply, &bot_state).await;
+
+    if let Some((race, entrant)) = assigned {
+        update_scheduling_message(gid, &race, bot_state.clone(), pool).await;
+        notify_assignment(&race, &entrant, bot_state.clone(), pool).await;
+    }
 }
 
-async fn get_categories(game: &Game, pool: &SqlitePool) -> Vec<Category> {
-    debug!(
-        "Getting categories for game (name {} id {})",
-        game.name, game.id
-    );
-    let q = sqlx::query_as::<_, Category>(
-        "SELECT id, game_id, name, name_pretty  FROM category WHERE game_id = ?",
-    ).bind(game.id);
-    let mut rows = q.fetch(pool);
-    let mut categories = vec![];
-    while let r = rows.try_next().await {
-        match r {
-            Ok(mc) => match mc {
-                Some(c) => {
-                    categories.push(c);
-                }
-                None => {
-                    break;
-                }
-            },
-            Err(e) => {
-                warn!("Error fetching row: {:?}", e);
-            }
+/// Assigns a volunteer to commentate or restream a race. Returns the reply and, if someone was
+/// newly assigned, the race and their updated signup.
+async fn _assign(
+    gid: GuildId,
+    mut args: Arguments<'_>,
+    pool: &SqlitePool,
+) -> (String, Option<(Race, RaceEntrant)>) {
+    let syntax_error = "Please use the following format: !assign <commentary|restream> <race id> @user";
+    let kind = match args.next() {
+        Some("commentary") => EntrantKind::COMMENTATOR,
+        Some("restream") => EntrantKind::RESTREAMER,
+        _ => {
+            return (syntax_error.to_owned(), None);
         }
-    }
+    };
+    let id = match args.next().map(|a| a.parse::<i64>()) {
+        Some(Ok(id)) => id,
+        _ => {
+            return (syntax_error.to_owned(), None);
+        }
+    };
+    let user_id = match args.next().and_then(parse_user_mention) {
+        Some(u) => u,
+        None => {
+            return (syntax_error.to_owned(), None);
+        }
+    };
 
-    categories
-}
+    let race = match get_race(gid, id, pool).await {
+        Some(r) => r,
+        None => {
+            return ("No valid race found.".to_string(), None);
+        }
+    };
+    let state = race.get_state();
+    if !(state == RaceState::SCHEDULED || state == RaceState::ACTIVE) {
+        return (format!("{} is already over.", race), None);
+    }
 
-async fn get_upcoming_races(window: Duration, pool: &SqlitePool) -> Vec<Race> {
-    let now = Local::now().timestamp();
-    let until = (Local::now() + CDuration::from_std(window).unwrap()).timestamp();
-    let state = RaceState::SCHEDULED.to_string();
-    let q = sqlx::query_as::<_, Race>(
-        "SELECT * FROM race WHERE state = ? and occurs > ? and occurs < ?",
-    ).bind(state).bind(now).bind(until);
-    let mut rows = q.fetch(pool);
-    let mut races = vec![];
-    while let r = rows.try_next().await {
-        match r {
-            Ok(mc) => match mc {
-                Some(c) => {
-                    races.push(c);
-                }
-                None => {
-                    break;
-                }
-            },
-            Err(e) => {
-                warn!("Error fetching row: {:?}", e);
-            }
+    let mut entrant = match get_entrant(race.id, user_id, kind, pool).await {
+        Some(e) if e.get_status() != EntrantStatus::WITHDRAWN => e,
+        _ => {
+            return (
+                format!("<@{}> hasn't volunteered to {} {}.", user_id, kind_verb(kind), race),
+                None,
+            );
         }
+    };
+    if entrant.assigned {
+        return (
+            format!("<@{}> is already assigned to {} {}.", user_id, kind_verb(kind), race),
+            None,
+        );
     }
 
-    races
+    entrant.assigned = true;
+    entrant.updated = Utc::now().timestamp();
+    match entrant.save(pool).await {
+        Ok(()) => (
+            format!("<@{}> will {} {}.", user_id, kind_verb(kind), race),
+            Some((race, entrant)),
+        ),
+        Err(e) => {
+            warn!("Error assigning {} to {}: {}", user_id, race, e);
+            ("Unknown error assigning crew. Bug Fox about it.".to_string(), None)
+        }
+    }
 }
 
-async fn setup_emojis(guild: &Box<GuildCreate>, bot_state: Arc<BotState>) {
-    let mut lock = bot_state.emojis.write().await;
-    for e in &guild.emojis {
-        lock.insert(e.name.to_string(), e.clone());
-        debug!("Inserting emoji {}", e.name.to_string());
+fn kind_verb(kind: EntrantKind) -> &'static str {
+    match kind {
+        EntrantKind::RACER => "race",
+        EntrantKind::COMMENTATOR => "commentate",
+        EntrantKind::RESTREAMER => "restream",
     }
 }
 
-async fn setup_channels(guild: &Box<GuildCreate>, bot_state: Arc<BotState>) {
-    let mut has_schedule_channel = false;
-    let mut lock = bot_state.channels.write().await;
-    for c in &guild.channels {
-        debug!("Inserting channel `{}` {}", c.name(), c.id());
-        lock.insert(c.name().to_string(), c.id());
-        if c.name() == SCHEDULING_CHANNEL_NAME {
-            has_schedule_channel = true;
+/// Parses a user mention, i.e. <@123> or <@!123>
+fn parse_user_mention(mention: &str) -> Option<UserId> {
+    let id = mention.strip_prefix("<@")?.strip_suffix(">")?;
+    let id = id.strip_prefix("!").unwrap_or(id);
+    id.parse::<u64>().ok().map(UserId)
+}
+
+/// DMs someone to let them know they've been picked for a race's crew
+async fn notify_assignment(
+    race: &Race,
+    entrant: &RaceEntrant,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let user_id = match entrant.get_user_id() {
+        Some(u) => u,
+        None => {
+            return;
         }
-    }
-    if !has_schedule_channel {
-        match bot_state
-            .http
-            .create_guild_channel(guild.id.clone(), SCHEDULING_CHANNEL_NAME)
-        {
-            Ok(chan) => match chan
-                .kind(ChannelType::GuildText)
-                .parent_id(798390141496197212)
-                .await
-            {
-                Ok(created) => {
-                    lock.insert(created.name().to_string(), created.id());
-                }
-                Err(e) => {
-                    warn!("Error creating scheduling channel: {}", e);
-                }
-            },
-            Err(e) => {
-                warn!("Error creating scheduling channel: {}", e);
-            }
+    };
+    let channel = match bot_state.http.create_private_channel(user_id).await {
+        Ok(c) => c,
+        Err(e) => {
+            warn!("Error opening DM with {}: {}", user_id, e);
+            return;
         }
+    };
+    let game = Game::get_by_id(race.game_id, pool).await.unwrap();
+    let cat = Category::get_by_id(race.category_id, pool).await.unwrap();
+    if let Err(e) = bot_state
+        .http
+        .create_message(channel.id)
+        .content(format!(
+            "You've been assigned to {} the {} - {} race on {}. Thanks for helping out!",
+            kind_verb(entrant.get_kind()),
+            game.name_pretty,
+            cat.name_pretty,
+            datetime_to_discord_format(&race.get_occurs()),
+        ))
+        .unwrap()
+        .await
+    {
+        warn!("Error DMing {} about {}: {}", user_id, race, e);
     }
 }
 
-async fn setup_roles(guild: &Box<GuildCreate>, bot_state: Arc<BotState>) {
-    let desired_roles = vec![
-        DesiredRoleBuilder::default()
-            .name("active-racer".to_string())
-            .color(0xE74C3C)
-            .mentionable(true)
-            .build()
-            .unwrap(),
-        DesiredRoleBuilder::default()
-            .name("unconfirmed-racer".to_string())
-            .color(0xf7c9c4)
-            .mentionable(true)
-            .build()
-            .unwrap(),
-    ];
+async fn settings(
+    ctx: &CommandContext,
+    args: Arguments<'_>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    // admins can always get in, so nobody can lock everyone out with bad moderator_role_names
+    let gid = match moderator_guild(ctx, bot_state.clone())
+        .await
+        .or_else(|| admin_guild(ctx, &bot_state))
+    {
+        Some(g) => g,
+        None => {
+            ctx.reply("You are not authorized to change settings.", &bot_state).await;
+            return;
+        }
+    };
 
-    let mut desired_roles_by_name: HashMap<String, DesiredRole> = Default::default();
-    for role in desired_roles {
-        desired_roles_by_name.insert(role.name.clone(), role);
+    let current = bot_state.get_settings(gid).await;
+    let role_names: Vec<String> = bot_state
+        .cache
+        .guild_roles(gid)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|r| bot_state.cache.role(r))
+        .map(|r| r.name.clone())
+        .collect();
+    let (reply, updated) = _settings(gid, args, current, &role_names, pool).await;
+    ctx.reply(reply, &bot_state).await;
+
+    if let Some(updated) = updated {
+        {
+            let mut lock = bot_state.settings.write().await;
+            lock.insert(gid, updated);
+        }
+        refresh_guild(gid, bot_state, pool).await;
     }
+}
 
-    for role in &guild.roles {
-        if desired_roles_by_name.contains_key(&role.name) {
-            let desired = desired_roles_by_name.get(&role.name).unwrap();
-            if !desired.matches(role) {
-                warn!("Non-matching role found!! wtf {:?}", role);
-            } else {
-                desired_roles_by_name.remove(&role.name);
+/// Handles `!settings show` and `!settings set <key> <value>`. Returns the reply and, if
+/// something changed, the guild's new settings. `role_names` are the roles that exist in the
+/// guild, so moderator_role_names can't be pointed at something nobody can have.
+async fn _settings(
+    gid: GuildId,
+    mut args: Arguments<'_>,
+    mut settings: GuildSettings,
+    role_names: &[String],
+    pool: &SqlitePool,
+) -> (String, Option<GuildSettings>) {
+    let syntax_error = format!(
+        "Please use the following format: !settings show, or !settings set <setting> <value>. Settings are: {}",
+        SETTING_KEYS.join(", ")
+    );
+    match args.next() {
+        None | Some("show") => {
+            let mut lines = vec!["Settings for this server:".to_string()];
+            for key in SETTING_KEYS.iter() {
+                lines.push(format!("* {}: {}", key, settings.get(key).unwrap_or_default()));
+            }
+            (lines.join("\n"), None)
+        }
+        Some("set") => {
+            let (key, value) = match (args.next(), args.into_remainder()) {
+                (Some(k), Some(v)) => (k, v.trim()),
+                (Some(k), None) => (k, ""),
+                _ => {
+                    return (syntax_error, None);
+                }
+            };
+            if let Err(e) = settings.set(key, value) {
+                return (format!("Couldn't change {}: {}", key, e), None);
+            }
+            if key == "moderator_role_names" {
+                if let Some(missing) = settings
+                    .moderator_role_names
+                    .iter()
+                    .find(|n| !role_names.contains(n))
                 {
-                    let mut lock = bot_state.roles.write().await;
-                    lock.insert(role.name.clone(), role.clone());
+                    return (
+                        format!("Couldn't change {}: there's no role called {}", key, missing),
+                        None,
+                    );
+                }
+            }
+            // save it the way `get` formats it, so it reads back the same way
+            let saved = settings.get(key).unwrap_or_default();
+            match save_guild_setting(gid, key, &saved, pool).await {
+                Ok(()) => (format!("{} is now {}", key, saved), Some(settings)),
+                Err(e) => {
+                    warn!("Error saving setting {} for guild {}: {:?}", key, gid, e);
+                    (
+                        "Unknown error saving the setting. Bug Fox about it.".to_string(),
+                        None,
+                    )
                 }
             }
         }
+        Some(_) => (syntax_error, None),
     }
+}
 
-    for desired in desired_roles_by_name.values() {
-        let c = bot_state.http.create_role(guild.id);
-        info!("Creating role {:?}", desired);
-        match desired.create(c).await {
-            Ok(_) => {
-                debug!("Successfuly created role!");
-            }
-            Err(e) => {
-                warn!("Error creating role: {:?}", e);
-            }
+/// Re-reads the guild's channels and emojis so changed settings take effect right away. This
+/// creates the scheduling channel (and its cleanse message) if the setting points somewhere new.
+async fn refresh_guild(gid: GuildId, bot_state: Arc<BotState>, pool: &SqlitePool) {
+    match bot_state.http.guild_channels(gid).await {
+        Ok(channels) => setup_channels(gid, &channels, bot_state.clone()).await,
+        Err(e) => {
+            warn!("Error fetching channels for guild {}: {}", gid, e);
         }
     }
-
-    for er in bot_state.roles.read().await.values() {
-        match bot_state
-            .http
-            .add_guild_member_role(guild.id, UserId::from(FOXLISK_USER_ID), er.id)
-            .await
-        {
-            Ok(_) => {}
-            Err(e) => {
-                warn!("Failed to assign to role: {:?}", e);
-            }
+    match bot_state.http.emojis(gid).await {
+        Ok(emojis) => setup_emojis(gid, &emojis, bot_state.clone()).await,
+        Err(e) => {
+            warn!("Error fetching emojis for guild {}: {}", gid, e);
         }
     }
+    setup_cleanse_message(gid, bot_state, pool).await;
 }
 
-// some - maybe most - of this stuff should be in integration tests probably.
-#[cfg(test)]
-mod test {
-    use crate::discord::{
-        create_race, get_category, get_game, get_pool, get_upcoming_races, parse_time, RaceState,
-        _end_race, nag_times,
+async fn timezone(
+    ctx: &CommandContext,
+    args: Arguments<'_>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let user_id = match ctx.user_id {
+        Some(u) => u,
+        None => {
+            return;
+        }
     };
-    use crate::models::Race;
-    use chrono::{DateTime, Datelike, Duration as CDuration, Local, NaiveDateTime, Timelike};
-    use chrono_tz::Tz;
-    use chrono_tz::US::Eastern;
-    use lru::LruCache;
-    use sqlx::SqlitePool;
-    use tokio::time::Duration;
-    use twilight_model::id::MessageId;
+    ctx.reply(_timezone(user_id, args, pool).await, &bot_state).await;
+}
 
-    fn init() {
-        let _ = env_logger::builder().is_test(true).try_init();
+/// Shows or changes the timezone someone types race times in
+async fn _timezone(user_id: UserId, mut args: Arguments<'_>, pool: &SqlitePool) -> String {
+    let name = match args.next() {
+        Some(n) => n,
+        None => {
+            return format!(
+                "Your timezone is {}. Change it with !timezone <name>, e.g. `!timezone America/Chicago`.",
+                get_user_timezone(user_id, pool).await.name()
+            );
+        }
+    };
+    let tz = match find_timezone(name) {
+        Some(tz) => tz,
+        None => {
+            return format!(
+                "I don't know a timezone called {}. Try a name like America/Chicago or Europe/Berlin, \
+                from <https://en.wikipedia.org/wiki/List_of_tz_database_time_zones>",
+                name
+            );
+        }
+    };
+    match set_user_timezone(user_id, tz, pool).await {
+        Ok(()) => format!("Your timezone is now {}.", tz.name()),
+        Err(e) => {
+            warn!("Error setting timezone for {}: {}", user_id, e);
+            "Unknown error setting your timezone. Bug Fox about it.".to_string()
+        }
     }
+}
 
-    #[test]
-    fn test_nag_times() {
-        assert_eq!(vec![15, 30], nag_times(999));
-        assert_eq!(vec![15], nag_times(27));
-        assert_eq!(vec![] as Vec<i64>, nag_times(2));
-    }
+async fn racetime(
+    ctx: &CommandContext,
+    args: Arguments<'_>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let user_id = match ctx.user_id {
+        Some(u) => u,
+        None => {
+            return;
+        }
+    };
+    let moderator = is_moderator(ctx, bot_state.clone()).await;
+    ctx.reply(_racetime(user_id, moderator, args, pool).await, &bot_state).await;
+}
 
-    #[test]
-    fn test_lru_thing() {
-        let mut cache: LruCache<i64, Vec<i32>> = LruCache::new(3);
-        cache.put(1, vec![5, 15, 30]);
+/// Shows or changes the racetime.gg account someone's results in race rooms get recorded under.
+/// Moderators can hand an account to someone else with `!racetime <name> @user`, in case the
+/// wrong person got to it first.
+async fn _racetime(
+    user_id: UserId,
+    moderator: bool,
+    args: Arguments<'_>,
+    pool: &SqlitePool,
+) -> String {
+    let remainder = args.into_remainder().map(str::trim).unwrap_or_default();
+    let target = remainder
+        .rsplit_once(' ')
+        .and_then(|(n, m)| parse_user_mention(m).map(|u| (n.trim(), u)));
+    if let Some((name, other)) = target {
+        if !moderator {
+            return "Only moderators can link someone else's racetime.gg account.".to_string();
+        }
+        return match relink_racetime_name(other, name, pool).await {
+            Ok(()) => format!("<@{}>'s racetime.gg account is now {}.", other, name),
+            Err(e) => {
+                warn!("Error linking racetime.gg account {} to {}: {}", name, other, e);
+                "Unknown error linking the racetime.gg account. Bug Fox about it.".to_string()
+            }
+        };
+    }
+    if remainder.is_empty() {
+        return match get_racetime_name(user_id, pool).await {
+            Some(n) => format!(
+                "Your racetime.gg account is {}. Change it with !racetime <name>, e.g. `!racetime Fox#1234`.",
+                n
+            ),
+            None => "You haven't linked a racetime.gg account. Link one with !racetime <name>, e.g. \
+                `!racetime Fox#1234`, so your results in race rooms get recorded here."
+                .to_string(),
+        };
+    }
+    let name = remainder;
+    if let Some(other) = get_racetime_link(name, pool).await {
+        if other == user_id {
+            return format!("Your racetime.gg account is already {}.", name);
+        }
+        return format!("Someone else has already linked {}. Ask a moderator if that's wrong.", name);
+    }
+    match set_racetime_name(user_id, name, pool).await {
+        Ok(()) => format!(
+            "Your racetime.gg account is now {}. Your results in race rooms will be recorded here.",
+            name
+        ),
+        Err(e) => {
+            warn!("Error linking racetime.gg account for {}: {}", user_id, e);
+            "Unknown error linking your racetime.gg account. Bug Fox about it.".to_string()
+        }
+    }
+}
 
-        {
-            let rn = cache.get(&1).unwrap();
-            assert_eq!(&vec![5, 15, 30], rn);
+/// Tacked onto syntax errors for commands that take a time
+fn timezone_note(tz: Tz) -> String {
+    format!("*Times are in {}; use !timezone to change that*", tz.name())
+}
+
+async fn resync(ctx: &CommandContext, bot_state: Arc<BotState>, pool: &SqlitePool) {
+    let gid = match moderator_guild(ctx, bot_state.clone()).await {
+        Some(g) => g,
+        None => {
+            ctx.reply("You are not authorized to resync roles.", &bot_state).await;
+            return;
         }
-        let mrn = cache.get_mut(&1).unwrap();
+    };
 
-        match mrn.pop() {
-            Some(time) => {
-                assert_eq!(time, 30);
+    let summary = reconcile_roles(gid, bot_state.clone(), pool).await;
+    info!("Manual role reconciliation: {}", summary);
+    ctx.reply(summary, &bot_state).await;
+}
+
+/// Page size for fetching the member list; 1000 is the most Discord hands out at once
+const MEMBER_PAGE_SIZE: u64 = 1000;
+
+/// Everyone's current roles, straight from Discord. We don't subscribe to member events, so the
+/// cache only knows about people who happened to speak up, and its roles can be out of date.
+/// Listing members needs the Server Members intent turned on in the developer portal.
+async fn fetch_member_roles(
+    gid: GuildId,
+    bot_state: Arc<BotState>,
+) -> Result<HashMap<UserId, Vec<RoleId>>, twilight_http::Error> {
+    let mut held: HashMap<UserId, Vec<RoleId>> = Default::default();
+    let mut after = None;
+    loop {
+        let mut req = bot_state
+            .http
+            .guild_members(gid)
+            .limit(MEMBER_PAGE_SIZE)
+            .unwrap();
+        if let Some(a) = after {
+            req = req.after(a);
+        }
+        let page = req.await?;
+        let full = page.len() as u64 == MEMBER_PAGE_SIZE;
+        after = page.iter().map(|m| m.user.id).max();
+        for member in page {
+            held.insert(member.user.id, member.roles);
+        }
+        if !full {
+            return Ok(held);
+        }
+    }
+}
+
+/// Brings race roles back in line with the ACTIVE races: anyone who reacted gets their role back,
+/// and anyone holding a race role who isn't in an active race loses it. Signups are refreshed
+/// from the reactions along the way, since we may have missed reaction events while down.
+/// Returns a summary of what changed.
+async fn reconcile_roles(gid: GuildId, bot_state: Arc<BotState>, pool: &SqlitePool) -> String {
+    let (unconfirmed_racer_role, confirmed_racer_role) = match (
+        bot_state.get_role(gid, "unconfirmed-racer").await,
+        bot_state.get_role(gid, "active-racer").await,
+    ) {
+        (Some(u), Some(c)) => (u, c),
+        _ => {
+            return "Can't reconcile roles: racer roles are missing.".to_string();
+        }
+    };
+    let mut volunteer_roles: HashMap<EntrantKind, Role> = Default::default();
+    for kind in [EntrantKind::COMMENTATOR, EntrantKind::RESTREAMER] {
+        if let Some(role) = bot_state.get_role(gid, volunteer_role_name(kind).unwrap()).await {
+            volunteer_roles.insert(kind, role);
+        }
+    }
+    let my_id = bot_state.cache.current_user().map(|u| u.id);
+
+    let mut desired: HashMap<UserId, HashSet<RoleId>> = Default::default();
+    for race in get_active_races(gid, pool).await {
+        sync_entrants_from_reactions(gid, &race, my_id, bot_state.clone(), pool).await;
+        for kind in [
+            EntrantKind::RACER,
+            EntrantKind::COMMENTATOR,
+            EntrantKind::RESTREAMER,
+        ] {
+            for entrant in get_entrants(race.id, kind, pool).await {
+                let user = match entrant.get_user_id() {
+                    Some(u) => u,
+                    None => {
+                        continue;
+                    }
+                };
+                let role = match (kind, entrant.get_status()) {
+                    (_, EntrantStatus::WITHDRAWN) => None,
+                    (EntrantKind::RACER, EntrantStatus::CONFIRMED) => Some(confirmed_racer_role.id),
+                    (EntrantKind::RACER, EntrantStatus::INTERESTED) => {
+                        Some(unconfirmed_racer_role.id)
+                    }
+                    (_, _) => volunteer_roles.get(&kind).map(|r| r.id),
+                };
+                if let Some(r) = role {
+                    desired.entry(user).or_default().insert(r);
+                }
             }
-            None => {}
         }
-        {
-            let rn = cache.get(&1).unwrap();
-            assert_eq!(&vec![5, 15], rn);
+    }
+    // confirmed for some other race wins
+    for roles in desired.values_mut() {
+        if roles.contains(&confirmed_racer_role.id) {
+            roles.remove(&unconfirmed_racer_role.id);
         }
     }
 
-    #[test]
-    fn test_vec_semantics() {
-        let v = vec![9, 8, 7];
-        let f = v.first();
-        assert_eq!(Some(&9), f);
-        let f2 = v.first();
-        assert_eq!(Some(&9), f2);
+    let mut managed = vec![unconfirmed_racer_role.id, confirmed_racer_role.id];
+    managed.extend(volunteer_roles.values().map(|r| r.id));
+    let held = match fetch_member_roles(gid, bot_state.clone()).await {
+        Ok(h) => h,
+        Err(e) => {
+            warn!("Error fetching members for guild {}: {}", gid, e);
+            return "Can't reconcile roles: couldn't fetch the guild's members.".to_string();
+        }
+    };
+
+    let (to_add, to_remove) = plan_role_changes(&desired, &held, &managed);
+    let roles_by_id: HashMap<RoleId, &Role> = vec![&unconfirmed_racer_role, &confirmed_racer_role]
+        .into_iter()
+        .chain(volunteer_roles.values())
+        .map(|r| (r.id, r))
+        .collect();
+    let mut failed = 0;
+    for (user, role) in &to_remove {
+        debug!("Reconcile: removing role {} from {}", role, user);
+        if let Err(e) = remove_role(gid, user, roles_by_id[role], bot_state.clone()).await {
+            warn!("{}", e);
+            failed += 1;
+        }
+    }
+    for (user, role) in &to_add {
+        debug!("Reconcile: adding role {} to {}", role, user);
+        if let Err(e) = add_role(gid, user, roles_by_id[role], bot_state.clone()).await {
+            warn!("{}", e);
+            failed += 1;
+        }
     }
 
-    async fn initdb(pool: &SqlitePool) {
-        let queries = vec![
-            "DELETE FROM race",
-            "DELETE FROM category",
-            "DELETE FROM game",
-            "INSERT INTO game (id, name, name_pretty) VALUES (1, 'alttp', 'A Link To The Past')",
-            "INSERT INTO category (game_id, name, name_pretty) VALUES (1, 'ms', 'Master Sword')",
-            "INSERT INTO category (game_id, name, name_pretty) VALUES (1, 'nmg', 'Any% NMG No S&Q')"
-            ];
-        for sql in queries {
-            let q = sqlx::query(sql);
-            q.execute(pool).await;
+    let mut summary = format!(
+        "Roles resynced: added {} and removed {} race roles.",
+        to_add.len(),
+        to_remove.len()
+    );
+    if failed > 0 {
+        summary.push_str(&format!(" {} of those changes failed; see the logs.", failed));
+    }
+    summary
+}
+
+// (user, role) pairs
+type RoleChanges = Vec<(UserId, RoleId)>;
+
+/// Works out which of the `managed` roles need to be added and removed so that everyone has
+/// exactly the roles they're supposed to according to `desired`.
+fn plan_role_changes(
+    desired: &HashMap<UserId, HashSet<RoleId>>,
+    held: &HashMap<UserId, Vec<RoleId>>,
+    managed: &[RoleId],
+) -> (RoleChanges, RoleChanges) {
+    let mut to_add = vec![];
+    let mut to_remove = vec![];
+    for (user, roles) in held {
+        for role in roles.iter().filter(|r| managed.contains(r)) {
+            let wanted = desired.get(user).map(|d| d.contains(role)).unwrap_or(false);
+            if !wanted {
+                to_remove.push((*user, *role));
+            }
+        }
+    }
+    for (user, roles) in desired {
+        for role in roles {
+            let has_it = held.get(user).map(|r| r.contains(role)).unwrap_or(false);
+            if !has_it {
+                to_add.push((*user, *role));
+            }
         }
     }
+    to_add.sort();
+    to_remove.sort();
+    (to_add, to_remove)
+}
 
-    #[test]
-    fn test_datetime_stuff() {
-        init();
-        let ndt = NaiveDateTime::parse_from_str("06/09/2021 11:00pm", "%m/%d/%Y %I:%M%P");
-        ndt.unwrap();
+/// Updates the signups for an ACTIVE race to match the reactions on its messages: the racing,
+/// commentating and restreaming reactions on the scheduling message, and the confirm reaction on
+/// the confirmation message. If we can't fetch the reactions, the signups are left as they are.
+async fn sync_entrants_from_reactions(
+    gid: GuildId,
+    race: &Race,
+    my_id: Option<UserId>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    // the buttons update signups directly, there's nothing to catch up on
+    if race.signup_buttons {
+        return;
+    }
+    let (scheduling, active) = match (
+        race.get_scheduling_message_id(),
+        get_scheduling_channel(gid, bot_state.clone()).await,
+        race.get_active_message_id(),
+        get_active_channel(gid, bot_state.clone()).await,
+    ) {
+        (Some(smid), Some(scid), Some(amid), Some(acid)) => ((scid, smid), (acid, amid)),
+        _ => {
+            warn!("Can't find the messages for {}, leaving signups alone", race);
+            return;
+        }
+    };
+    let kinds = [EntrantKind::RACER, EntrantKind::COMMENTATOR, EntrantKind::RESTREAMER];
+    let mut signed_up: HashMap<EntrantKind, HashSet<UserId>> = HashMap::new();
+    for kind in kinds {
+        let users = match signup_reaction(kind, gid, bot_state.clone()).await {
+            Some(react) => {
+                get_reactions_for(bot_state.clone(), scheduling.0, scheduling.1, react).await
+            }
+            None => None,
+        };
+        match users {
+            Some(u) => {
+                let users = u.iter().map(|u| u.id).filter(|u| Some(*u) != my_id).collect();
+                signed_up.insert(kind, users);
+            }
+            None => {
+                warn!("Couldn't fetch {} reactions for {}, leaving signups alone", kind, race);
+                return;
+            }
+        }
+    }
+    let confirmed: HashSet<UserId> = match get_reactions_for(
+        bot_state.clone(),
+        active.0,
+        active.1,
+        Reactions::CONFIRMING.get_reaction_type(),
+    )
+    .await
+    {
+        Some(u) => u.iter().map(|u| u.id).filter(|u| Some(*u) != my_id).collect(),
+        None => {
+            warn!("Couldn't fetch confirmations for {}, leaving signups alone", race);
+            return;
+        }
+    };
+    // same as set_confirmation: readying up without signing up for anything makes you a racer
+    let unsigned: Vec<UserId> = confirmed
+        .iter()
+        .filter(|u| !signed_up.values().any(|s| s.contains(u)))
+        .copied()
+        .collect();
+    signed_up.entry(EntrantKind::RACER).or_default().extend(unsigned);
+
+    for kind in kinds {
+        let users = &signed_up[&kind];
+        for user in users {
+            let status = if confirmed.contains(user) {
+                EntrantStatus::CONFIRMED
+            } else {
+                EntrantStatus::INTERESTED
+            };
+            set_entrant_status(race.id, *user, kind, status, pool).await;
+        }
+        for entrant in get_entrants(race.id, kind, pool).await {
+            if let Some(user) = entrant.get_user_id() {
+                if !users.contains(&user) && entrant.get_status() != EntrantStatus::WITHDRAWN {
+                    set_entrant_status(race.id, user, kind, EntrantStatus::WITHDRAWN, pool).await;
+                }
+            }
+        }
+    }
+}
 
-        let now: DateTime<Tz> = Local::now().with_timezone(&Eastern);
-        std::thread::sleep(Duration::from_millis(5));
-        let nower: DateTime<Tz> = Local::now().with_timezone(&Eastern);
-        let fwd = nower - now;
-        assert!(fwd.num_milliseconds() > 4);
-        let backwd = now - nower;
-        assert!(backwd.num_milliseconds() < -4);
+async fn list_categories(
+    ctx: &CommandContext,
+    args: Arguments<'_>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let contents = _list_categories(args, pool).await;
+    ctx.reply(contents, &bot_state).await;
+}
+
+async fn _list_categories(mut args: Arguments<'_>, pool: &SqlitePool) -> String {
+    let game_name = match args.next() {
+        Some(game) => game,
+        None => {
+            return "Please specify game: !listcategories <game>".to_owned();
+        }
+    };
+    let game = match get_game(game_name, pool).await {
+        Some(g) => g,
+        None => {
+            return "No game found with that name".to_owned();
+        }
+    };
+
+    let categories = get_categories(&game, pool).await;
+
+    let mut msg_parts = vec![format!("Available categories for {}:", game.name_pretty)];
+    msg_parts.extend(
+        categories
+            .iter()
+            .map(|c| format!("* {} ({})", c.name_pretty, c.name)),
+    );
+
+    msg_parts.join("\n")
+}
+
+async fn has_any_role(
+    user_roles: &[RoleId],
+    guild_id: GuildId,
+    bot_state: Arc<BotState>,
+    roles: Vec<&str>,
+) -> bool {
+    // note: this is mega annoying and probably pretty slow to do in here.
+    let user_roles: HashSet<RoleId> = HashSet::from_iter(user_roles.iter().cloned());
+    let guild_roles = bot_state.cache.guild_roles(guild_id).unwrap();
+    let roles = roles.iter().map(|s| s.to_string()).collect::<Vec<String>>();
+    // i do not understand why the ::<_> bullshit is required here
+    // stolen from https://stackoverflow.com/questions/62949404/cannot-infer-type-for-type-parameter-s-when-using-hashsetfrom-iter
+    let valid_role_names = HashSet::<_>::from_iter(roles);
+
+    let mut permitted = false;
+    for r in &guild_roles {
+        let role = bot_state.cache.role(r.clone()).unwrap();
+        if valid_role_names.contains(&role.name) && user_roles.contains(&role.id) {
+            permitted = true;
+            break;
+        }
     }
+    permitted
+}
 
-    #[test]
-    fn test_parse_time() {
-        init();
-        let odt = parse_time("06/09/2021 11:00pm");
-        assert!(odt.is_some());
-        let dt = odt.unwrap();
-        assert_eq!(6, dt.month());
-        assert_eq!(9, dt.day());
-        assert_eq!(2021, dt.year());
-        assert_eq!(23, dt.hour());
-        assert_eq!(0, dt.minute());
-        assert_eq!(0, dt.second());
-        assert_eq!("2021-06-09T23:00:00-04:00", dt.to_rfc3339());
-        assert_eq!(1623294000, dt.timestamp());
+/// Checks whether whoever ran the command is allowed to run moderator-only commands
+async fn is_moderator(ctx: &CommandContext, bot_state: Arc<BotState>) -> bool {
+    moderator_guild(ctx, bot_state).await.is_some()
+}
+
+/// The guild the command was run in, if whoever ran it is allowed to run moderator-only commands
+/// there. Races belong to a guild, so commands that deal with races need to know which one.
+async fn moderator_guild(ctx: &CommandContext, bot_state: Arc<BotState>) -> Option<GuildId> {
+    let guild_id = ctx.guild_id?;
+    let role_names = bot_state.get_settings(guild_id).await.moderator_role_names;
+    let role_names = role_names.iter().map(String::as_str).collect();
+    if has_any_role(&ctx.roles, guild_id, bot_state, role_names).await {
+        Some(guild_id)
+    } else {
+        None
+    }
+}
+
+/// The guild the command was run in, if whoever ran it owns the guild or has a role with the
+/// Administrator permission there
+fn admin_guild(ctx: &CommandContext, bot_state: &BotState) -> Option<GuildId> {
+    let guild_id = ctx.guild_id?;
+    let guild = bot_state.cache.guild(guild_id)?;
+    if ctx.user_id == Some(guild.owner_id) {
+        return Some(guild_id);
+    }
+    // @everyone's id is the guild's id, and it's never in the member's role list
+    let everyone = RoleId(guild_id.0);
+    let admin = ctx
+        .roles
+        .iter()
+        .chain(std::iter::once(&everyone))
+        .filter_map(|r| bot_state.cache.role(*r))
+        .any(|r| r.permissions.contains(Permissions::ADMINISTRATOR));
+    if admin {
+        Some(guild_id)
+    } else {
+        None
+    }
+}
+
+async fn add_race(
+    ctx: &CommandContext,
+    args: Arguments<'_>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let gid = match moderator_guild(ctx, bot_state.clone()).await {
+        Some(g) => g,
+        None => {
+            ctx.reply("You are not authorized to create races.", &bot_state).await;
+            return;
+        }
+    };
+
+    let tz = ctx.timezone(pool).await;
+    let now = Local::now().with_timezone(&tz);
+    let (reply, schedule, race) = _add_race(gid, args, now, tz, bot_state.clone(), pool).await;
+    ctx.reply(reply, &bot_state).await;
+
+    if let (Some(schedule_message), Some(r)) = (schedule, race) {
+        post_scheduling_message(gid, r, schedule_message, bot_state.clone(), pool).await;
+    }
+}
+
+/// Posts a new race in the scheduling channel and puts the signup buttons on it
+async fn post_scheduling_message(
+    gid: GuildId,
+    mut race: Race,
+    content: String,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let cid = match get_scheduling_channel(gid, bot_state.clone()).await {
+        Some(c) => c,
+        None => {
+            warn!("No scheduling channel found");
+            return;
+        }
+    };
+    match bot_state
+        .http
+        .create_message(cid)
+        .content(content)
+        .unwrap()
+        .await
+    {
+        Ok(ok) => {
+            race.set_scheduling_message_id(ok.id);
+            if let Err(e) = race.save(pool).await {
+                warn!("Error saving the scheduling message for {}: {:?}", race, e);
+            }
+            if let Err(e) =
+                interactions::set_components(&bot_state.http, cid, ok.id, signup_buttons(race.id))
+                    .await
+            {
+                warn!("Error adding signup buttons to {}: {}", race, e);
+            }
+        }
+        Err(e) => {
+            warn!("Error creating scheduling message: {}", e);
+        }
+    }
+}
+
+// TODO this is kind of a shitty return value at this point lol
+async fn _add_race(
+    gid: GuildId,
+    mut args: Arguments<'_>,
+    now: DateTime<Tz>,
+    tz: Tz,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) -> (String, Option<String>, Option<Race>) {
+    let syntax_error = format!("Please use the following format: !newrace <game alias> <category alias> <time> [--force] [-- notes]. For example: `!newrace alttp ms 6/9/2021 11:00pm -- for new runners`. {}", timezone_note(tz));
+    let game_name = match args.next() {
+        Some(game) => game,
+        None => {
+            return (syntax_error.to_owned(), None, None);
+        }
+    };
+
+    let cat_name = match args.next() {
+        Some(cat) => cat,
+        None => {
+            return (syntax_error.to_owned(), None, None);
+        }
+    };
+
+    let (time, notes) = match args.into_remainder() {
+        Some(rest) => split_notes(rest),
+        None => {
+            return (syntax_error.to_owned(), None, None);
+        }
+    };
+    // only the time can have a --force in it; the notes are whatever the moderator typed
+    let (time, force) = take_force_flag(time);
+
+    let occurs = match time_parser::parse_time(&time, tz, now) {
+        Ok(dt) => dt,
+        Err(e) => {
+            return (e.to_string(), None, None);
+        }
+    };
+
+    let game = match get_game(game_name, pool).await {
+        Some(g) => g,
+        None => {
+            return (
+                "No game found with that name. Try !listgames".to_owned(),
+                None,
+                None,
+            );
+        }
+    };
+
+    let cat = match get_category(&game, cat_name, pool).await {
+        Some(c) => c,
+        None => {
+            return (
+                format!(
+                    "No matching category found. try !listcategories {}",
+                    game.name
+                ),
+                None,
+                None,
+            );
+        }
+    };
+
+    if cat.retired {
+        return (
+            format!(
+                "{} has been retired. try !listcategories {}",
+                cat.name_pretty, game.name
+            ),
+            None,
+            None,
+        );
+    }
+
+    let notify_before_race = bot_state.get_settings(gid).await.notify_before_race();
+    // not saved yet, so its id won't match any real race
+    let candidate = Race::new(0, gid, game.id, cat.id, occurs);
+    match check_race_time(&candidate, now, Some(notify_before_race), pool).await {
+        Err(refusal) => {
+            return (refusal, None, None);
+        }
+        Ok(warnings) if !warnings.is_empty() && !force => {
+            return (
+                format!("{} Add --force to schedule it anyway.", warnings.join(" ")),
+                None,
+                None,
+            );
+        }
+        Ok(_) => {}
+    }
+
+    let mut r = match create_race(gid, &game, &cat, occurs, pool).await {
+        Ok(r) => r,
+        Err(e) => {
+            warn!("Error creating race: {:?}", e);
+            return (describe_race_error(&e, "creating"), None, None);
+        }
+    };
+
+    if let Some(n) = notes {
+        r.notes = Some(n.to_string());
+        if let Err(e) = r.save(pool).await {
+            warn!("Error saving notes for {}: {}", r, e);
+        }
+    }
+
+    let schedule_content = scheduling_message_content(gid, &r, &game, &cat, bot_state.clone(), pool).await;
+
+    ("Race created!".to_string(), Some(schedule_content), Some(r))
+}
+
+/// Splits `!newrace` input into the time and the optional notes after a `--`. A `--force` before
+/// the notes isn't mistaken for the start of them.
+fn split_notes(input: &str) -> (&str, Option<&str>) {
+    let separator = input
+        .match_indices("--")
+        .map(|(idx, _)| idx)
+        .find(|idx| input[*idx..].split_whitespace().next() != Some("--force"));
+    match separator {
+        Some(idx) => {
+            let notes = input[idx + 2..].trim();
+            (
+                input[..idx].trim(),
+                if notes.is_empty() { None } else { Some(notes) },
+            )
+        }
+        None => (input.trim(), None),
+    }
+}
+
+/// Pulls a standalone `--force` out of a command's arguments
+fn take_force_flag(input: &str) -> (String, bool) {
+    let mut words: Vec<&str> = input.split(' ').collect();
+    match words.iter().position(|w| *w == "--force") {
+        Some(idx) => {
+            words.remove(idx);
+            (words.join(" "), true)
+        }
+        None => (input.to_string(), false),
+    }
+}
+
+// anything further out than this is probably a typo'd year. months are close enough to 30 days.
+const MAX_RACE_MONTHS_AHEAD: i64 = 6;
+// races of the same thing closer together than this would be running at the same time
+const RACE_OVERLAP_HOURS: i64 = 3;
+
+/// Sanity checks when `race` is set to happen. Returns why that can't work at all, or a list of
+/// reasons it probably isn't what the moderator meant, which they can override with `--force`.
+/// `notify_before_race` is None for races that are already past the signup stage.
+async fn check_race_time(
+    race: &Race,
+    now: DateTime<Tz>,
+    notify_before_race: Option<Duration>,
+    pool: &SqlitePool,
+) -> Result<Vec<String>, String> {
+    let occurs = race.get_occurs();
+    if occurs <= now {
+        return Err(format!(
+            "{} is in the past.",
+            datetime_to_discord_format(&occurs)
+        ));
+    }
+
+    if race_exists_at(race, pool).await {
+        return Err("There's already a race of that game and category at that time.".to_string());
+    }
+
+    let mut warnings = vec![];
+    if let Some(gid) = race.get_guild_id() {
+        let overlap = CDuration::hours(RACE_OVERLAP_HOURS);
+        let others = get_races_between(
+            gid,
+            race.game_id,
+            race.category_id,
+            occurs - overlap,
+            occurs + overlap,
+            pool,
+        )
+        .await;
+        for other in others {
+            if other.id == race.id {
+                continue;
+            }
+            warnings.push(format!(
+                "{} is the same game and category, at {}.",
+                other,
+                datetime_to_discord_format(&other.get_occurs())
+            ));
+        }
+    }
+
+    if let Some(notify) = notify_before_race {
+        if occurs <= now + CDuration::from_std(notify).unwrap() {
+            warnings.push(format!(
+                "That's less than {} minutes away, so people won't have long to sign up.",
+                notify.as_secs() / 60
+            ));
+        }
+    }
+    if occurs > now + CDuration::days(30 * MAX_RACE_MONTHS_AHEAD) {
+        warnings.push(format!(
+            "That's more than {} months away.",
+            MAX_RACE_MONTHS_AHEAD
+        ));
+    }
+    Ok(warnings)
+}
+
+/// Turns the database errors people can cause into something they can act on. `doing` is what
+/// was happening, e.g. "creating".
+fn describe_race_error(e: &sqlx::Error, doing: &str) -> String {
+    if is_unique_violation(e) {
+        "There's already a race of that game and category at that time.".to_string()
+    } else if is_foreign_key_violation(e) {
+        "That game or category doesn't exist anymore.".to_string()
+    } else {
+        format!("Unknown error {} the race. Bug Fox about it.", doing)
+    }
+}
+
+fn datetime_to_discord_format(datetime: &DateTime<Tz>) -> String {
+    format!("<t:{}:F>", datetime.timestamp())
+}
+
+/// Builds the text of the message posted in the scheduling channel. Used both when creating the
+/// race and when editing that message later.
+async fn scheduling_message_content(
+    gid: GuildId,
+    race: &Race,
+    game: &Game,
+    cat: &Category,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) -> String {
+    let signup_instructions = if race.signup_buttons {
+        "If you are interested in racing, commentating or restreaming, use the buttons below.\n"
+            .to_string()
+    } else {
+        let (racer_react_name, racer_react_id) = {
+            let emoji_name = bot_state.get_settings(gid).await.racing_emoji_name;
+            let lock = bot_state.emojis.read().await;
+            match lock.get(&gid).and_then(|emojis| emojis.get(&emoji_name)) {
+                None => {
+                    warn!("Can't find raising hand emoji");
+                    (":thumbup:".to_owned(), EmojiId(0))
+                }
+                Some(e) => (e.name.clone(), e.id),
+            }
+        };
+        format!(
+            "If you are interested in racing, react with <:{}:{}>
+If you are available to commentate, react with :{}:
+If you are able to restream, react with :{}:
+",
+            racer_react_name,
+            racer_react_id,
+            Reactions::COMMENTATING.get_name(),
+            Reactions::RESTREAMING.get_name(),
+        )
+    };
+
+    let notes = match &race.notes {
+        Some(n) => format!("\nNotes: {}\n", n),
+        None => "".to_string(),
+    };
+
+    let mut crew_lines = vec![];
+    for (kind, heading) in [
+        (EntrantKind::COMMENTATOR, "Commentary"),
+        (EntrantKind::RESTREAMER, "Restream"),
+    ] {
+        let assigned = get_assigned_crew(race.id, kind, pool).await;
+        if !assigned.is_empty() {
+            let mentions: Vec<String> = assigned.iter().map(|e| format!("<@{}>", e.user_id)).collect();
+            crew_lines.push(format!("{}: {}\n", heading, mentions.join(", ")));
+        }
+    }
+    let crew = if crew_lines.is_empty() {
+        "".to_string()
+    } else {
+        format!("\n{}", crew_lines.join(""))
+    };
+
+    format!(
+        "There will be a race of {} - {} on {} (note that this time is *already localized for you*).
+{}{}
+{}
+(Mod note: This is Race #{})
+",
+        game.name_pretty,
+        cat.name_pretty,
+        datetime_to_discord_format(&race.get_occurs()),
+        notes,
+        crew,
+        signup_instructions,
+        race.id,
+    )
+}
+
+/// Discord won't send messages longer than this many characters
+const DISCORD_MESSAGE_LIMIT: usize = 2000;
+/// How many races `!races past` shows when it isn't given a number
+const DEFAULT_PAST_RACES: i64 = 10;
+
+/// Joins lines into as few messages as fit under `limit`. A line that's too long on its own gets
+/// cut up.
+fn paginate(lines: &[String], limit: usize) -> Vec<String> {
+    let mut pages = vec![];
+    let mut page = String::new();
+    for line in lines {
+        let mut line = line.as_str();
+        while line.chars().count() > limit {
+            let cut = line.char_indices().nth(limit).map(|(i, _)| i).unwrap_or(line.len());
+            if !page.is_empty() {
+                pages.push(std::mem::take(&mut page));
+            }
+            pages.push(line[..cut].to_string());
+            line = &line[cut..];
+        }
+        if !page.is_empty() && page.chars().count() + 1 + line.chars().count() > limit {
+            pages.push(std::mem::take(&mut page));
+        }
+        if !page.is_empty() {
+            page.push('\n');
+        }
+        page.push_str(line);
+    }
+    if !page.is_empty() {
+        pages.push(page);
+    }
+    pages
+}
+
+fn message_link(gid: GuildId, cid: ChannelId, mid: MessageId) -> String {
+    format!("https://discord.com/channels/{}/{}/{}", gid, cid, mid)
+}
+
+/// Which races `!races` should show
+#[derive(Debug, Default, PartialEq)]
+struct RaceFilter {
+    game_id: Option<i64>,
+    category_id: Option<i64>,
+    // completed and cancelled races, newest first, instead of upcoming ones
+    past: bool,
+    limit: Option<i64>,
+}
+
+async fn races(
+    ctx: &CommandContext,
+    args: Arguments<'_>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let gid = match ctx.guild_id {
+        Some(g) => g,
+        None => {
+            return;
+        }
+    };
+    for page in _races(gid, args, pool).await {
+        ctx.reply(page, &bot_state).await;
+    }
+}
+
+async fn _races(gid: GuildId, args: Arguments<'_>, pool: &SqlitePool) -> Vec<String> {
+    let filter = match parse_race_filter(args, pool).await {
+        Ok(f) => f,
+        Err(e) => {
+            return vec![e];
+        }
+    };
+    let races = get_filtered_races(gid, &filter, pool).await;
+    if races.is_empty() {
+        return vec![if filter.past {
+            "No races have happened yet.".to_string()
+        } else {
+            "There are no races scheduled.".to_string()
+        }];
+    }
+
+    let mut lines = vec![if filter.past {
+        "Past races:".to_string()
+    } else {
+        "Upcoming races:".to_string()
+    }];
+    for race in races {
+        let (game, cat) = match (
+            Game::get_by_id(race.game_id, pool).await,
+            Category::get_by_id(race.category_id, pool).await,
+        ) {
+            (Some(g), Some(c)) => (g, c),
+            _ => {
+                warn!("Can't find the game or category for {}", race);
+                continue;
+            }
+        };
+        let racers = get_entrants(race.id, EntrantKind::RACER, pool)
+            .await
+            .into_iter()
+            .filter(|e| e.get_status() != EntrantStatus::WITHDRAWN)
+            .count();
+        let status = match race.get_state() {
+            RaceState::ACTIVE => " (happening now)",
+            RaceState::CANCELLED => " (cancelled)",
+            _ => "",
+        };
+        lines.push(format!(
+            "{}: {} - {}, {}{}. {} {}",
+            race,
+            game.name_pretty,
+            cat.name_pretty,
+            datetime_to_discord_format(&race.get_occurs()),
+            status,
+            racers,
+            if racers == 1 { "racer" } else { "racers" },
+        ));
+    }
+    paginate(&lines, DISCORD_MESSAGE_LIMIT)
+}
+
+/// Reads `[game [category]] [past] [count]` in any order, except that the category has to come
+/// right after its game
+async fn parse_race_filter(
+    args: Arguments<'_>,
+    pool: &SqlitePool,
+) -> Result<RaceFilter, String> {
+    let mut filter = RaceFilter::default();
+    let mut game = None;
+    for arg in args {
+        if arg == "past" {
+            filter.past = true;
+        } else if arg == "upcoming" {
+            filter.past = false;
+        } else if let Ok(n) = arg.parse::<i64>() {
+            if n < 1 {
+                return Err("The number of races has to be at least 1.".to_string());
+            }
+            filter.limit = Some(n);
+        } else if let Some(g) = get_game(arg, pool).await {
+            filter.game_id = Some(g.id);
+            filter.category_id = None;
+            game = Some(g);
+        } else if let Some(c) = match &game {
+            Some(g) => get_category(g, arg, pool).await,
+            None => None,
+        } {
+            filter.category_id = Some(c.id);
+        } else {
+            return Err(format!(
+                "I don't know what \"{}\" is. Please use the following format: \
+                !races [game [category]] [past] [count]",
+                arg
+            ));
+        }
+    }
+    if filter.past && filter.limit.is_none() {
+        filter.limit = Some(DEFAULT_PAST_RACES);
+    }
+    Ok(filter)
+}
+
+async fn race(
+    ctx: &CommandContext,
+    args: Arguments<'_>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let gid = match ctx.guild_id {
+        Some(g) => g,
+        None => {
+            return;
+        }
+    };
+    let scheduling_channel = get_scheduling_channel(gid, bot_state.clone()).await;
+    let active_channel = get_active_channel(gid, bot_state.clone()).await;
+    // this lists everyone signed up, but there's no reason to ping them all
+    for page in _race(gid, args, scheduling_channel, active_channel, pool).await {
+        ctx.reply_quietly(page, &bot_state).await;
+    }
+}
+
+async fn _race(
+    gid: GuildId,
+    mut args: Arguments<'_>,
+    scheduling_channel: Option<ChannelId>,
+    active_channel: Option<ChannelId>,
+    pool: &SqlitePool,
+) -> Vec<String> {
+    let id = match args.next().map(|a| a.parse::<i64>()) {
+        Some(Ok(id)) => id,
+        _ => {
+            return vec!["Please use the following format: !race <race id>".to_string()];
+        }
+    };
+    let race = match get_race(gid, id, pool).await {
+        Some(r) => r,
+        None => {
+            return vec!["No valid race found.".to_string()];
+        }
+    };
+    let (game, cat) = match (
+        Game::get_by_id(race.game_id, pool).await,
+        Category::get_by_id(race.category_id, pool).await,
+    ) {
+        (Some(g), Some(c)) => (g, c),
+        _ => {
+            warn!("Can't find the game or category for {}", race);
+            return vec!["Unknown error fetching the race. Bug Fox about it.".to_string()];
+        }
+    };
+
+    let mut lines = vec![
+        format!("{}: {} - {}", race, game.name_pretty, cat.name_pretty),
+        format!(
+            "When: {} (<t:{}:R>)",
+            datetime_to_discord_format(&race.get_occurs()),
+            race.occurs
+        ),
+        format!("State: {}", race.get_state()),
+    ];
+    if let Some(sid) = race.series_id {
+        lines.push(format!("Part of Series #{}", sid));
+    }
+    if let Some(n) = &race.notes {
+        lines.push(format!("Notes: {}", n));
+    }
+    if let Some(started) = race.started {
+        lines.push(format!("Started: <t:{}:T> (<t:{}:R>)", started, started));
+    }
+    if let Some(expected) = cat.expected_duration {
+        lines.push(format!("Expected length: about {}", format_finish_time(expected)));
+    }
+    if let Some(url) = &race.racetime_room {
+        lines.push(format!("Race room: {}", url));
+    }
+    for (kind, heading) in [
+        (EntrantKind::RACER, "Racers"),
+        (EntrantKind::COMMENTATOR, "Commentators"),
+        (EntrantKind::RESTREAMER, "Restreamers"),
+    ] {
+        let entrants = entrant_mentions(race.id, kind, pool).await;
+        if entrants.is_empty() {
+            lines.push(format!("{}: nobody yet", heading));
+        } else {
+            lines.push(format!("{} ({}): {}", heading, entrants.len(), entrants.join(", ")));
+        }
+    }
+    for (heading, channel, message) in [
+        ("Scheduling message", scheduling_channel, race.get_scheduling_message_id()),
+        ("Confirmation message", active_channel, race.get_active_message_id()),
+    ] {
+        if let (Some(cid), Some(mid)) = (channel, message) {
+            lines.push(format!("{}: {}", heading, message_link(gid, cid, mid)));
+        }
+    }
+    paginate(&lines, DISCORD_MESSAGE_LIMIT)
+}
+
+async fn race_note(
+    ctx: &CommandContext,
+    args: Arguments<'_>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let gid = match moderator_guild(ctx, bot_state.clone()).await {
+        Some(g) => g,
+        None => {
+            ctx.reply("You are not authorized to edit races.", &bot_state).await;
+            return;
+        }
+    };
+
+    let (reply, race) = _race_note(gid, args, pool).await;
+    ctx.reply(reply, &bot_state).await;
+
+    if let Some(r) = race {
+        update_scheduling_message(gid, &r, bot_state.clone(), pool).await;
+    }
+}
+
+/// Sets (or, given no text, clears) the notes on a race. Returns the reply and the updated race.
+async fn _race_note(
+    gid: GuildId,
+    mut args: Arguments<'_>,
+    pool: &SqlitePool,
+) -> (String, Option<Race>) {
+    let syntax_error = "Please use the following format: !racenote <race id> <notes>. Leave the notes off to clear them.";
+    let id = match args.next().map(|a| a.parse::<i64>()) {
+        Some(Ok(id)) => id,
+        _ => {
+            return (syntax_error.to_owned(), None);
+        }
+    };
+    let notes = args
+        .into_remainder()
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .map(str::to_string);
+
+    let mut race = match get_race(gid, id, pool).await {
+        Some(r) => r,
+        None => {
+            return ("No valid race found.".to_string(), None);
+        }
+    };
+    race.notes = notes;
+    match race.save(pool).await {
+        Ok(()) => {
+            let reply = match &race.notes {
+                Some(n) => format!("Notes for {} set to: {}", race, n),
+                None => format!("Notes for {} cleared.", race),
+            };
+            (reply, Some(race))
+        }
+        Err(e) => {
+            warn!("Error saving notes for {}: {}", race, e);
+            ("Unknown error updating the race. Bug Fox about it.".to_string(), None)
+        }
+    }
+}
+
+/// Re-renders a race's scheduling message, if it has one, to match the current state of the race
+async fn update_scheduling_message(
+    gid: GuildId,
+    race: &Race,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let mid = match race.get_scheduling_message_id() {
+        Some(m) => m,
+        None => {
+            return;
+        }
+    };
+    let cid = match get_scheduling_channel(gid, bot_state.clone()).await {
+        Some(c) => c,
+        None => {
+            warn!("No scheduling channel found");
+            return;
+        }
+    };
+    let game = Game::get_by_id(race.game_id, pool).await.unwrap();
+    let cat = Category::get_by_id(race.category_id, pool).await.unwrap();
+    let content = scheduling_message_content(gid, race, &game, &cat, bot_state.clone(), pool).await;
+    if let Err(e) = bot_state
+        .http
+        .update_message(cid, mid)
+        .content(content)
+        .unwrap()
+        .await
+    {
+        warn!("Error updating scheduling message for {}: {}", race, e);
+    }
+}
+
+async fn end_race(
+    ctx: &CommandContext,
+    mut args: Arguments<'_>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let gid = match moderator_guild(ctx, bot_state.clone()).await {
+        Some(g) => g,
+        None => {
+            ctx.reply("You are not authorized to end races.", &bot_state).await;
+            return;
+        }
+    };
+
+    let id = match args.next() {
+        Some(arg) => match arg.parse::<i64>() {
+            Ok(_id) => Some(_id),
+            Err(_) => {
+                ctx.reply("Please specify a race id, or nothing if you want to try to end the currently active race", &bot_state).await;
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let (content, ended) = _end_race(gid, id, pool).await;
+    if let Some(race) = ended {
+        remove_race_roles(gid, &race, bot_state.clone(), pool).await;
+        post_results(gid, &race, bot_state.clone(), pool).await;
+    }
+
+    ctx.reply(content, &bot_state).await;
+}
+
+/// Marks a race COMPLETED. Returns the reply and, if the race was ended, the race itself so the
+/// caller can clean up its discord state.
+async fn _end_race(gid: GuildId, oid: Option<i64>, pool: &SqlitePool) -> (String, Option<Race>) {
+    let orace = match oid {
+        Some(rid) => get_race(gid, rid, pool).await,
+        None => get_active_race(gid, pool).await,
+    };
+
+    match orace {
+        Some(mut race) => match race.get_state() {
+            RaceState::ACTIVE => {
+                race.set_state(RaceState::COMPLETED);
+                if let Err(e) = race.save(pool).await {
+                    warn!("Error completing {}: {:?}", race, e);
+                    return ("Unknown error ending the race. Bug Fox about it.".to_string(), None);
+                }
+                update_ratings(gid, &race, pool).await;
+                (format!("{} completed.", race), Some(race))
+            }
+            _ => (format!("{} is not currently active.", race), None),
+        },
+        None => ("No valid race found.".to_string(), None),
+    }
+}
+
+/// Takes the race roles away from everyone signed up for this race
+async fn remove_race_roles(
+    gid: GuildId,
+    race: &Race,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    for kind in [
+        EntrantKind::RACER,
+        EntrantKind::COMMENTATOR,
+        EntrantKind::RESTREAMER,
+    ] {
+        let role_names = match volunteer_role_name(kind) {
+            Some(name) => vec![name],
+            None => vec!["unconfirmed-racer", "active-racer"],
+        };
+        let mut roles_to_remove = vec![];
+        for name in role_names {
+            if let Some(role) = bot_state.get_role(gid, name).await {
+                roles_to_remove.push(role);
+            }
+        }
+
+        for entrant in get_entrants(race.id, kind, pool).await {
+            if entrant.get_status() == EntrantStatus::WITHDRAWN {
+                continue;
+            }
+            if let Some(user) = entrant.get_user_id() {
+                for role in &roles_to_remove {
+                    if let Err(e) = remove_role(gid, &user, role, bot_state.clone()).await {
+                        warn!("{}", e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Posts the results of a finished race in the active channel
+async fn post_results(gid: GuildId, race: &Race, bot_state: Arc<BotState>, pool: &SqlitePool) {
+    let cid = match get_active_channel(gid, bot_state.clone()).await {
+        Some(c) => c,
+        None => {
+            return;
+        }
+    };
+    let lines = results_table(race, pool).await;
+    for page in paginate(&lines, DISCORD_MESSAGE_LIMIT) {
+        let create = match bot_state.http.create_message(cid).content(page) {
+            Ok(c) => c,
+            Err(e) => {
+                warn!("Error building results for {}: {}", race, e);
+                return;
+            }
+        };
+        // everyone in the race gets mentioned; they don't need pinging about it
+        if let Err(e) = create.allowed_mentions().build().await {
+            warn!("Error posting results for {}: {}", race, e);
+        }
+    }
+}
+
+/// Finishers in order, then forfeits, then anyone who signed up to race but never said how it
+/// went. Empty if nobody raced.
+async fn results_table(race: &Race, pool: &SqlitePool) -> Vec<String> {
+    let results = get_results(race.id, pool).await;
+    let missing: Vec<String> = get_entrants(race.id, EntrantKind::RACER, pool)
+        .await
+        .iter()
+        .filter(|e| e.get_status() != EntrantStatus::WITHDRAWN)
+        .filter(|e| !results.iter().any(|r| r.user_id == e.user_id))
+        .map(|e| format!("<@{}>", e.user_id))
+        .collect();
+    if results.is_empty() && missing.is_empty() {
+        return vec![];
+    }
+
+    let mut lines = match (
+        Game::get_by_id(race.game_id, pool).await,
+        Category::get_by_id(race.category_id, pool).await,
+    ) {
+        (Some(g), Some(c)) => vec![format!(
+            "Results for {} ({} - {}):",
+            race, g.name_pretty, c.name_pretty
+        )],
+        _ => vec![format!("Results for {}:", race)],
+    };
+    for r in &results {
+        let comment = match &r.comment {
+            Some(c) => format!(" - {}", c),
+            None => "".to_string(),
+        };
+        lines.push(match (r.placement, r.finish_time) {
+            (Some(p), Some(t)) => format!("{}. <@{}> {}{}", p, r.user_id, format_finish_time(t), comment),
+            _ => format!("FF <@{}>{}", r.user_id, comment),
+        });
+    }
+    if !missing.is_empty() {
+        lines.push(format!("No result: {}", missing.join(", ")));
+    }
+    lines
+}
+
+fn ordinal(n: i64) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", n, suffix)
+}
+
+/// Finds the active race the user is racing in. `race_id` is only needed if they're in more
+/// than one.
+async fn racing_race(
+    gid: GuildId,
+    user_id: UserId,
+    race_id: Option<i64>,
+    pool: &SqlitePool,
+) -> Result<Race, String> {
+    let races = match race_id {
+        Some(id) => match get_race(gid, id, pool).await {
+            Some(r) if r.get_state() == RaceState::ACTIVE => vec![r],
+            Some(r) => {
+                return Err(format!("{} is not currently active.", r));
+            }
+            None => {
+                return Err("No valid race found.".to_string());
+            }
+        },
+        None => get_active_races(gid, pool).await,
+    };
+    let mut racing = vec![];
+    for race in races {
+        if let Some(e) = get_entrant(race.id, user_id, EntrantKind::RACER, pool).await {
+            if e.get_status() != EntrantStatus::WITHDRAWN {
+                racing.push(race);
+            }
+        }
+    }
+    match (racing.pop(), racing.is_empty()) {
+        (Some(r), true) => Ok(r),
+        (Some(_), false) => Err(
+            "You're in more than one race right now. Please put the race id first.".to_string(),
+        ),
+        (None, _) => match race_id {
+            Some(id) => Err(format!("You aren't racing in Race #{}.", id)),
+            None => Err("You aren't in any race that's going on right now.".to_string()),
+        },
+    }
+}
+
+// how long !start counts down for, and the numbers the countdown message shows along the way
+const START_COUNTDOWN_SECS: i64 = 10;
+const COUNTDOWN_STEPS: [i64; 6] = [5, 4, 3, 2, 1, 0];
+
+async fn start(
+    ctx: &CommandContext,
+    args: Arguments<'_>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let (gid, user_id) = match (ctx.guild_id, ctx.user_id) {
+        (Some(g), Some(u)) => (g, u),
+        _ => {
+            return;
+        }
+    };
+    let moderator = is_moderator(ctx, bot_state.clone()).await;
+    let (reply, race) = _start(gid, user_id, moderator, args, Utc::now().timestamp(), pool).await;
+    ctx.reply(reply, &bot_state).await;
+
+    if let Some(r) = race {
+        let racers = get_entrants(r.id, EntrantKind::RACER, pool)
+            .await
+            .iter()
+            .filter(|e| e.get_status() != EntrantStatus::WITHDRAWN)
+            .map(|e| format!("<@{}>", e.user_id))
+            .collect();
+        // the event loop shouldn't wait on the countdown
+        tokio::spawn(countdown(gid, r, racers, bot_state.clone()));
+    }
+}
+
+/// Starts the race timer: the race counts as starting when the countdown finishes, so finish
+/// times and the automatic end are measured from then. Confirmed racers can start their own race,
+/// and moderators can start any active race. Returns the reply and the started race.
+async fn _start(
+    gid: GuildId,
+    user_id: UserId,
+    moderator: bool,
+    mut args: Arguments<'_>,
+    now: i64,
+    pool: &SqlitePool,
+) -> (String, Option<Race>) {
+    let race_id = match args.next().map(|a| a.parse::<i64>()) {
+        Some(Ok(id)) => Some(id),
+        Some(Err(_)) => {
+            return ("Please use the following format: !start [race id]".to_string(), None);
+        }
+        None => None,
+    };
+    let mut race = match (moderator, race_id) {
+        (true, Some(id)) => match get_race(gid, id, pool).await {
+            Some(r) if r.get_state() == RaceState::ACTIVE => r,
+            Some(r) => {
+                return (format!("{} is not currently active.", r), None);
+            }
+            None => {
+                return ("No valid race found.".to_string(), None);
+            }
+        },
+        _ => match racing_race(gid, user_id, race_id, pool).await {
+            Ok(r) => r,
+            Err(e) => {
+                return (e, None);
+            }
+        },
+    };
+    if let Some(started) = race.started {
+        return (format!("{} already started <t:{}:R>.", race, started), None);
+    }
+    if !moderator {
+        let confirmed = matches!(
+            get_entrant(race.id, user_id, EntrantKind::RACER, pool).await,
+            Some(e) if e.get_status() == EntrantStatus::CONFIRMED
+        );
+        if !confirmed {
+            return (
+                format!("Only confirmed racers can start {}. Confirm first, or ask a moderator.", race),
+                None,
+            );
+        }
+    }
+
+    let starts = now + START_COUNTDOWN_SECS;
+    race.started = Some(starts);
+    match race.save(pool).await {
+        Ok(()) => (format!("{} starts <t:{}:R>. Good luck!", race, starts), Some(race)),
+        Err(e) => {
+            warn!("Error starting {}: {:?}", race, e);
+            ("Unknown error starting the race. Bug Fox about it.".to_string(), None)
+        }
+    }
+}
+
+/// Counts down to the start of a race in the active channel, pinging its racers once at the top
+async fn countdown(gid: GuildId, race: Race, racers: Vec<String>, bot_state: Arc<BotState>) {
+    let cid = match get_active_channel(gid, bot_state.clone()).await {
+        Some(c) => c,
+        None => {
+            warn!("No active channel found to count down {}", race);
+            return;
+        }
+    };
+    let starts = race.start_timestamp();
+    let text = |left: i64| {
+        if left > 0 {
+            format!("{} {} starts in {}...", racers.join(" "), race, left)
+        } else {
+            format!("{} {}: GO!", racers.join(" "), race)
+        }
+    };
+    let mid = match bot_state
+        .http
+        .create_message(cid)
+        .content(text(starts - Utc::now().timestamp()))
+        .unwrap()
+        .allowed_mentions()
+        .parse_users()
+        .build()
+        .await
+    {
+        Ok(m) => m.id,
+        Err(e) => {
+            warn!("Error posting the countdown for {}: {}", race, e);
+            return;
+        }
+    };
+    for left in COUNTDOWN_STEPS.iter() {
+        let wait = (starts - left) * 1000 - Utc::now().timestamp_millis();
+        if wait > 0 {
+            tokio::time::sleep(Duration::from_millis(wait as u64)).await;
+        }
+        if let Err(e) = bot_state
+            .http
+            .update_message(cid, mid)
+            .content(text(*left))
+            .unwrap()
+            .await
+        {
+            warn!("Error updating the countdown for {}: {}", race, e);
+        }
+    }
+}
+
+async fn done(
+    ctx: &CommandContext,
+    args: Arguments<'_>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let (gid, user_id) = match (ctx.guild_id, ctx.user_id) {
+        (Some(g), Some(u)) => (g, u),
+        _ => {
+            return;
+        }
+    };
+    let now = Local::now().with_timezone(&Eastern);
+    ctx.reply(_done(gid, user_id, args, now, pool).await, &bot_state).await;
+}
+
+async fn _done(
+    gid: GuildId,
+    user_id: UserId,
+    args: Arguments<'_>,
+    now: DateTime<Tz>,
+    pool: &SqlitePool,
+) -> String {
+    let usage = "Please use the following format: !done <time, like 1:23:45> [race id] [-- comment]";
+    let (rest, comment) = match args.into_remainder() {
+        Some(r) => split_notes(r),
+        None => {
+            return usage.to_string();
+        }
+    };
+    let words: Vec<&str> = rest.split_whitespace().collect();
+    let (race_id, time) = match words.as_slice() {
+        [time] => (None, *time),
+        [time, id] => match id.parse::<i64>() {
+            Ok(id) => (Some(id), *time),
+            Err(_) => {
+                return usage.to_string();
+            }
+        },
+        _ => {
+            return usage.to_string();
+        }
+    };
+    let secs = match parse_finish_time(time) {
+        Some(s) => s,
+        None => {
+            return format!(
+                "Couldn't understand the time `{}`. Try something like 1:23:45.",
+                time
+            );
+        }
+    };
+    let race = match racing_race(gid, user_id, race_id, pool).await {
+        Ok(r) => r,
+        Err(e) => {
+            return e;
+        }
+    };
+    if race.start_timestamp().saturating_add(secs) > now.timestamp() {
+        return format!(
+            "{} hasn't been going for {} yet.",
+            race,
+            format_finish_time(secs)
+        );
+    }
+    if let Err(e) = set_result(race.id, user_id, Some(secs), comment, pool).await {
+        warn!("Error saving {}'s result for {}: {:?}", user_id, race, e);
+        return "Unknown error saving your result. Bug Fox about it.".to_string();
+    }
+    match get_result(race.id, user_id, pool).await.and_then(|r| r.placement) {
+        Some(p) => format!(
+            "You finished {} in {} ({} so far).",
+            race,
+            format_finish_time(secs),
+            ordinal(p)
+        ),
+        None => format!("You finished {} in {}.", race, format_finish_time(secs)),
+    }
+}
+
+async fn forfeit(
+    ctx: &CommandContext,
+    args: Arguments<'_>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let (gid, user_id) = match (ctx.guild_id, ctx.user_id) {
+        (Some(g), Some(u)) => (g, u),
+        _ => {
+            return;
+        }
+    };
+    ctx.reply(_forfeit(gid, user_id, args, pool).await, &bot_state).await;
+}
+
+async fn _forfeit(gid: GuildId, user_id: UserId, args: Arguments<'_>, pool: &SqlitePool) -> String {
+    let (rest, comment) = match args.into_remainder() {
+        Some(r) => split_notes(r),
+        None => ("", None),
+    };
+    let race_id = match rest.split_whitespace().collect::<Vec<&str>>().as_slice() {
+        [] => None,
+        [id] => match id.parse::<i64>() {
+            Ok(id) => Some(id),
+            Err(_) => {
+                return "Please use the following format: !forfeit [race id] [-- comment]"
+                    .to_string();
+            }
+        },
+        _ => {
+            return "Please use the following format: !forfeit [race id] [-- comment]".to_string();
+        }
+    };
+    let race = match racing_race(gid, user_id, race_id, pool).await {
+        Ok(r) => r,
+        Err(e) => {
+            return e;
+        }
+    };
+    match set_result(race.id, user_id, None, comment, pool).await {
+        Ok(()) => format!("You forfeited {}.", race),
+        Err(e) => {
+            warn!("Error saving {}'s forfeit for {}: {:?}", user_id, race, e);
+            "Unknown error saving your result. Bug Fox about it.".to_string()
+        }
+    }
+}
+
+async fn set_result_command(
+    ctx: &CommandContext,
+    args: Arguments<'_>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let gid = match moderator_guild(ctx, bot_state.clone()).await {
+        Some(g) => g,
+        None => {
+            ctx.reply("You are not authorized to change results.", &bot_state)
+                .await;
+            return;
+        }
+    };
+    ctx.reply_quietly(_set_result(gid, args, pool).await, &bot_state)
+        .await;
+}
+
+/// Lets moderators fix anyone's result, during the race or after it's over
+async fn _set_result(gid: GuildId, args: Arguments<'_>, pool: &SqlitePool) -> String {
+    let usage = "Please use the following format: !setresult <race id> @user <time|forfeit|clear> [-- comment]";
+    let (rest, comment) = match args.into_remainder() {
+        Some(r) => split_notes(r),
+        None => {
+            return usage.to_string();
+        }
+    };
+    let (race_id, user_id, result) = match rest.split_whitespace().collect::<Vec<&str>>().as_slice()
+    {
+        [id, user, result] => match (id.parse::<i64>(), parse_user_mention(user)) {
+            (Ok(id), Some(u)) => (id, u, result.to_lowercase()),
+            _ => {
+                return usage.to_string();
+            }
+        },
+        _ => {
+            return usage.to_string();
+        }
+    };
+    let race = match get_race(gid, race_id, pool).await {
+        Some(r) => r,
+        None => {
+            return "No valid race found.".to_string();
+        }
+    };
+    match race.get_state() {
+        RaceState::ACTIVE | RaceState::COMPLETED => {}
+        _ => {
+            return format!("{} hasn't started.", race);
+        }
+    }
+
+    let (res, reply) = match result.as_str() {
+        "clear" => (
+            clear_result(race.id, user_id, pool).await,
+            format!("Cleared <@{}>'s result for {}.", user_id, race),
+        ),
+        "forfeit" | "ff" => (
+            set_result(race.id, user_id, None, comment, pool).await,
+            format!("<@{}> forfeited {}.", user_id, race),
+        ),
+        time => match parse_finish_time(time) {
+            Some(secs) => (
+                set_result(race.id, user_id, Some(secs), comment, pool).await,
+                format!(
+                    "<@{}> finished {} in {}.",
+                    user_id,
+                    race,
+                    format_finish_time(secs)
+                ),
+            ),
+            None => {
+                return format!(
+                    "Couldn't understand the time `{}`. Try something like 1:23:45.",
+                    time
+                );
+            }
+        },
+    };
+    if let Err(e) = res {
+        warn!("Error saving {}'s result for {}: {:?}", user_id, race, e);
+        return "Unknown error saving the result. Bug Fox about it.".to_string();
+    }
+    if race.get_state() == RaceState::COMPLETED {
+        if let Err(e) = recompute_ratings(gid, race.category_id, pool).await {
+            warn!("Error recomputing ratings after changing {}: {:?}", race, e);
+        }
+    }
+    reply
+}
+
+/// How many racers each page of `!leaderboard` shows
+const LEADERBOARD_PAGE_SIZE: usize = 20;
+/// How many of someone's races `!pb` lists
+const PB_HISTORY_LIMIT: usize = 20;
+
+/// One racer's standing in a category, over every completed race
+#[derive(sqlx::FromRow, Debug, PartialEq)]
+struct LeaderboardEntry {
+    user_id: String,
+    // null if they've never finished
+    pb: Option<i64>,
+    races: i64,
+    wins: i64,
+    avg_placement: Option<f64>,
+}
+
+/// Reads the `<game> <category>` at the front of the arguments
+async fn game_and_category(
+    args: &mut Arguments<'_>,
+    usage: &str,
+    pool: &SqlitePool,
+) -> Result<(Game, Category), String> {
+    let (game_name, cat_name) = match (args.next(), args.next()) {
+        (Some(g), Some(c)) => (g, c),
+        _ => {
+            return Err(usage.to_string());
+        }
+    };
+    let game = match get_game(game_name, pool).await {
+        Some(g) => g,
+        None => {
+            return Err("No game found with that name. Try !listgames".to_string());
+        }
+    };
+    match get_category(&game, cat_name, pool).await {
+        Some(c) => Ok((game, c)),
+        None => Err(format!(
+            "No matching category found. try !listcategories {}",
+            game.name
+        )),
+    }
+}
+
+async fn leaderboard(
+    ctx: &CommandContext,
+    args: Arguments<'_>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let gid = match ctx.guild_id {
+        Some(g) => g,
+        None => {
+            return;
+        }
+    };
+    // everyone on the board gets mentioned; nobody needs a ping for it
+    for page in _leaderboard(gid, args, pool).await {
+        ctx.reply_quietly(page, &bot_state).await;
+    }
+}
+
+async fn _leaderboard(gid: GuildId, mut args: Arguments<'_>, pool: &SqlitePool) -> Vec<String> {
+    let usage = "Please use the following format: !leaderboard <game> <category> [page]";
+    let (game, cat) = match game_and_category(&mut args, usage, pool).await {
+        Ok(gc) => gc,
+        Err(e) => {
+            return vec![e];
+        }
+    };
+    let page = match args.next().map(|p| p.parse::<usize>()) {
+        None => 1,
+        Some(Ok(p)) if p > 0 => p,
+        Some(_) => {
+            return vec![usage.to_string()];
+        }
+    };
+
+    let entries = get_leaderboard(gid, game.id, cat.id, pool).await;
+    if entries.is_empty() {
+        return vec![format!(
+            "Nobody has raced {} - {} yet.",
+            game.name_pretty, cat.name_pretty
+        )];
+    }
+    let pages = entries.len().div_ceil(LEADERBOARD_PAGE_SIZE);
+    if page > pages {
+        return vec![if pages == 1 {
+            "There's only one page.".to_string()
+        } else {
+            format!("There are only {} pages.", pages)
+        }];
+    }
+
+    let mut lines = vec![format!(
+        "{} - {} leaderboard:",
+        game.name_pretty, cat.name_pretty
+    )];
+    let start = (page - 1) * LEADERBOARD_PAGE_SIZE;
+    for (rank, entry) in entries
+        .iter()
+        .enumerate()
+        .skip(start)
+        .take(LEADERBOARD_PAGE_SIZE)
+    {
+        let races = format!(
+            "{} {}",
+            entry.races,
+            if entry.races == 1 { "race" } else { "races" }
+        );
+        lines.push(match (entry.pb, entry.avg_placement) {
+            (Some(pb), Some(avg)) => format!(
+                "{}. <@{}> PB {}, {}, {} {}, average place {:.1}",
+                rank + 1,
+                entry.user_id,
+                format_finish_time(pb),
+                races,
+                entry.wins,
+                if entry.wins == 1 { "win" } else { "wins" },
+                avg
+            ),
+            _ => format!("-. <@{}> no finishes, {}", entry.user_id, races),
+        });
+    }
+    if pages > 1 {
+        lines.push(format!(
+            "Page {} of {}. `!leaderboard {} {} <page>` for the rest.",
+            page, pages, game.name, cat.name
+        ));
+    }
+    paginate(&lines, DISCORD_MESSAGE_LIMIT)
+}
+
+async fn pb(
+    ctx: &CommandContext,
+    args: Arguments<'_>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let (gid, user_id) = match (ctx.guild_id, ctx.user_id) {
+        (Some(g), Some(u)) => (g, u),
+        _ => {
+            return;
+        }
+    };
+    for page in _pb(gid, user_id, args, pool).await {
+        ctx.reply_quietly(page, &bot_state).await;
+    }
+}
+
+/// Someone's best time and their recent results in a category. `caller` is who to look up when
+/// no user is given.
+async fn _pb(gid: GuildId, caller: UserId, mut args: Arguments<'_>, pool: &SqlitePool) -> Vec<String> {
+    let usage = "Please use the following format: !pb <game> <category> [@user]";
+    let (game, cat) = match game_and_category(&mut args, usage, pool).await {
+        Ok(gc) => gc,
+        Err(e) => {
+            return vec![e];
+        }
+    };
+    let user_id = match args.next() {
+        Some(mention) => match parse_user_mention(mention) {
+            Some(u) => u,
+            None => {
+                return vec![usage.to_string()];
+            }
+        },
+        None => caller,
+    };
+
+    let history = get_user_results(gid, game.id, cat.id, user_id, pool).await;
+    if history.is_empty() {
+        return vec![format!(
+            "<@{}> hasn't raced {} - {} yet.",
+            user_id, game.name_pretty, cat.name_pretty
+        )];
+    }
+    let best = history
+        .iter()
+        .filter_map(|(result, race)| result.finish_time.map(|t| (t, race)))
+        .min_by_key(|(t, _)| *t);
+    let mut lines = vec![match best {
+        Some((t, race)) => format!(
+            "<@{}>'s {} - {} PB is {}, from {} on <t:{}:D>.",
+            user_id,
+            game.name_pretty,
+            cat.name_pretty,
+            format_finish_time(t),
+            race,
+            race.occurs
+        ),
+        None => format!(
+            "<@{}> hasn't finished {} - {} yet.",
+            user_id, game.name_pretty, cat.name_pretty
+        ),
+    }];
+    for (result, race) in history.iter().take(PB_HISTORY_LIMIT) {
+        let outcome = match (result.finish_time, result.placement) {
+            (Some(t), Some(p)) => format!("{}, {}", format_finish_time(t), ordinal(p)),
+            _ => "forfeit".to_string(),
+        };
+        let comment = match &result.comment {
+            Some(c) => format!(" - {}", c),
+            None => "".to_string(),
+        };
+        lines.push(format!("{} <t:{}:D>: {}{}", race, race.occurs, outcome, comment));
+    }
+    if history.len() > PB_HISTORY_LIMIT {
+        lines.push(format!("...and {} older races.", history.len() - PB_HISTORY_LIMIT));
+    }
+    paginate(&lines, DISCORD_MESSAGE_LIMIT)
+}
+
+/// How many of someone's rating changes `!rating` lists
+const RATING_HISTORY_LIMIT: usize = 10;
+
+fn format_rating(rating: f64) -> String {
+    format!("{:.0}", rating)
+}
+
+/// Everyone's latest rating and how many rated races they've run, highest rating first.
+/// `changes` has to be oldest first.
+fn current_ratings(changes: &[RatingChange]) -> Vec<(String, f64, usize)> {
+    let mut latest: HashMap<&str, (f64, usize)> = HashMap::new();
+    for change in changes {
+        let entry = latest.entry(&change.user_id).or_insert((0.0, 0));
+        *entry = (change.rating_after, entry.1 + 1);
+    }
+    let mut ratings: Vec<(String, f64, usize)> = latest
+        .into_iter()
+        .map(|(user, (rating, races))| (user.to_string(), rating, races))
+        .collect();
+    ratings.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then_with(|| a.0.cmp(&b.0)));
+    ratings
+}
+
+/// Rates a completed race and records everyone's change in `tx`, so a failure partway through
+/// doesn't leave half a race rated. `current` holds everyone's rating in the race's category
+/// going in, and gets updated. Confirmed racers with no result count as forfeits.
+async fn apply_race_ratings(
+    race: &Race,
+    current: &mut HashMap<String, f64>,
+    tx: &mut Transaction<'_, Sqlite>,
+    pool: &SqlitePool,
+) -> Result<(), sqlx::Error> {
+    let mut racers: Vec<(String, Outcome)> = get_results(race.id, pool)
+        .await
+        .into_iter()
+        .map(|r| {
+            let outcome = match r.finish_time {
+                Some(t) if !r.forfeit => Outcome::Finished(t),
+                _ => Outcome::Forfeit,
+            };
+            (r.user_id, outcome)
+        })
+        .collect();
+    for entrant in get_entrants(race.id, EntrantKind::RACER, pool).await {
+        if entrant.get_status() == EntrantStatus::CONFIRMED
+            && !racers.iter().any(|(u, _)| *u == entrant.user_id)
+        {
+            racers.push((entrant.user_id, Outcome::Forfeit));
+        }
+    }
+    if racers.len() < 2 {
+        return Ok(());
+    }
+
+    let before: Vec<(f64, Outcome)> = racers
+        .iter()
+        .map(|(u, o)| (current.get(u).copied().unwrap_or(INITIAL_RATING), *o))
+        .collect();
+    let after = rate_race(&before);
+    let now = Utc::now().timestamp();
+    for (((user_id, _), (rating_before, _)), rating_after) in racers.iter().zip(before).zip(after) {
+        sqlx::query(
+            "INSERT INTO rating_change (guild_id, category_id, race_id, user_id, rating_before, \
+            rating_after, created) VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(race.guild_id.clone())
+        .bind(race.category_id)
+        .bind(race.id)
+        .bind(user_id)
+        .bind(rating_before)
+        .bind(rating_after)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+        current.insert(user_id.clone(), rating_after);
+    }
+    Ok(())
+}
+
+/// Updates ratings for a race that just completed. If a later race in the category has already
+/// been rated, the category gets rated again from the start so everything stays in order.
+async fn update_ratings(gid: GuildId, race: &Race, pool: &SqlitePool) {
+    let changes = get_rating_changes(gid, race.category_id, pool).await;
+    // changes are always added in race order, so the last one is from the latest rated race
+    let out_of_order = match changes.last() {
+        Some(last) => match Race::get_by_id(last.race_id, pool).await {
+            Some(r) => (r.occurs, r.id) > (race.occurs, race.id),
+            None => true,
+        },
+        None => false,
+    };
+    let res = if out_of_order {
+        recompute_ratings(gid, race.category_id, pool).await
+    } else {
+        let mut current = changes
+            .into_iter()
+            .map(|c| (c.user_id, c.rating_after))
+            .collect();
+        match pool.begin().await {
+            Ok(mut tx) => match apply_race_ratings(race, &mut current, &mut tx, pool).await {
+                Ok(()) => tx.commit().await,
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        }
+    };
+    if let Err(e) = res {
+        warn!("Error updating ratings for {}: {:?}", race, e);
+    }
+}
+
+/// Throws away a category's ratings and rates every completed race again, oldest first. It all
+/// happens in one transaction, so the old ratings stay put if anything goes wrong.
+async fn recompute_ratings(gid: GuildId, category_id: i64, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM rating_change WHERE guild_id = ? AND category_id = ?")
+        .bind(gid.to_string())
+        .bind(category_id)
+        .execute(&mut tx)
+        .await?;
+    let races = sqlx::query_as::<_, Race>(
+        "SELECT * FROM race WHERE guild_id = ? AND category_id = ? AND state = ? ORDER BY occurs, id",
+    )
+    .bind(gid.to_string())
+    .bind(category_id)
+    .bind(RaceState::COMPLETED.to_string())
+    .fetch_all(&mut tx)
+    .await?;
+    let mut current = HashMap::new();
+    for race in races {
+        apply_race_ratings(&race, &mut current, &mut tx, pool).await?;
+    }
+    tx.commit().await
+}
+
+async fn rating(
+    ctx: &CommandContext,
+    args: Arguments<'_>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let (gid, user_id) = match (ctx.guild_id, ctx.user_id) {
+        (Some(g), Some(u)) => (g, u),
+        _ => {
+            return;
+        }
+    };
+    for page in _rating(gid, user_id, args, pool).await {
+        ctx.reply_quietly(page, &bot_state).await;
+    }
+}
+
+/// Someone's ratings in every category, or their recent changes in one. `caller` is who to look
+/// up when no user is given.
+async fn _rating(gid: GuildId, caller: UserId, args: Arguments<'_>, pool: &SqlitePool) -> Vec<String> {
+    let usage = "Please use the following format: !rating [@user] [game category]";
+    let (mentions, words): (Vec<&str>, Vec<&str>) =
+        args.partition(|a| parse_user_mention(a).is_some());
+    let user_id = match mentions.as_slice() {
+        [] => caller,
+        [mention] => parse_user_mention(mention).unwrap(),
+        _ => {
+            return vec![usage.to_string()];
+        }
+    };
+
+    if words.is_empty() {
+        let changes = get_user_rating_changes(gid, user_id, pool).await;
+        let mut categories: Vec<i64> = vec![];
+        for change in &changes {
+            if !categories.contains(&change.category_id) {
+                categories.push(change.category_id);
+            }
+        }
+        if categories.is_empty() {
+            return vec![format!("<@{}> doesn't have a rating yet.", user_id)];
+        }
+        let mut lines = vec![format!("<@{}>'s ratings:", user_id)];
+        for category_id in categories {
+            let in_category: Vec<&RatingChange> = changes
+                .iter()
+                .filter(|c| c.category_id == category_id)
+                .collect();
+            let name = match Category::get_by_id(category_id, pool).await {
+                Some(c) => match Game::get_by_id(c.game_id, pool).await {
+                    Some(g) => format!("{} - {}", g.name_pretty, c.name_pretty),
+                    None => c.name_pretty,
+                },
+                None => {
+                    continue;
+                }
+            };
+            lines.push(format!(
+                "{}: {} after {} {}",
+                name,
+                format_rating(in_category.last().unwrap().rating_after),
+                in_category.len(),
+                if in_category.len() == 1 { "race" } else { "races" }
+            ));
+        }
+        return paginate(&lines, DISCORD_MESSAGE_LIMIT);
+    }
+
+    let rest = words.join(" ");
+    let mut args = Arguments::new(&rest);
+    let (game, cat) = match game_and_category(&mut args, usage, pool).await {
+        Ok(gc) => gc,
+        Err(e) => {
+            return vec![e];
+        }
+    };
+    if args.next().is_some() {
+        return vec![usage.to_string()];
+    }
+    let changes = get_rating_changes(gid, cat.id, pool).await;
+    let ratings = current_ratings(&changes);
+    let (rank, (_, current, races)) = match ratings
+        .iter()
+        .enumerate()
+        .find(|(_, (u, _, _))| *u == user_id.to_string())
+    {
+        Some(r) => r,
+        None => {
+            return vec![format!(
+                "<@{}> hasn't been rated in {} - {} yet.",
+                user_id, game.name_pretty, cat.name_pretty
+            )];
+        }
+    };
+    let mut lines = vec![format!(
+        "<@{}> is rated {} in {} - {} after {} {}, #{} of {}.",
+        user_id,
+        format_rating(*current),
+        game.name_pretty,
+        cat.name_pretty,
+        races,
+        if *races == 1 { "race" } else { "races" },
+        rank + 1,
+        ratings.len()
+    )];
+    for change in changes
+        .iter()
+        .rev()
+        .filter(|c| c.user_id == user_id.to_string())
+        .take(RATING_HISTORY_LIMIT)
+    {
+        let when = match Race::get_by_id(change.race_id, pool).await {
+            Some(r) => format!(" <t:{}:D>", r.occurs),
+            None => "".to_string(),
+        };
+        lines.push(format!(
+            "Race #{}{}: {} → {} ({:+.0})",
+            change.race_id,
+            when,
+            format_rating(change.rating_before),
+            format_rating(change.rating_after),
+            change.rating_after - change.rating_before
+        ));
+    }
+    paginate(&lines, DISCORD_MESSAGE_LIMIT)
+}
+
+async fn ratings(
+    ctx: &CommandContext,
+    args: Arguments<'_>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let gid = match ctx.guild_id {
+        Some(g) => g,
+        None => {
+            return;
+        }
+    };
+    for page in _ratings(gid, args, pool).await {
+        ctx.reply_quietly(page, &bot_state).await;
+    }
+}
+
+async fn _ratings(gid: GuildId, mut args: Arguments<'_>, pool: &SqlitePool) -> Vec<String> {
+    let usage = "Please use the following format: !ratings <game> <category>";
+    let (game, cat) = match game_and_category(&mut args, usage, pool).await {
+        Ok(gc) => gc,
+        Err(e) => {
+            return vec![e];
+        }
+    };
+    let ratings = current_ratings(&get_rating_changes(gid, cat.id, pool).await);
+    if ratings.is_empty() {
+        return vec![format!(
+            "Nobody has been rated in {} - {} yet.",
+            game.name_pretty, cat.name_pretty
+        )];
+    }
+    let mut lines = vec![format!("{} - {} ratings:", game.name_pretty, cat.name_pretty)];
+    for (rank, (user_id, rating, races)) in ratings.iter().enumerate() {
+        lines.push(format!(
+            "{}. <@{}> {} ({} {})",
+            rank + 1,
+            user_id,
+            format_rating(*rating),
+            races,
+            if *races == 1 { "race" } else { "races" }
+        ));
+    }
+    paginate(&lines, DISCORD_MESSAGE_LIMIT)
+}
+
+async fn cancel_race(
+    ctx: &CommandContext,
+    mut args: Arguments<'_>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let gid = match moderator_guild(ctx, bot_state.clone()).await {
+        Some(g) => g,
+        None => {
+            ctx.reply("You are not authorized to cancel races.", &bot_state).await;
+            return;
+        }
+    };
+
+    let id = match args.next().map(|a| a.parse::<i64>()) {
+        Some(Ok(id)) => id,
+        _ => {
+            ctx.reply("Please use the following format: !cancelrace <race id> [reason]", &bot_state).await;
+            return;
+        }
+    };
+    let reason = args
+        .into_remainder()
+        .map(str::trim)
+        .filter(|r| !r.is_empty())
+        .map(str::to_string);
+
+    // figure out who to tell before we go cleaning up the messages they reacted to
+    let interested = match get_race(gid, id, pool).await {
+        Some(race) => get_interested_users(gid, &race, bot_state.clone(), pool).await,
+        None => vec![],
+    };
+
+    let (content, cancelled) = _cancel_race(gid, id, pool).await;
+    ctx.reply(content, &bot_state).await;
+
+    if let Some(race) = cancelled {
+        cleanup_cancelled_race(gid, &race, reason, interested, bot_state.clone(), pool).await;
+    }
+}
+
+/// Marks a race CANCELLED. Races that are already over can't be cancelled.
+async fn _cancel_race(gid: GuildId, id: i64, pool: &SqlitePool) -> (String, Option<Race>) {
+    let mut race = match get_race(gid, id, pool).await {
+        Some(r) => r,
+        None => {
+            return ("No valid race found.".to_string(), None);
+        }
+    };
+
+    match race.get_state() {
+        RaceState::SCHEDULED | RaceState::ACTIVE => {
+            race.set_state(RaceState::CANCELLED);
+            match race.save(pool).await {
+                Ok(()) => (format!("{} cancelled.", race), Some(race)),
+                Err(e) => {
+                    warn!("Error cancelling {}: {}", race, e);
+                    ("Unknown error cancelling the race. Bug Fox about it.".to_string(), None)
+                }
+            }
+        }
+        RaceState::COMPLETED | RaceState::CANCELLED => {
+            (format!("{} is already over.", race), None)
+        }
+    }
+}
+
+/// Everyone (other than us) who reacted to sign up for or confirm a race, plus anyone signed up
+/// for it who hasn't withdrawn.
+async fn get_interested_users(
+    gid: GuildId,
+    race: &Race,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) -> Vec<UserId> {
+    let mut users: HashSet<UserId> = Default::default();
+    if let (Some(mid), Some(cid), Some(react)) = (
+        race.get_scheduling_message_id(),
+        get_scheduling_channel(gid, bot_state.clone()).await,
+        bot_state.get_racing_reaction(gid).await,
+    ) {
+        if let Some(reactors) = get_reactions_for(bot_state.clone(), cid, mid, react).await {
+            users.extend(reactors.iter().map(|u| u.id));
+        }
+    }
+    if let (Some(mid), Some(cid)) = (
+        race.get_active_message_id(),
+        get_active_channel(gid, bot_state.clone()).await,
+    ) {
+        if let Some(reactors) = get_reactions_for(
+            bot_state.clone(),
+            cid,
+            mid,
+            Reactions::CONFIRMING.get_reaction_type(),
+        )
+        .await
+        {
+            users.extend(reactors.iter().map(|u| u.id));
+        }
+    }
+    for racer in get_entrants(race.id, EntrantKind::RACER, pool).await {
+        if racer.get_status() != EntrantStatus::WITHDRAWN {
+            users.extend(racer.get_user_id());
+        }
+    }
+    if let Some(me) = bot_state.cache.current_user() {
+        users.remove(&me.id);
+    }
+    users.into_iter().collect()
+}
+
+/// Cleans up discord state for a race that just got cancelled: the scheduling message is edited
+/// to say so, the confirmation message is deleted, roles are removed, and everyone who was
+/// interested gets pinged.
+async fn cleanup_cancelled_race(
+    gid: GuildId,
+    race: &Race,
+    reason: Option<String>,
+    interested: Vec<UserId>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let game = Game::get_by_id(race.game_id, pool).await.unwrap();
+    let cat = Category::get_by_id(race.category_id, pool).await.unwrap();
+    let reason = match reason {
+        Some(r) => format!(" Reason: {}", r),
+        None => "".to_string(),
+    };
+    let scheduling_channel = get_scheduling_channel(gid, bot_state.clone()).await;
+
+    if let (Some(cid), Some(mid)) = (scheduling_channel, race.get_scheduling_message_id()) {
+        let content = format!(
+            "**CANCELLED**: The race of {} - {} on {} has been cancelled.{}\n\n(Mod note: This was {})",
+            game.name_pretty,
+            cat.name_pretty,
+            datetime_to_discord_format(&race.get_occurs()),
+            reason,
+            race,
+        );
+        if let Err(e) = bot_state
+            .http
+            .update_message(cid, mid)
+            .content(content)
+            .unwrap()
+            .await
+        {
+            warn!("Error editing scheduling message for cancelled {}: {}", race, e);
+        }
+        if race.signup_buttons {
+            if let Err(e) = interactions::set_components(&bot_state.http, cid, mid, vec![]).await {
+                warn!("Error removing signup buttons for cancelled {}: {}", race, e);
+            }
+        } else if let Err(e) = bot_state.http.delete_all_reactions(cid, mid).await {
+            warn!("Error clearing reactions for cancelled {}: {}", race, e);
+        }
+    }
+
+    if let (Some(cid), Some(mid)) = (
+        get_active_channel(gid, bot_state.clone()).await,
+        race.get_active_message_id(),
+    ) {
+        if let Err(e) = bot_state.http.delete_message(cid, mid).await {
+            warn!("Error deleting confirmation message for cancelled {}: {}", race, e);
+        }
+    }
+
+    remove_race_roles(gid, race, bot_state.clone(), pool).await;
+
+    if interested.is_empty() {
+        return;
+    }
+    if let Some(cid) = scheduling_channel {
+        let mentions = interested
+            .iter()
+            .map(|u| format!("<@{}>", u))
+            .collect::<Vec<String>>()
+            .join(" ");
+        if let Err(e) = bot_state
+            .http
+            .create_message(cid)
+            .content(format!(
+                "{} The {} - {} race on {} has been cancelled.{}",
+                mentions,
+                game.name_pretty,
+                cat.name_pretty,
+                datetime_to_discord_format(&race.get_occurs()),
+                reason,
+            ))
+            .unwrap()
+            .await
+        {
+            warn!("Error announcing the cancellation of {}: {}", race, e);
+        }
+    }
+}
+
+async fn reschedule_race(
+    ctx: &CommandContext,
+    args: Arguments<'_>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let gid = match moderator_guild(ctx, bot_state.clone()).await {
+        Some(g) => g,
+        None => {
+            ctx.reply("You are not authorized to reschedule races.", &bot_state).await;
+            return;
+        }
+    };
+
+    let (reply, rescheduled, stale_active_message) =
+        _reschedule_race(
+            gid,
+            args,
+            Local::now().with_timezone(&Eastern),
+            bot_state.get_settings(gid).await.notify_before_race(),
+            ctx.timezone(pool).await,
+            pool,
+        )
+        .await;
+    ctx.reply(reply, &bot_state).await;
+
+    let race = match rescheduled {
+        Some(r) => r,
+        None => {
+            return;
+        }
+    };
+
+    let interested = get_interested_users(gid, &race, bot_state.clone(), pool).await;
+    update_scheduling_message(gid, &race, bot_state.clone(), pool).await;
+
+    if let Some(mid) = stale_active_message {
+        if let Some(cid) = get_active_channel(gid, bot_state.clone()).await {
+            if let Err(e) = bot_state.http.delete_message(cid, mid).await {
+                warn!("Error deleting confirmation message for rescheduled {}: {}", race, e);
+            }
+        }
+        remove_race_roles(gid, &race, bot_state.clone(), pool).await;
+    }
+
+    if interested.is_empty() {
+        return;
+    }
+    if let Some(cid) = get_scheduling_channel(gid, bot_state.clone()).await {
+        let game = Game::get_by_id(race.game_id, pool).await.unwrap();
+        let cat = Category::get_by_id(race.category_id, pool).await.unwrap();
+        let mentions = interested
+            .iter()
+            .map(|u| format!("<@{}>", u))
+            .collect::<Vec<String>>()
+            .join(" ");
+        if let Err(e) = bot_state
+            .http
+            .create_message(cid)
+            .content(format!(
+                "{} The {} - {} race has been moved to {}.",
+                mentions,
+                game.name_pretty,
+                cat.name_pretty,
+                datetime_to_discord_format(&race.get_occurs()),
+            ))
+            .unwrap()
+            .await
+        {
+            warn!("Error announcing the reschedule of {}: {}", race, e);
+        }
+    }
+}
+
+/// Moves a race to a new time. Returns the reply, the updated race, and - if the race was ACTIVE
+/// and has been pushed back out of the notification window - the confirmation message that
+/// no longer applies.
+async fn _reschedule_race(
+    gid: GuildId,
+    mut args: Arguments<'_>,
+    now: DateTime<Tz>,
+    notify_before_race: Duration,
+    tz: Tz,
+    pool: &SqlitePool,
+) -> (String, Option<Race>, Option<MessageId>) {
+    let syntax_error = format!("Please use the following format: !reschedule <race id> <time> [--force]. For example: `!reschedule 12 6/9/2021 11:00pm`. {}", timezone_note(tz));
+    let id = match args.next().map(|a| a.parse::<i64>()) {
+        Some(Ok(id)) => id,
+        _ => {
+            return (syntax_error.to_owned(), None, None);
+        }
+    };
+    let (time, force) = match args.into_remainder() {
+        Some(rest) => take_force_flag(rest),
+        None => {
+            return (syntax_error.to_owned(), None, None);
+        }
+    };
+    let occurs = match time_parser::parse_time(&time, tz, now) {
+        Ok(dt) => dt,
+        Err(e) => {
+            return (e.to_string(), None, None);
+        }
+    };
+
+    let mut race = match get_race(gid, id, pool).await {
+        Some(r) => r,
+        None => {
+            return ("No valid race found.".to_string(), None, None);
+        }
+    };
+
+    let mut stale_active_message = None;
+    let mut notify_window = Some(notify_before_race);
+    match race.get_state() {
+        RaceState::SCHEDULED => {}
+        RaceState::ACTIVE => {
+            let window = CDuration::from_std(notify_before_race).unwrap();
+            if occurs > now + window {
+                race.set_state(RaceState::SCHEDULED);
+                stale_active_message = race.get_active_message_id();
+                race.active_message_id = None;
+            } else {
+                // people have already signed up, so there's no need to warn about that
+                notify_window = None;
+            }
+        }
+        RaceState::COMPLETED | RaceState::CANCELLED => {
+            return (format!("{} is already over.", race), None, None);
+        }
+    }
+
+    race.set_occurs(occurs);
+    match check_race_time(&race, now, notify_window, pool).await {
+        Err(refusal) => {
+            return (refusal, None, None);
+        }
+        Ok(warnings) if !warnings.is_empty() && !force => {
+            return (
+                format!("{} Add --force to move it anyway.", warnings.join(" ")),
+                None,
+                None,
+            );
+        }
+        Ok(_) => {}
+    }
+    match race.save(pool).await {
+        Ok(()) => (
+            format!(
+                "{} moved to {}.",
+                race,
+                datetime_to_discord_format(&race.get_occurs())
+            ),
+            Some(race),
+            stale_active_message,
+        ),
+        Err(e) => {
+            warn!("Error rescheduling {}: {}", race, e);
+            (describe_race_error(&e, "rescheduling"), None, None)
+        }
+    }
+}
+
+async fn new_series(
+    ctx: &CommandContext,
+    args: Arguments<'_>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let gid = match moderator_guild(ctx, bot_state.clone()).await {
+        Some(g) => g,
+        None => {
+            ctx.reply("You are not authorized to create race series.", &bot_state).await;
+            return;
+        }
+    };
+    let days_ahead = bot_state.get_settings(gid).await.series_days_ahead;
+    let tz = ctx.timezone(pool).await;
+    let now = Local::now().with_timezone(&tz);
+    ctx.reply(_new_series(gid, args, now, days_ahead, tz, pool).await, &bot_state).await;
+}
+
+async fn _new_series(
+    gid: GuildId,
+    mut args: Arguments<'_>,
+    now: DateTime<Tz>,
+    days_ahead: u64,
+    tz: Tz,
+    pool: &SqlitePool,
+) -> String {
+    let syntax_error = format!("Please use the following format: !newseries <game alias> <category alias> <weekly|biweekly|monthly> <time of the first race> [-- notes]. For example: `!newseries alttp nmg weekly 6/12/2021 8:00pm -- for new runners`. {}", timezone_note(tz));
+    let (game_name, cat_name, recurrence) = match (args.next(), args.next(), args.next()) {
+        (Some(g), Some(c), Some(r)) => (g, c, r),
+        _ => {
+            return syntax_error.to_string();
+        }
+    };
+    let recurrence = match Recurrence::from_str(&recurrence.to_ascii_uppercase()) {
+        Ok(r) => r,
+        Err(_) => {
+            return syntax_error.to_string();
+        }
+    };
+    let (time, notes) = match args.into_remainder() {
+        Some(rest) => split_notes(rest),
+        None => {
+            return syntax_error.to_string();
+        }
+    };
+    let first = match time_parser::parse_time(time, tz, now) {
+        Ok(dt) => dt,
+        Err(e) => {
+            return e.to_string();
+        }
+    };
+
+    let game = match get_game(game_name, pool).await {
+        Some(g) => g,
+        None => {
+            return "No game found with that name. Try !listgames".to_string();
+        }
+    };
+    let cat = match get_category(&game, cat_name, pool).await {
+        Some(c) => c,
+        None => {
+            return format!("No matching category found. try !listcategories {}", game.name);
+        }
+    };
+    if cat.retired {
+        return format!(
+            "{} has been retired. try !listcategories {}",
+            cat.name_pretty, game.name
+        );
+    }
+    if recurrence == Recurrence::MONTHLY && first.day() > 28 {
+        return "Monthly series happen in the same week of every month, like on the 2nd Saturday, \
+            so the first race has to be in the first four weeks of its month."
+            .to_string();
+    }
+
+    let mut series = match create_series(gid, &game, &cat, recurrence, first, pool).await {
+        Some(s) => s,
+        None => {
+            return "Unknown error creating the series. Bug Fox about it.".to_string();
+        }
+    };
+    if let Some(n) = notes {
+        series.notes = Some(n.to_string());
+        if let Err(e) = series.save(pool).await {
+            warn!("Error saving notes for {}: {}", series, e);
+        }
+    }
+
+    format!(
+        "{} created: {} - {} {}, starting {}. Races get posted {} days ahead of time.",
+        series,
+        game.name_pretty,
+        cat.name_pretty,
+        describe_recurrence(&series),
+        datetime_to_discord_format(&first),
+        days_ahead
+    )
+}
+
+/// e.g. "every other week" or "on the 2nd Saturday of every month"
+fn describe_recurrence(series: &RaceSeries) -> String {
+    match series.get_recurrence() {
+        Recurrence::WEEKLY => "every week".to_string(),
+        Recurrence::BIWEEKLY => "every other week".to_string(),
+        Recurrence::MONTHLY => {
+            let first = series.get_first_date();
+            let week = ["1st", "2nd", "3rd", "4th", "5th"][first.day0() as usize / 7];
+            format!("on the {} {} of every month", week, first.format("%A"))
+        }
+    }
+}
+
+async fn list_series(ctx: &CommandContext, bot_state: Arc<BotState>, pool: &SqlitePool) {
+    let gid = match ctx.guild_id {
+        Some(g) => g,
+        None => {
+            return;
+        }
+    };
+    let now = Local::now().with_timezone(&Eastern);
+    ctx.reply(_list_series(gid, now, pool).await, &bot_state).await;
+}
+
+async fn _list_series(gid: GuildId, now: DateTime<Tz>, pool: &SqlitePool) -> String {
+    let series = get_active_series(gid, pool).await;
+    if series.is_empty() {
+        return "There are no race series running.".to_string();
+    }
+    let mut msg_parts = vec!["Race series:".to_string()];
+    for s in series {
+        let (game, cat) = match (
+            Game::get_by_id(s.game_id, pool).await,
+            Category::get_by_id(s.category_id, pool).await,
+        ) {
+            (Some(g), Some(c)) => (g, c),
+            _ => {
+                warn!("Can't find the game or category for {}", s);
+                continue;
+            }
+        };
+        let next = match s.next_occurrence(now) {
+            Some(t) => format!(" Next race: {}", datetime_to_discord_format(&t)),
+            None => "".to_string(),
+        };
+        let notes = match &s.notes {
+            Some(n) => format!(" Notes: {}", n),
+            None => "".to_string(),
+        };
+        msg_parts.push(format!(
+            "{}: {} - {} {}.{}{}",
+            s,
+            game.name_pretty,
+            cat.name_pretty,
+            describe_recurrence(&s),
+            next,
+            notes
+        ));
+    }
+    msg_parts.join("\n")
+}
+
+async fn end_series(
+    ctx: &CommandContext,
+    args: Arguments<'_>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    let gid = match moderator_guild(ctx, bot_state.clone()).await {
+        Some(g) => g,
+        None => {
+            ctx.reply("You are not authorized to end race series.", &bot_state).await;
+            return;
+        }
+    };
+    ctx.reply(_end_series(gid, args, pool).await, &bot_state).await;
+}
+
+/// Stops a series from creating any more races. Races it already created are left alone.
+async fn _end_series(gid: GuildId, mut args: Arguments<'_>, pool: &SqlitePool) -> String {
+    let id = match args.next().map(|a| a.parse::<i64>()) {
+        Some(Ok(id)) => id,
+        _ => {
+            return "Please use the following format: !endseries <series id>".to_string();
+        }
+    };
+    let mut series = match get_series(gid, id, pool).await {
+        Some(s) => s,
+        None => {
+            return "No valid series found.".to_string();
+        }
+    };
+    if !series.active {
+        return format!("{} has already ended.", series);
+    }
+    series.active = false;
+    match series.save(pool).await {
+        Ok(()) => format!(
+            "{} ended. Races it already scheduled will still happen; use !cancelrace to cancel them.",
+            series
+        ),
+        Err(e) => {
+            warn!("Error ending {}: {}", series, e);
+            "Unknown error ending the series. Bug Fox about it.".to_string()
+        }
+    }
+}
+
+/// Creates and posts the races from the guild's series that are coming up soon
+async fn schedule_series(gid: GuildId, bot_state: Arc<BotState>, pool: &SqlitePool) {
+    let lookahead = bot_state.get_settings(gid).await.series_lookahead();
+    let now = Local::now().with_timezone(&Eastern);
+    for race in _schedule_series(gid, now, lookahead, pool).await {
+        let (game, cat) = match (
+            Game::get_by_id(race.game_id, pool).await,
+            Category::get_by_id(race.category_id, pool).await,
+        ) {
+            (Some(g), Some(c)) => (g, c),
+            _ => {
+                warn!("Can't find the game or category for {}", race);
+                continue;
+            }
+        };
+        let content =
+            scheduling_message_content(gid, &race, &game, &cat, bot_state.clone(), pool).await;
+        post_scheduling_message(gid, race, content, bot_state.clone(), pool).await;
+    }
+}
+
+/// Creates the races from each of the guild's series that start within `lookahead` of `now`,
+/// skipping any that were already created. Returns the new races, which still need to be posted.
+async fn _schedule_series(
+    gid: GuildId,
+    now: DateTime<Tz>,
+    lookahead: Duration,
+    pool: &SqlitePool,
+) -> Vec<Race> {
+    let until = now + CDuration::from_std(lookahead).unwrap();
+    let mut created = vec![];
+    for mut series in get_active_series(gid, pool).await {
+        let after = match series.scheduled_through {
+            Some(ts) if ts > now.timestamp() => Utc.timestamp(ts, 0).with_timezone(&Eastern),
+            _ => now,
+        };
+        let times = series.occurrences_between(after, until);
+        if times.is_empty() {
+            continue;
+        }
+        let (game, cat) = match (
+            Game::get_by_id(series.game_id, pool).await,
+            Category::get_by_id(series.category_id, pool).await,
+        ) {
+            (Some(g), Some(c)) => (g, c),
+            _ => {
+                warn!("Can't find the game or category for {}", series);
+                continue;
+            }
+        };
+        for occurs in times {
+            match create_race(gid, &game, &cat, occurs, pool).await {
+                Ok(mut race) => {
+                    race.series_id = Some(series.id);
+                    race.notes = series.notes.clone();
+                    if let Err(e) = race.save(pool).await {
+                        warn!("Error saving {} for {}: {}", race, series, e);
+                    }
+                    info!("Created {} for {}", race, series);
+                    created.push(race);
+                }
+                Err(e) => {
+                    // probably a moderator already scheduled this one by hand
+                    warn!("Couldn't create the race at {} for {}: {}", occurs, series, e);
+                }
+            }
+            series.scheduled_through = Some(occurs.timestamp());
+        }
+        if let Err(e) = series.save(pool).await {
+            warn!("Error saving {}: {}", series, e);
+        }
+    }
+    created
+}
+
+fn is_unique_violation(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::Database(db) => db.message().contains("UNIQUE constraint failed"),
+        _ => false,
+    }
+}
+
+fn is_foreign_key_violation(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::Database(db) => db.message().contains("FOREIGN KEY constraint failed"),
+        _ => false,
+    }
+}
+
+/// Gets a race by id, as long as it belongs to the given guild
+async fn get_race(gid: GuildId, id: i64, pool: &SqlitePool) -> Option<Race> {
+    Race::get_by_id(id, pool)
+        .await
+        .filter(|r| r.get_guild_id() == Some(gid))
+}
+
+/// Gets the guild's currently active race. If more than one is found, returns None
+// this is just to make the types line up more easily but it might suck?
+async fn get_active_race(gid: GuildId, pool: &SqlitePool) -> Option<Race> {
+    let mut races = get_active_races(gid, pool).await;
+    let ret = races.pop();
+    if !races.is_empty() {
+        info!("Found multiple active races");
+        return None;
+    }
+    ret
+}
+
+async fn get_race_by_scheduling_message_id(mid: MessageId, pool: &SqlitePool) -> Option<Race> {
+    let q = sqlx::query_as::<_, Race>("SELECT * FROM race WHERE scheduling_message_id = ?")
+        .bind(mid.to_string());
+    match q.fetch_optional(pool).await {
+        Ok(r) => r,
+        Err(e) => {
+            warn!("Error fetching race by scheduling message id: {:?}", e);
+            None
+        }
+    }
+}
+
+async fn get_race_by_active_message_id(mid: MessageId, pool: &SqlitePool) -> Option<Race> {
+    let q = sqlx::query_as::<_, Race>("SELECT * FROM race WHERE active_message_id = ?")
+        .bind(mid.to_string());
+    match q.fetch_optional(pool).await {
+        Ok(r) => r,
+        Err(e) => {
+            warn!("Error fetching race by active message id: {:?}", e);
+            None
+        }
+    }
+}
+
+/// Records that someone has signed up for (or readied up for, or withdrawn from) a race
+async fn set_entrant_status(
+    race_id: i64,
+    user_id: UserId,
+    kind: EntrantKind,
+    status: EntrantStatus,
+    pool: &SqlitePool,
+) {
+    let now = Utc::now().timestamp();
+    let q = sqlx::query(
+        "INSERT INTO race_entrant (race_id, user_id, kind, status, created, updated) \
+        VALUES (?, ?, ?, ?, ?, ?) \
+        ON CONFLICT (race_id, user_id, kind) DO UPDATE SET status = excluded.status, updated = excluded.updated, \
+        assigned = CASE WHEN excluded.status = ? THEN FALSE ELSE assigned END",
+    )
+    .bind(race_id)
+    .bind(user_id.to_string())
+    .bind(kind.to_string())
+    .bind(status.to_string())
+    .bind(now)
+    .bind(now)
+    .bind(EntrantStatus::WITHDRAWN.to_string());
+    if let Err(e) = q.execute(pool).await {
+        warn!(
+            "Error setting {} to {} {} for race {}: {:?}",
+            user_id, status, kind, race_id, e
+        );
+    }
+}
+
+async fn get_entrant(
+    race_id: i64,
+    user_id: UserId,
+    kind: EntrantKind,
+    pool: &SqlitePool,
+) -> Option<RaceEntrant> {
+    let q = sqlx::query_as::<_, RaceEntrant>(
+        "SELECT * FROM race_entrant WHERE race_id = ? AND user_id = ? AND kind = ?",
+    )
+    .bind(race_id)
+    .bind(user_id.to_string())
+    .bind(kind.to_string());
+    match q.fetch_optional(pool).await {
+        Ok(e) => e,
+        Err(e) => {
+            warn!("Error fetching entrant: {:?}", e);
+            None
+        }
+    }
+}
+
+/// Gets everything the user has signed up for in this race, including things they withdrew from
+async fn get_user_entrants(race_id: i64, user_id: UserId, pool: &SqlitePool) -> Vec<RaceEntrant> {
+    let q = sqlx::query_as::<_, RaceEntrant>(
+        "SELECT * FROM race_entrant WHERE race_id = ? AND user_id = ?",
+    )
+    .bind(race_id)
+    .bind(user_id.to_string());
+    match q.fetch_all(pool).await {
+        Ok(entrants) => entrants,
+        Err(e) => {
+            warn!("Error fetching entrants: {:?}", e);
+            vec![]
+        }
+    }
+}
+
+/// Gets everyone who has ever signed up for the race as `kind`, including those who withdrew
+async fn get_entrants(race_id: i64, kind: EntrantKind, pool: &SqlitePool) -> Vec<RaceEntrant> {
+    let q = sqlx::query_as::<_, RaceEntrant>(
+        "SELECT * FROM race_entrant WHERE race_id = ? AND kind = ? ORDER BY created",
+    )
+    .bind(race_id)
+    .bind(kind.to_string());
+    match q.fetch_all(pool).await {
+        Ok(entrants) => entrants,
+        Err(e) => {
+            warn!("Error fetching entrants: {:?}", e);
+            vec![]
+        }
+    }
+}
+
+/// Records a finish time, or a forfeit if `finish_time` is None, and re-ranks everyone in the race
+async fn set_result(
+    race_id: i64,
+    user_id: UserId,
+    finish_time: Option<i64>,
+    comment: Option<&str>,
+    pool: &SqlitePool,
+) -> Result<(), sqlx::Error> {
+    let now = Utc::now().timestamp();
+    sqlx::query(
+        "INSERT INTO race_result (race_id, user_id, finish_time, forfeit, comment, created, updated) \
+        VALUES (?, ?, ?, ?, ?, ?, ?) \
+        ON CONFLICT (race_id, user_id) DO UPDATE SET finish_time = excluded.finish_time, \
+        forfeit = excluded.forfeit, comment = excluded.comment, updated = excluded.updated",
+    )
+    .bind(race_id)
+    .bind(user_id.to_string())
+    .bind(finish_time)
+    .bind(finish_time.is_none())
+    .bind(comment)
+    .bind(now)
+    .bind(now)
+    .execute(pool)
+    .await?;
+    update_placements(race_id, pool).await
+}
+
+async fn clear_result(race_id: i64, user_id: UserId, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM race_result WHERE race_id = ? AND user_id = ?")
+        .bind(race_id)
+        .bind(user_id.to_string())
+        .execute(pool)
+        .await?;
+    update_placements(race_id, pool).await
+}
+
+/// Ranks finishers by time. Ties share a placement, and forfeits don't get one.
+async fn update_placements(race_id: i64, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE race_result SET placement = CASE WHEN forfeit THEN NULL ELSE \
+        (SELECT COUNT(*) + 1 FROM race_result AS faster WHERE faster.race_id = race_result.race_id \
+        AND NOT faster.forfeit AND faster.finish_time < race_result.finish_time) END \
+        WHERE race_id = ?",
+    )
+    .bind(race_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+async fn get_result(race_id: i64, user_id: UserId, pool: &SqlitePool) -> Option<RaceResult> {
+    let q = sqlx::query_as::<_, RaceResult>(
+        "SELECT * FROM race_result WHERE race_id = ? AND user_id = ?",
+    )
+    .bind(race_id)
+    .bind(user_id.to_string());
+    match q.fetch_optional(pool).await {
+        Ok(r) => r,
+        Err(e) => {
+            warn!("Error fetching result: {:?}", e);
+            None
+        }
+    }
+}
+
+/// Everyone's results for the race, finishers first
+async fn get_results(race_id: i64, pool: &SqlitePool) -> Vec<RaceResult> {
+    let q = sqlx::query_as::<_, RaceResult>(
+        "SELECT * FROM race_result WHERE race_id = ? ORDER BY forfeit, finish_time, created",
+    )
+    .bind(race_id);
+    match q.fetch_all(pool).await {
+        Ok(results) => results,
+        Err(e) => {
+            warn!("Error fetching results: {:?}", e);
+            vec![]
+        }
+    }
+}
+
+/// Everyone who has a result in a completed race of the category, fastest PB first. People who
+/// never finished go last.
+async fn get_leaderboard(
+    gid: GuildId,
+    game_id: i64,
+    category_id: i64,
+    pool: &SqlitePool,
+) -> Vec<LeaderboardEntry> {
+    let q = sqlx::query_as::<_, LeaderboardEntry>(
+        "SELECT race_result.user_id AS user_id, MIN(race_result.finish_time) AS pb, \
+        COUNT(*) AS races, SUM(CASE WHEN race_result.placement = 1 THEN 1 ELSE 0 END) AS wins, \
+        AVG(race_result.placement) AS avg_placement \
+        FROM race_result JOIN race ON race.id = race_result.race_id \
+        WHERE race.guild_id = ? AND race.game_id = ? AND race.category_id = ? AND race.state = ? \
+        GROUP BY race_result.user_id ORDER BY pb IS NULL, pb, races DESC, user_id",
+    )
+    .bind(gid.to_string())
+    .bind(game_id)
+    .bind(category_id)
+    .bind(RaceState::COMPLETED.to_string());
+    match q.fetch_all(pool).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("Error fetching leaderboard: {:?}", e);
+            vec![]
+        }
+    }
+}
+
+/// The user's results in completed races of the category, newest first
+async fn get_user_results(
+    gid: GuildId,
+    game_id: i64,
+    category_id: i64,
+    user_id: UserId,
+    pool: &SqlitePool,
+) -> Vec<(RaceResult, Race)> {
+    let q = sqlx::query_as::<_, RaceResult>(
+        "SELECT race_result.* FROM race_result JOIN race ON race.id = race_result.race_id \
+        WHERE race.guild_id = ? AND race.game_id = ? AND race.category_id = ? AND race.state = ? \
+        AND race_result.user_id = ? ORDER BY race.occurs DESC",
+    )
+    .bind(gid.to_string())
+    .bind(game_id)
+    .bind(category_id)
+    .bind(RaceState::COMPLETED.to_string())
+    .bind(user_id.to_string());
+    let results = match q.fetch_all(pool).await {
+        Ok(results) => results,
+        Err(e) => {
+            warn!("Error fetching results for {}: {:?}", user_id, e);
+            return vec![];
+        }
+    };
+    let mut history = vec![];
+    for result in results {
+        if let Some(race) = Race::get_by_id(result.race_id, pool).await {
+            history.push((result, race));
+        }
+    }
+    history
+}
+
+/// Every rating change in the category, oldest first
+async fn get_rating_changes(gid: GuildId, category_id: i64, pool: &SqlitePool) -> Vec<RatingChange> {
+    let q = sqlx::query_as::<_, RatingChange>(
+        "SELECT * FROM rating_change WHERE guild_id = ? AND category_id = ? ORDER BY id",
+    )
+    .bind(gid.to_string())
+    .bind(category_id);
+    match q.fetch_all(pool).await {
+        Ok(changes) => changes,
+        Err(e) => {
+            warn!("Error fetching rating changes: {:?}", e);
+            vec![]
+        }
+    }
+}
+
+/// Every rating change the user has had in any category, oldest first
+async fn get_user_rating_changes(gid: GuildId, user_id: UserId, pool: &SqlitePool) -> Vec<RatingChange> {
+    let q = sqlx::query_as::<_, RatingChange>(
+        "SELECT * FROM rating_change WHERE guild_id = ? AND user_id = ? ORDER BY id",
+    )
+    .bind(gid.to_string())
+    .bind(user_id.to_string());
+    match q.fetch_all(pool).await {
+        Ok(changes) => changes,
+        Err(e) => {
+            warn!("Error fetching rating changes for {}: {:?}", user_id, e);
+            vec![]
+        }
+    }
+}
+
+/// Gets the people a moderator has picked to fill `kind` for the race
+async fn get_assigned_crew(race_id: i64, kind: EntrantKind, pool: &SqlitePool) -> Vec<RaceEntrant> {
+    get_entrants(race_id, kind, pool)
+        .await
+        .into_iter()
+        .filter(|e| e.assigned)
+        .collect()
+}
+
+/// Gets everything the user is signed up for in races that haven't happened yet or are happening
+/// right now
+async fn get_current_signups(gid: GuildId, user_id: UserId, pool: &SqlitePool) -> Vec<RaceEntrant> {
+    let q = sqlx::query_as::<_, RaceEntrant>(
+        "SELECT race_entrant.* FROM race_entrant JOIN race ON race.id = race_entrant.race_id \
+        WHERE race.guild_id = ? AND race_entrant.user_id = ? AND race_entrant.status != ? \
+        AND race.state IN (?, ?)",
+    )
+    .bind(gid.to_string())
+    .bind(user_id.to_string())
+    .bind(EntrantStatus::WITHDRAWN.to_string())
+    .bind(RaceState::SCHEDULED.to_string())
+    .bind(RaceState::ACTIVE.to_string());
+    match q.fetch_all(pool).await {
+        Ok(entrants) => entrants,
+        Err(e) => {
+            warn!("Error fetching signups for {}: {:?}", user_id, e);
+            vec![]
+        }
+    }
+}
+
+async fn get_setting(name: &str, pool: &SqlitePool) -> Option<String> {
+    let q = sqlx::query("SELECT value FROM setting WHERE name = ?").bind(name);
+    match q.fetch_optional(pool).await {
+        Ok(row) => row.map(|r| r.get::<String, &str>("value")),
+        Err(e) => {
+            warn!("Error fetching setting {}: {:?}", name, e);
+            None
+        }
+    }
+}
+
+async fn set_setting(name: &str, value: &str, pool: &SqlitePool) {
+    let q = sqlx::query(
+        "INSERT INTO setting (name, value) VALUES (?, ?) \
+        ON CONFLICT (name) DO UPDATE SET value = excluded.value",
+    )
+    .bind(name)
+    .bind(value);
+    if let Err(e) = q.execute(pool).await {
+        warn!("Error saving setting {}: {:?}", name, e);
+    }
+}
+
+/// Builds the guild's settings from whatever it has saved on top of the config file defaults.
+/// Saved values that are no longer valid are skipped.
+async fn load_guild_settings(gid: GuildId, config: &Config, pool: &SqlitePool) -> GuildSettings {
+    let mut settings = GuildSettings::from_config(config);
+    let q = sqlx::query("SELECT name, value FROM guild_settings WHERE guild_id = ?")
+        .bind(gid.to_string());
+    match q.fetch_all(pool).await {
+        Ok(rows) => {
+            for row in rows {
+                let name = row.get::<String, &str>("name");
+                let value = row.get::<String, &str>("value");
+                if let Err(e) = settings.set(&name, &value) {
+                    warn!("Ignoring saved setting {} for guild {}: {}", name, gid, e);
+                }
+            }
+        }
+        Err(e) => {
+            warn!("Error fetching settings for guild {}: {:?}", gid, e);
+        }
+    }
+    settings
+}
+
+async fn save_guild_setting(
+    gid: GuildId,
+    name: &str,
+    value: &str,
+    pool: &SqlitePool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO guild_settings (guild_id, name, value) VALUES (?, ?, ?) \
+        ON CONFLICT (guild_id, name) DO UPDATE SET value = excluded.value",
+    )
+    .bind(gid.to_string())
+    .bind(name)
+    .bind(value)
+    .execute(pool)
+    .await
+    .map(|_| ())
+}
+
+/// The timezone someone types times in. Eastern unless they've picked one with !timezone
+async fn get_user_timezone(user_id: UserId, pool: &SqlitePool) -> Tz {
+    let q = sqlx::query("SELECT timezone FROM user_timezone WHERE user_id = ?")
+        .bind(user_id.to_string());
+    match q.fetch_optional(pool).await {
+        Ok(Some(row)) => {
+            let name = row.get::<String, &str>("timezone");
+            match name.parse::<Tz>() {
+                Ok(tz) => tz,
+                Err(e) => {
+                    warn!("Error parsing timezone {} for {}: {}", name, user_id, e);
+                    Eastern
+                }
+            }
+        }
+        Ok(None) => Eastern,
+        Err(e) => {
+            warn!("Error fetching timezone for {}: {}", user_id, e);
+            Eastern
+        }
+    }
+}
+
+async fn set_user_timezone(user_id: UserId, tz: Tz, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO user_timezone (user_id, timezone) VALUES (?, ?) \
+        ON CONFLICT (user_id) DO UPDATE SET timezone = excluded.timezone",
+    )
+    .bind(user_id.to_string())
+    .bind(tz.name())
+    .execute(pool)
+    .await
+    .map(|_| ())
+}
+
+/// The discord user who linked this racetime.gg account. Only the full name (with its
+/// discriminator) counts, since plain names aren't unique on racetime.gg.
+async fn get_racetime_link(full_name: &str, pool: &SqlitePool) -> Option<UserId> {
+    let q = sqlx::query("SELECT user_id FROM racetime_user WHERE racetime_name = ? COLLATE NOCASE")
+        .bind(full_name);
+    match q.fetch_optional(pool).await {
+        Ok(Some(row)) => row.get::<String, &str>("user_id").parse::<u64>().ok().map(UserId),
+        Ok(None) => None,
+        Err(e) => {
+            warn!("Error fetching racetime.gg link for {}: {}", full_name, e);
+            None
+        }
+    }
+}
+
+async fn get_racetime_name(user_id: UserId, pool: &SqlitePool) -> Option<String> {
+    let q = sqlx::query("SELECT racetime_name FROM racetime_user WHERE user_id = ?")
+        .bind(user_id.to_string());
+    match q.fetch_optional(pool).await {
+        Ok(row) => row.map(|r| r.get::<String, &str>("racetime_name")),
+        Err(e) => {
+            warn!("Error fetching racetime.gg name for {}: {}", user_id, e);
+            None
+        }
+    }
+}
+
+async fn set_racetime_name(user_id: UserId, name: &str, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO racetime_user (user_id, racetime_name) VALUES (?, ?) \
+        ON CONFLICT (user_id) DO UPDATE SET racetime_name = excluded.racetime_name",
+    )
+    .bind(user_id.to_string())
+    .bind(name)
+    .execute(pool)
+    .await
+    .map(|_| ())
+}
+
+/// Links a racetime.gg account to someone, taking it away from whoever had it before
+async fn relink_racetime_name(user_id: UserId, name: &str, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM racetime_user WHERE racetime_name = ? COLLATE NOCASE")
+        .bind(name)
+        .execute(&mut tx)
+        .await?;
+    sqlx::query(
+        "INSERT INTO racetime_user (user_id, racetime_name) VALUES (?, ?) \
+        ON CONFLICT (user_id) DO UPDATE SET racetime_name = excluded.racetime_name",
+    )
+    .bind(user_id.to_string())
+    .bind(name)
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await
+}
+
+/// Gets all of the guild's currently active races.
+async fn get_active_races(gid: GuildId, pool: &SqlitePool) -> Vec<Race> {
+    let state = RaceState::ACTIVE.to_string();
+    let q = sqlx::query_as::<_, Race>("SELECT * FROM race WHERE guild_id = ? AND state = ?")
+        .bind(gid.to_string())
+        .bind(state);
+    let rows = q.fetch(pool);
+
+    rows.map(|r| r.unwrap()).collect::<Vec<Race>>().await
+}
+
+// TODO: This creates a race with null message_id and state SCHEDULED, always. Is that bad?
+async fn create_race(
+    gid: GuildId,
+    game: &Game,
+    category: &Category,
+    occurs: DateTime<Tz>,
+    pool: &SqlitePool,
+) -> Result<Race, sqlx::Error> {
+    let ts = occurs.timestamp();
+    let state = RaceState::SCHEDULED.to_string();
+    let q = sqlx::query(
+        "INSERT INTO race (guild_id, game_id, category_id, occurs, state, signup_buttons) VALUES (?, ?, ?, ?, ?, TRUE); \
+        SELECT last_insert_rowid() as rowid;").bind(gid.to_string()).bind(game.id).bind(category.id).bind(ts).bind(state);
+    let row = q.fetch_one(pool).await?;
+    Ok(Race::new(
+        row.get::<i64, &str>("rowid"),
+        gid,
+        game.id,
+        category.id,
+        occurs,
+    ))
+}
+
+async fn get_series(gid: GuildId, id: i64, pool: &SqlitePool) -> Option<RaceSeries> {
+    RaceSeries::get_by_id(id, pool)
+        .await
+        .filter(|s| s.get_guild_id() == Some(gid))
+}
+
+async fn get_active_series(gid: GuildId, pool: &SqlitePool) -> Vec<RaceSeries> {
+    let q = sqlx::query_as::<_, RaceSeries>(
+        "SELECT * FROM race_series WHERE guild_id = ? AND active = TRUE ORDER BY id",
+    )
+    .bind(gid.to_string());
+    match q.fetch_all(pool).await {
+        Ok(series) => series,
+        Err(e) => {
+            warn!("Error fetching race series: {:?}", e);
+            vec![]
+        }
+    }
+}
+
+async fn create_series(
+    gid: GuildId,
+    game: &Game,
+    category: &Category,
+    recurrence: Recurrence,
+    first: DateTime<Tz>,
+    pool: &SqlitePool,
+) -> Option<RaceSeries> {
+    let mut series = RaceSeries::new(0, gid, game.id, category.id, recurrence, first);
+    let q = sqlx::query(
+        "INSERT INTO race_series (guild_id, game_id, category_id, recurrence, first_date, time_of_day, timezone) \
+        VALUES (?, ?, ?, ?, ?, ?, ?); SELECT last_insert_rowid() as rowid;",
+    )
+    .bind(&series.guild_id)
+    .bind(series.game_id)
+    .bind(series.category_id)
+    .bind(&series.recurrence)
+    .bind(&series.first_date)
+    .bind(&series.time_of_day)
+    .bind(&series.timezone);
+    match q.fetch_one(pool).await {
+        Ok(row) => {
+            series.id = row.get::<i64, &str>("rowid");
+            Some(series)
+        }
+        Err(e) => {
+            error!("error creating series: {:?}", e);
+            None
+        }
+    }
+}
+
+async fn list_games(ctx: &CommandContext, bot_state: Arc<BotState>, pool: &SqlitePool) {
+    let games = get_games(pool).await;
+    let mut msg_parts = vec!["Available games:".to_owned()];
+    msg_parts.extend(
+        games
+            .iter()
+            .map(|g| format!("* {} ({})", g.name_pretty, g.name)),
+    );
+
+    let contents = msg_parts.join("\n");
+    // TODO: actually check content length if we get enough games
+    ctx.reply(contents, &bot_state).await;
+}
+
+async fn manage_games(
+    ctx: &CommandContext,
+    command: &str,
+    args: Arguments<'_>,
+    bot_state: Arc<BotState>,
+    pool: &SqlitePool,
+) {
+    if !is_moderator(ctx, bot_state.clone()).await {
+        ctx.reply("You are not authorized to manage games and categories.", &bot_state).await;
+        return;
+    }
+
+    let contents = match command {
+        "addgame" => _add_game(args, pool).await,
+        "addcategory" => _add_category(args, pool).await,
+        "editgame" => _edit_game(args, pool).await,
+        "editcategory" => _edit_category(args, pool).await,
+        "retirecategory" => _retire_category(args, pool).await,
+        _ => {
+            warn!("manage_games called with unexpected command {}", command);
+            return;
+        }
+    };
+    ctx.reply(contents, &bot_state).await;
+}
+
+/// aliases are what people type in commands, so keep them to one easily-typed word
+fn is_valid_alias(alias: &str) -> bool {
+    !alias.is_empty()
+        && alias
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+const INVALID_ALIAS: &str = "Aliases may only contain lowercase letters, numbers, and underscores.";
+
+/// Returns an error message if `name` or `name_pretty` would collide with a game other than
+/// `existing_id`
+async fn check_game_names(
+    name: &str,
+    name_pretty: &str,
+    existing_id: Option<i64>,
+    pool: &SqlitePool,
+) -> Option<String> {
+    if !is_valid_alias(name) {
+        return Some(INVALID_ALIAS.to_string());
+    }
+    if name_pretty.is_empty() {
+        return Some("The game name can't be empty.".to_string());
+    }
+    let q = sqlx::query_as::<_, Game>(
+        "SELECT id, name, name_pretty FROM game WHERE name = ? OR name_pretty = ?",
+    )
+    .bind(name)
+    .bind(name_pretty);
+    // the game being renamed can match too, so look past it
+    match q.fetch_all(pool).await {
+        Ok(games) => games
+            .iter()
+            .find(|g| Some(g.id) != existing_id)
+            .map(|g| format!("That conflicts with the existing game {}", g)),
+        Err(e) => {
+            warn!("Error checking game names: {:?}", e);
+            Some("Error checking existing games".to_string())
+        }
+    }
+}
+
+/// Category aliases and names are unique across *all* games, not just within one game
+async fn check_category_names(
+    name: &str,
+    name_pretty: &str,
+    existing_id: Option<i64>,
+    pool: &SqlitePool,
+) -> Option<String> {
+    if !is_valid_alias(name) {
+        return Some(INVALID_ALIAS.to_string());
+    }
+    if name_pretty.is_empty() {
+        return Some("The category name can't be empty.".to_string());
+    }
+    let q = sqlx::query_as::<_, Category>(
+        "SELECT * FROM category WHERE name = ? OR name_pretty = ?",
+    )
+    .bind(name)
+    .bind(name_pretty);
+    match q.fetch_all(pool).await {
+        Ok(cats) => cats
+            .iter()
+            .find(|c| Some(c.id) != existing_id)
+            .map(|c| format!("That conflicts with the existing category {}", c)),
+        Err(e) => {
+            warn!("Error checking category names: {:?}", e);
+            Some("Error checking existing categories".to_string())
+        }
+    }
+}
+
+async fn _add_game(mut args: Arguments<'_>, pool: &SqlitePool) -> String {
+    let syntax_error = "Please use the following format: !addgame <game alias> <game name>. For example: `!addgame ffx Final Fantasy X`";
+    let name = match args.next() {
+        Some(n) => n,
+        None => {
+            return syntax_error.to_owned();
+        }
+    };
+    let name_pretty = match args.into_remainder().map(str::trim) {
+        Some(n) if !n.is_empty() => n,
+        _ => {
+            return syntax_error.to_owned();
+        }
+    };
+
+    if let Some(err) = check_game_names(name, name_pretty, None, pool).await {
+        return err;
+    }
+
+    match create_game(name, name_pretty, pool).await {
+        Some(g) => format!("Game created: {}", g),
+        None => "Unknown error creating the game. Bug Fox about it.".to_owned(),
+    }
+}
+
+async fn _add_category(mut args: Arguments<'_>, pool: &SqlitePool) -> String {
+    let syntax_error = "Please use the following format: !addcategory <game alias> <category alias> <category name>. For example: `!addcategory ffx any_pc Any% PC`";
+    let game_name = match args.next() {
+        Some(g) => g,
+        None => {
+            return syntax_error.to_owned();
+        }
+    };
+    let name = match args.next() {
+        Some(n) => n,
+        None => {
+            return syntax_error.to_owned();
+        }
+    };
+    let name_pretty = match args.into_remainder().map(str::trim) {
+        Some(n) if !n.is_empty() => n,
+        _ => {
+            return syntax_error.to_owned();
+        }
+    };
+
+    let game = match get_game(game_name, pool).await {
+        Some(g) => g,
+        None => {
+            return "No game found with that name. Try !listgames".to_owned();
+        }
+    };
+
+    if let Some(err) = check_category_names(name, name_pretty, None, pool).await {
+        return err;
+    }
+
+    match create_category(&game, name, name_pretty, pool).await {
+        Some(c) => format!("Category created for {}: {}", game.name_pretty, c),
+        None => "Unknown error creating the category. Bug Fox about it.".to_owned(),
+    }
+}
+
+async fn _edit_game(mut args: Arguments<'_>, pool: &SqlitePool) -> String {
+    let syntax_error = "Please use the following format: !editgame <game alias> <alias|name> <new value>. For example: `!editgame ffx name Final Fantasy X HD`";
+    let game_name = match args.next() {
+        Some(g) => g,
+        None => {
+            return syntax_error.to_owned();
+        }
+    };
+    let field = match args.next() {
+        Some(f) => f,
+        None => {
+            return syntax_error.to_owned();
+        }
+    };
+    let value = match args.into_remainder().map(str::trim) {
+        Some(v) if !v.is_empty() => v,
+        _ => {
+            return syntax_error.to_owned();
+        }
+    };
+
+    let mut game = match get_game(game_name, pool).await {
+        Some(g) => g,
+        None => {
+            return "No game found with that name. Try !listgames".to_owned();
+        }
+    };
+    match field {
+        "alias" => {
+            game.name = value.to_string();
+        }
+        "name" => {
+            game.name_pretty = value.to_string();
+        }
+        _ => {
+            return syntax_error.to_owned();
+        }
+    }
+
+    if let Some(err) = check_game_names(&game.name, &game.name_pretty, Some(game.id), pool).await {
+        return err;
+    }
+
+    match game.save(pool).await {
+        Ok(()) => format!("Game updated: {}", game),
+        Err(e) => {
+            warn!("Error updating game: {:?}", e);
+            "Unknown error updating the game. Bug Fox about it.".to_owned()
+        }
+    }
+}
+
+async fn _edit_category(mut args: Arguments<'_>, pool: &SqlitePool) -> String {
+    let syntax_error = "Please use the following format: !editcategory <game alias> <category alias> <alias|name|racetime|goal|expected|max> <new value>. For example: `!editcategory alttp nmg name Any% No Major Glitches`. `racetime` is the racetime.gg category races get rooms in and `goal` is the goal for those rooms. `expected` is how long races usually take and `max` is how long they can go before they get ended automatically, like `4h30m`. Set any of those to `none` to clear it.";
+    let game_name = match args.next() {
+        Some(g) => g,
+        None => {
+            return syntax_error.to_owned();
+        }
+    };
+    let cat_name = match args.next() {
+        Some(c) => c,
+        None => {
+            return syntax_error.to_owned();
+        }
+    };
+    let field = match args.next() {
+        Some(f) => f,
+        None => {
+            return syntax_error.to_owned();
+        }
+    };
+    let value = match args.into_remainder().map(str::trim) {
+        Some(v) if !v.is_empty() => v,
+        _ => {
+            return syntax_error.to_owned();
+        }
+    };
+
+    let game = match get_game(game_name, pool).await {
+        Some(g) => g,
+        None => {
+            return "No game found with that name. Try !listgames".to_owned();
+        }
+    };
+    let mut cat = match get_category(&game, cat_name, pool).await {
+        Some(c) => c,
+        None => {
+            return format!("No matching category found. try !listcategories {}", game.name);
+        }
+    };
+    match field {
+        "alias" => {
+            cat.name = value.to_string();
+        }
+        "name" => {
+            cat.name_pretty = value.to_string();
+        }
+        "racetime" => {
+            cat.racetime_category = optional_value(value);
+        }
+        "goal" => {
+            cat.racetime_goal = optional_value(value);
+        }
+        "expected" | "max" => {
+            let duration = match optional_value(value) {
+                Some(v) => match parse_finish_time(&v) {
+                    Some(d) if d > 0 => Some(d),
+                    _ => {
+                        return format!(
+                            "Couldn't understand the duration `{}`. Try something like 4h30m or 4:30:00.",
+                            v
+                        );
+                    }
+                },
+                None => None,
+            };
+            if field == "expected" {
+                cat.expected_duration = duration;
+            } else {
+                cat.max_duration = duration;
+            }
+            if let (Some(expected), Some(max)) = (cat.expected_duration, cat.max_duration) {
+                if max < expected {
+                    return format!(
+                        "Races can't be expected to take {} if they get ended after {}.",
+                        format_finish_time(expected),
+                        format_finish_time(max)
+                    );
+                }
+            }
+        }
+        _ => {
+            return syntax_error.to_owned();
+        }
+    }
+
+    if let Some(err) = check_category_names(&cat.name, &cat.name_pretty, Some(cat.id), pool).await {
+        return err;
+    }
+
+    match cat.save(pool).await {
+        Ok(()) => match (field, &cat.racetime_category) {
+            ("racetime" | "goal", Some(rc)) => format!(
+                "Category updated: {}. Its races get rooms in the {} category on racetime.gg, with the goal {}.",
+                cat,
+                rc,
+                cat.racetime_goal.as_ref().unwrap_or(&cat.name_pretty)
+            ),
+            ("racetime" | "goal", None) => {
+                format!("Category updated: {}. Its races don't get racetime.gg rooms.", cat)
+            }
+            ("expected" | "max", _) => format!("Category updated: {}. {}", cat, race_length(&cat)),
+            _ => format!("Category updated: {}", cat),
+        },
+        Err(e) => {
+            warn!("Error updating category: {:?}", e);
+            "Unknown error updating the category. Bug Fox about it.".to_owned()
+        }
+    }
+}
+
+/// How long a category's races last
+fn race_length(cat: &Category) -> String {
+    let max = match cat.max_duration {
+        Some(m) => format_finish_time(m),
+        None => format!("{} (the default)", format_finish_time(DEFAULT_MAX_RACE_SECS)),
+    };
+    match cat.expected_duration {
+        Some(e) => format!(
+            "Its races usually take about {} and get ended automatically after {}.",
+            format_finish_time(e),
+            max
+        ),
+        None => format!("Its races get ended automatically after {}.", max),
+    }
+}
+
+/// `none` clears a field
+fn optional_value(value: &str) -> Option<String> {
+    if value.eq_ignore_ascii_case("none") {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+async fn _retire_category(mut args: Arguments<'_>, pool: &SqlitePool) -> String {
+    let syntax_error = "Please use the following format: !retirecategory <game alias> <category alias>";
+    let game_name = match args.next() {
+        Some(g) => g,
+        None => {
+            return syntax_error.to_owned();
+        }
+    };
+    let cat_name = match args.next() {
+        Some(c) => c,
+        None => {
+            return syntax_error.to_owned();
+        }
+    };
+
+    let game = match get_game(game_name, pool).await {
+        Some(g) => g,
+        None => {
+            return "No game found with that name. Try !listgames".to_owned();
+        }
+    };
+    let mut cat = match get_category(&game, cat_name, pool).await {
+        Some(c) => c,
+        None => {
+            return format!("No matching category found. try !listcategories {}", game.name);
+        }
+    };
+    if cat.retired {
+        return format!("{} is already retired.", cat);
+    }
+
+    cat.retired = true;
+    match cat.save(pool).await {
+        Ok(()) => format!("Category retired: {}", cat),
+        Err(e) => {
+            warn!("Error retiring category: {:?}", e);
+            "Unknown error retiring the category. Bug Fox about it.".to_owned()
+        }
+    }
+}
+
+async fn create_game(name: &str, name_pretty: &str, pool: &SqlitePool) -> Option<Game> {
+    let q = sqlx::query(
+        "INSERT INTO game (name, name_pretty) VALUES (?, ?); \
+        SELECT last_insert_rowid() as rowid;",
+    )
+    .bind(name)
+    .bind(name_pretty);
+    match q.fetch_one(pool).await {
+        Ok(e) => Some(Game {
+            id: e.get::<i64, &str>("rowid"),
+            name: name.to_string(),
+            name_pretty: name_pretty.to_string(),
+        }),
+        Err(e) => {
+            error!("error creating game: {:?}", e);
+            None
+        }
+    }
+}
+
+async fn create_category(
+    game: &Game,
+    name: &str,
+    name_pretty: &str,
+    pool: &SqlitePool,
+) -> Option<Category> {
+    let q = sqlx::query(
+        "INSERT INTO category (game_id, name, name_pretty) VALUES (?, ?, ?); \
+        SELECT last_insert_rowid() as rowid;",
+    )
+    .bind(game.id)
+    .bind(name)
+    .bind(name_pretty);
+    match q.fetch_one(pool).await {
+        Ok(e) => Some(Category {
+            id: e.get::<i64, &str>("rowid"),
+            game_id: game.id,
+            name: name.to_string(),
+            name_pretty: name_pretty.to_string(),
+            retired: false,
+            racetime_category: None,
+            racetime_goal: None,
+            expected_duration: None,
+            max_duration: None,
+        }),
+        Err(e) => {
+            error!("error creating category: {:?}", e);
+            None
+        }
+    }
+}
+
+async fn get_game(name: &str, pool: &SqlitePool) -> Option<Game> {
+    let q = sqlx::query_as::<_, Game>(
+        "SELECT id, name, name_pretty FROM game WHERE name = ?",
+    ).bind(name);
+    match q.fetch_one(pool).await {
+        Ok(r) => Some(r),
+        Err(e) => {
+            warn!("Error fetching game: {:?}", e);
+            None
+        }
+    }
+}
+
+async fn get_category(game: &Game, name: &str, pool: &SqlitePool) -> Option<Category> {
+    debug!(
+        "Getting category {} for game (name {} id {}) ",
+        name, game.name, game.id
+    );
+    let q = sqlx::query_as::<_, Category>(
+        "SELECT * FROM category WHERE name = ? AND game_id = ?",
+    ).bind(name).bind(game.id);
+
+    match q.fetch_one(pool).await {
+        Ok(r) => Some(r),
+        Err(e) => {
+            warn!("Error fetching category: {:?}", e);
+            None
+        }
+    }
+}
+
+async fn get_games(pool: &SqlitePool) -> Vec<Game> {
+    let q = sqlx::query_as::<_, Game>("SELECT id, name, name_pretty FROM game");
+    let mut rows = q.fetch(pool);
+    let mut games = vec![];
+    while let r = rows.try_next().await {
+        match r {
+            Ok(mg) => match mg {
+                Some(g) => {
+                    games.push(g);
+                }
+                None => {
+                    break;
+                }
+            },
+            Err(e) => {
+                warn!("Error fetching row: {:?}", e);
+            }
+        }
+    }
+
+    games
+}
+
+/// Gets all of the non-retired categories for the given game
+async fn get_categories(game: &Game, pool: &SqlitePool) -> Vec<Category> {
+    debug!(
+        "Getting categories for game (name {} id {})",
+        game.name, game.id
+    );
+    let q = sqlx::query_as::<_, Category>(
+        "SELECT * FROM category WHERE game_id = ? AND retired = FALSE",
+    ).bind(game.id);
+    let mut rows = q.fetch(pool);
+    let mut categories = vec![];
+    while let r = rows.try_next().await {
+        match r {
+            Ok(mc) => match mc {
+                Some(c) => {
+                    categories.push(c);
+                }
+                None => {
+                    break;
+                }
+            },
+            Err(e) => {
+                warn!("Error fetching row: {:?}", e);
+            }
+        }
+    }
+
+    categories
+}
+
+async fn get_upcoming_races(gid: GuildId, window: Duration, pool: &SqlitePool) -> Vec<Race> {
+    let now = Local::now().timestamp();
+    let until = (Local::now() + CDuration::from_std(window).unwrap()).timestamp();
+    let state = RaceState::SCHEDULED.to_string();
+    let q = sqlx::query_as::<_, Race>(
+        "SELECT * FROM race WHERE guild_id = ? and state = ? and occurs > ? and occurs < ?",
+    ).bind(gid.to_string()).bind(state).bind(now).bind(until);
+    let mut rows = q.fetch(pool);
+    let mut races = vec![];
+    while let r = rows.try_next().await {
+        match r {
+            Ok(mc) => match mc {
+                Some(c) => {
+                    races.push(c);
+                }
+                None => {
+                    break;
+                }
+            },
+            Err(e) => {
+                warn!("Error fetching row: {:?}", e);
+            }
+        }
+    }
+
+    races
+}
+
+/// Scheduled and active races of a game and category starting from `from` to `until`
+async fn get_races_between(
+    gid: GuildId,
+    game_id: i64,
+    category_id: i64,
+    from: DateTime<Tz>,
+    until: DateTime<Tz>,
+    pool: &SqlitePool,
+) -> Vec<Race> {
+    let q = sqlx::query_as::<_, Race>(
+        "SELECT * FROM race WHERE guild_id = ? AND game_id = ? AND category_id = ? \
+        AND state IN (?, ?) AND occurs >= ? AND occurs <= ? ORDER BY occurs",
+    )
+    .bind(gid.to_string())
+    .bind(game_id)
+    .bind(category_id)
+    .bind(RaceState::SCHEDULED.to_string())
+    .bind(RaceState::ACTIVE.to_string())
+    .bind(from.timestamp())
+    .bind(until.timestamp());
+    match q.fetch_all(pool).await {
+        Ok(races) => races,
+        Err(e) => {
+            warn!("Error fetching races: {:?}", e);
+            vec![]
+        }
+    }
+}
+
+/// Whether some other race of the same game and category is at exactly the same time. The race
+/// table's unique constraint catches most of these, but sqlite never treats NULLs as equal, so
+/// it misses races from before the bot tracked guilds.
+async fn race_exists_at(race: &Race, pool: &SqlitePool) -> bool {
+    let q = sqlx::query(
+        "SELECT id FROM race WHERE (guild_id = ? OR guild_id IS NULL) AND game_id = ? \
+        AND category_id = ? AND occurs = ? AND id != ? LIMIT 1",
+    )
+    .bind(&race.guild_id)
+    .bind(race.game_id)
+    .bind(race.category_id)
+    .bind(race.occurs)
+    .bind(race.id);
+    match q.fetch_optional(pool).await {
+        Ok(row) => row.is_some(),
+        Err(e) => {
+            warn!("Error checking for races at the same time as {}: {:?}", race, e);
+            false
+        }
+    }
+}
+
+/// The guild's races matching `filter`: scheduled and active ones soonest first, or finished ones
+/// newest first
+async fn get_filtered_races(gid: GuildId, filter: &RaceFilter, pool: &SqlitePool) -> Vec<Race> {
+    let (states, order) = if filter.past {
+        ([RaceState::COMPLETED, RaceState::CANCELLED], "DESC")
+    } else {
+        ([RaceState::SCHEDULED, RaceState::ACTIVE], "ASC")
+    };
+    let mut sql = "SELECT * FROM race WHERE guild_id = ? AND state IN (?, ?)".to_string();
+    if filter.game_id.is_some() {
+        sql.push_str(" AND game_id = ?");
+    }
+    if filter.category_id.is_some() {
+        sql.push_str(" AND category_id = ?");
+    }
+    sql.push_str(&format!(" ORDER BY occurs {}, id {}", order, order));
+    if filter.limit.is_some() {
+        sql.push_str(" LIMIT ?");
+    }
+    let mut q = sqlx::query_as::<_, Race>(&sql)
+        .bind(gid.to_string())
+        .bind(states[0].to_string())
+        .bind(states[1].to_string());
+    if let Some(g) = filter.game_id {
+        q = q.bind(g);
+    }
+    if let Some(c) = filter.category_id {
+        q = q.bind(c);
+    }
+    if let Some(l) = filter.limit {
+        q = q.bind(l);
+    }
+    match q.fetch_all(pool).await {
+        Ok(races) => races,
+        Err(e) => {
+            warn!("Error fetching races: {:?}", e);
+            vec![]
+        }
+    }
+}
+
+/// Races (and the cleanse message) from before the bot ran in multiple guilds don't know which
+/// guild they're in. They all came from the original guild, so they get handed to it.
+async fn claim_legacy_races(gid: GuildId, pool: &SqlitePool) {
+    let q = sqlx::query("UPDATE race SET guild_id = ? WHERE guild_id IS NULL").bind(gid.to_string());
+    match q.execute(pool).await {
+        Ok(res) if res.rows_affected() > 0 => {
+            info!("Assigned {} legacy races to guild {}", res.rows_affected(), gid);
+        }
+        Ok(_) => {}
+        Err(e) => {
+            warn!("Error assigning legacy races to guild {}: {:?}", gid, e);
+        }
+    }
+    if get_cleanse_message_id(gid, pool).await.is_none() {
+        if let Some(legacy) = get_setting(CLEANSE_MESSAGE_SETTING, pool).await {
+            set_setting(&cleanse_message_setting(gid), &legacy, pool).await;
+        }
+    }
+}
+
+/// Replaces everything we know about the guild's emojis
+async fn setup_emojis(guild_id: GuildId, emojis: &[Emoji], bot_state: Arc<BotState>) {
+    let settings = bot_state.get_settings(guild_id).await;
+    let mut all_emojis = bot_state.emojis.write().await;
+    let lock = all_emojis.entry(guild_id).or_default();
+    lock.clear();
+    for e in emojis {
+        lock.insert(e.name.to_string(), e.clone());
+        debug!("Inserting emoji {}", e.name.to_string());
+    }
+    if !lock.contains_key(&settings.racing_emoji_name) {
+        warn!(
+            "Guild {} has no :{}: emoji, racers will get a placeholder",
+            guild_id, settings.racing_emoji_name
+        );
+    }
+}
+
+/// Replaces everything we know about the guild's channels, creating the scheduling channel if
+/// it doesn't exist yet
+async fn setup_channels(guild_id: GuildId, channels: &[GuildChannel], bot_state: Arc<BotState>) {
+    let settings = bot_state.get_settings(guild_id).await;
+    let mut has_schedule_channel = false;
+    let mut all_channels = bot_state.channels.write().await;
+    let lock = all_channels.entry(guild_id).or_default();
+    lock.clear();
+    for c in channels {
+        debug!("Inserting channel `{}` {}", c.name(), c.id());
+        lock.insert(c.name().to_string(), c.id());
+        if c.name() == settings.scheduling_channel_name {
+            has_schedule_channel = true;
+        }
+    }
+    if !has_schedule_channel {
+        match bot_state
+            .http
+            .create_guild_channel(guild_id, &settings.scheduling_channel_name)
+        {
+            Ok(chan) => {
+                let mut chan = chan.kind(ChannelType::GuildText);
+                // the configured category only exists in one of the guilds
+                if let Some(parent) = bot_state.config.channel_category_id {
+                    if channels.iter().any(|c| c.id() == ChannelId(parent)) {
+                        chan = chan.parent_id(parent);
+                    }
+                }
+                match chan.await {
+                    Ok(created) => {
+                        lock.insert(created.name().to_string(), created.id());
+                    }
+                    Err(e) => {
+                        warn!("Error creating scheduling channel: {}", e);
+                    }
+                }
+            }
+            Err(e) => {
+                warn!("Error creating scheduling channel: {}", e);
+            }
+        }
+    }
+}
+
+async fn setup_roles(guild: &Box<GuildCreate>, bot_state: Arc<BotState>) {
+    let desired_roles = vec![
+        DesiredRoleBuilder::default()
+            .name("active-racer".to_string())
+            .color(0xE74C3C)
+            .mentionable(true)
+            .build()
+            .unwrap(),
+        DesiredRoleBuilder::default()
+            .name("unconfirmed-racer".to_string())
+            .color(0xf7c9c4)
+            .mentionable(true)
+            .build()
+            .unwrap(),
+        DesiredRoleBuilder::default()
+            .name("active-commentator".to_string())
+            .color(0x3498DB)
+            .mentionable(true)
+            .build()
+            .unwrap(),
+        DesiredRoleBuilder::default()
+            .name("active-restreamer".to_string())
+            .color(0x9B59B6)
+            .mentionable(true)
+            .build()
+            .unwrap(),
+    ];
+
+    let mut desired_roles_by_name: HashMap<String, DesiredRole> = Default::default();
+    for role in desired_roles {
+        desired_roles_by_name.insert(role.name.clone(), role);
+    }
+
+    for role in &guild.roles {
+        if desired_roles_by_name.contains_key(&role.name) {
+            let desired = desired_roles_by_name.get(&role.name).unwrap();
+            if !desired.matches(role) {
+                warn!("Non-matching role found!! wtf {:?}", role);
+            } else {
+                desired_roles_by_name.remove(&role.name);
+                {
+                    let mut lock = bot_state.roles.write().await;
+                    lock.entry(guild.id)
+                        .or_default()
+                        .insert(role.name.clone(), role.clone());
+                }
+            }
+        }
+    }
+
+    for desired in desired_roles_by_name.values() {
+        let c = bot_state.http.create_role(guild.id);
+        info!("Creating role {:?}", desired);
+        match desired.create(c).await {
+            Ok(_) => {
+                debug!("Successfuly created role!");
+            }
+            Err(e) => {
+                warn!("Error creating role: {:?}", e);
+            }
+        }
+    }
+
+    for er in bot_state.get_roles(guild.id).await {
+        match bot_state
+            .http
+            .add_guild_member_role(guild.id, UserId::from(bot_state.config.owner_user_id), er.id)
+            .await
+        {
+            Ok(_) => {}
+            Err(e) => {
+                warn!("Failed to assign to role: {:?}", e);
+            }
+        }
+    }
+}
+
+// some - maybe most - of this stuff should be in integration tests probably.
+#[cfg(test)]
+mod test {
+    use crate::discord::{
+        create_race, get_category, get_game, get_pool, get_upcoming_races, RaceState,
+        _end_race, nag_times, _add_game, _add_category, _edit_category, _retire_category,
+        _list_categories, split_notes, _race_note, _cancel_race, get_active_races,
+        _reschedule_race, get_race_by_scheduling_message_id, get_race_by_active_message_id,
+        same_reaction, set_entrant_status, get_entrant, get_entrants, plan_role_changes,
+        get_setting, set_setting, get_current_signups, set_confirmation, _crew, _assign,
+        parse_user_mention, get_assigned_crew, _settings, load_guild_settings, _autocomplete,
+        button_id, parse_button_id, signup_status, ButtonAction, _new_series, _list_series,
+        _end_series, _schedule_series, get_active_series, _timezone, get_user_timezone,
+        is_unique_violation, check_race_time, take_force_flag, paginate, _races, _race, _done,
+        _forfeit, _set_result, results_table, get_results, ordinal, set_result, _leaderboard, _pb,
+        _rating, _ratings, get_rating_changes, recompute_ratings, open_racetime_room,
+        check_racetime_room, MAX_ROOM_FAILURES, _racetime, _start, race_timer, RaceTimer, get_race,
+        get_racetime_link, get_racetime_name,
+    };
+    use crate::config::{Config, GuildSettings};
+    use crate::time_parser::{self, TimeParseError};
+    use crate::models::{EntrantKind, EntrantStatus, Race};
+    use crate::racetime::{NewRoom, RacetimeClient, RacetimeError, RoomData};
+    use futures::future::BoxFuture;
+    use serde_json::{json, Value};
+    use chrono::{DateTime, Datelike, Duration as CDuration, Local, NaiveDateTime, TimeZone, Timelike};
+    use chrono_tz::Tz;
+    use chrono_tz::US::Eastern;
+    use lru::LruCache;
+    use sqlx::SqlitePool;
+    use tokio::time::Duration;
+    use twilight_command_parser::Arguments;
+    use twilight_model::channel::ReactionType;
+    use twilight_model::id::{ChannelId, EmojiId, GuildId, MessageId, RoleId, UserId};
+    use std::collections::HashMap;
+
+    const GUILD: GuildId = GuildId(1);
+
+    /// Reads a time typed by someone in `tz`, counting from the real current time
+    fn parse_time(time_str: &str, tz: Tz) -> Result<DateTime<Tz>, TimeParseError> {
+        time_parser::parse_time(time_str, tz, Local::now().with_timezone(&tz))
+    }
+
+    fn init() {
+        let _ = env_logger::builder().is_test(true).try_init();
+    }
+
+    #[test]
+    fn test_nag_times() {
+        let times = vec![15, 30, 60];
+        assert_eq!(vec![15, 30, 60], nag_times(&times, 999));
+        assert_eq!(vec![15, 30], nag_times(&times, 45));
+        assert_eq!(vec![15], nag_times(&times, 27));
+        assert_eq!(vec![] as Vec<i64>, nag_times(&times, 2));
+    }
+
+    #[test]
+    fn test_lru_thing() {
+        let mut cache: LruCache<i64, Vec<i32>> = LruCache::new(3);
+        cache.put(1, vec![5, 15, 30]);
+
+        {
+            let rn = cache.get(&1).unwrap();
+            assert_eq!(&vec![5, 15, 30], rn);
+        }
+        let mrn = cache.get_mut(&1).unwrap();
+
+        match mrn.pop() {
+            Some(time) => {
+                assert_eq!(time, 30);
+            }
+            None => {}
+        }
+        {
+            let rn = cache.get(&1).unwrap();
+            assert_eq!(&vec![5, 15], rn);
+        }
+    }
+
+    #[test]
+    fn test_vec_semantics() {
+        let v = vec![9, 8, 7];
+        let f = v.first();
+        assert_eq!(Some(&9), f);
+        let f2 = v.first();
+        assert_eq!(Some(&9), f2);
+    }
+
+    async fn initdb(pool: &SqlitePool) {
+        let queries = vec![
+            "DELETE FROM setting",
+            "DELETE FROM guild_settings",
+            "DELETE FROM user_timezone",
+            "DELETE FROM racetime_user",
+            "DELETE FROM rating_change",
+            "DELETE FROM race_result",
+            "DELETE FROM race_entrant",
+            "DELETE FROM race",
+            "DELETE FROM race_series",
+            "DELETE FROM category",
+            "DELETE FROM game",
+            "INSERT INTO game (id, name, name_pretty) VALUES (1, 'alttp', 'A Link To The Past')",
+            "INSERT INTO category (game_id, name, name_pretty) VALUES (1, 'ms', 'Master Sword')",
+            "INSERT INTO category (game_id, name, name_pretty) VALUES (1, 'nmg', 'Any% NMG No S&Q')"
+            ];
+        for sql in queries {
+            let q = sqlx::query(sql);
+            q.execute(pool).await.unwrap();
+        }
+    }
+
+    #[test]
+    fn test_datetime_stuff() {
+        init();
+        let ndt = NaiveDateTime::parse_from_str("06/09/2021 11:00pm", "%m/%d/%Y %I:%M%P");
+        ndt.unwrap();
+
+        let now: DateTime<Tz> = Local::now().with_timezone(&Eastern);
+        std::thread::sleep(Duration::from_millis(5));
+        let nower: DateTime<Tz> = Local::now().with_timezone(&Eastern);
+        let fwd = nower - now;
+        assert!(fwd.num_milliseconds() > 4);
+        let backwd = now - nower;
+        assert!(backwd.num_milliseconds() < -4);
+    }
+
+    #[test]
+    fn test_parse_time() {
+        init();
+        let odt = parse_time("06/09/2021 11:00pm", Eastern);
+        assert!(odt.is_ok());
+        let dt = odt.unwrap();
+        assert_eq!(6, dt.month());
+        assert_eq!(9, dt.day());
+        assert_eq!(2021, dt.year());
+        assert_eq!(23, dt.hour());
+        assert_eq!(0, dt.minute());
+        assert_eq!(0, dt.second());
+        assert_eq!("2021-06-09T23:00:00-04:00", dt.to_rfc3339());
+        assert_eq!(1623294000, dt.timestamp());
+
+        let berlin = parse_time("06/09/2021 11:00pm", chrono_tz::Europe::Berlin).unwrap();
+        assert_eq!("2021-06-09T23:00:00+02:00", berlin.to_rfc3339());
+    }
+
+    #[test]
+    fn test_split_notes() {
+        assert_eq!(("06/09/2021 11:00pm", None), split_notes("06/09/2021 11:00pm"));
+        assert_eq!(
+            ("06/09/2021 11:00pm", Some("for new runners")),
+            split_notes("06/09/2021 11:00pm -- for new runners")
+        );
+        assert_eq!(("06/09/2021 11:00pm", None), split_notes("06/09/2021 11:00pm --  "));
+        assert_eq!(
+            ("06/09/2021 11:00pm --force", Some("bring snacks")),
+            split_notes("06/09/2021 11:00pm --force -- bring snacks")
+        );
+        assert_eq!(
+            ("06/09/2021 11:00pm", Some("don't use --force")),
+            split_notes("06/09/2021 11:00pm -- don't use --force")
+        );
+    }
+
+    // N.B. any test that hits the database needs this annotation. the flavor="multi_thread" part is
+    // required to allow get_pool() to resolve, which eventually bottoms out doing something
+    // blocking, apparently.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_race_operations() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+        let g = get_game("alttp", &pool).await.unwrap();
+        let c = get_category(&g, "nmg", &pool).await.unwrap();
+        let when = parse_time("06/09/2021 11:10pm", Eastern).unwrap();
+        let r = create_race(GUILD, &g, &c, when, &pool).await;
+        assert!(r.is_ok());
+        let mut race = r.unwrap();
+        assert_eq!(race.occurs, when.timestamp());
+        assert_eq!(race.category_id, c.id);
+        assert_eq!(race.game_id, g.id);
+        assert_eq!(race.get_scheduling_message_id(), None);
+        assert_eq!(race.get_state(), RaceState::SCHEDULED);
+        let mid = MessageId(u64::MAX);
+        race.set_scheduling_message_id(mid);
+        race.set_state(RaceState::ACTIVE);
+        race.save(&pool).await.unwrap();
+
+        // it would be reasonable to add a get_race_by_id() kind of message, but I don't think it's
+        // actually useful yet.
+        let q = sqlx::query_as::<_, Race>("SELECT * FROM race WHERE id = ?").bind(race.id);
+        let race_refreshed = q.fetch_one(&pool).await.unwrap();
+        assert_eq!(race, race_refreshed);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_manage_games_and_categories() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+
+        assert_eq!(
+            "Game created: Final Fantasy X (ffx)",
+            _add_game(Arguments::new("ffx Final Fantasy X"), &pool).await
+        );
+        assert_eq!(
+            "That conflicts with the existing game Final Fantasy X (ffx)",
+            _add_game(Arguments::new("ffx Something Else"), &pool).await
+        );
+        assert_eq!(
+            "Aliases may only contain lowercase letters, numbers, and underscores.",
+            _add_game(Arguments::new("FFX! Final Fantasy Ten"), &pool).await
+        );
+
+        assert_eq!(
+            "Category created for Final Fantasy X: Any% PC (any_pc)",
+            _add_category(Arguments::new("ffx any_pc Any% PC"), &pool).await
+        );
+        // category aliases are unique across games
+        assert_eq!(
+            "That conflicts with the existing category Any% NMG No S&Q (nmg)",
+            _add_category(Arguments::new("ffx nmg No Major Glitches"), &pool).await
+        );
+
+        assert_eq!(
+            "Category updated: Any% PC Steam (any_pc)",
+            _edit_category(Arguments::new("ffx any_pc name Any% PC Steam"), &pool).await
+        );
+        // the category's own name matches too, but the clash with nmg still gets caught
+        assert_eq!(
+            "That conflicts with the existing category Any% NMG No S&Q (nmg)",
+            _edit_category(Arguments::new("alttp ms alias nmg"), &pool).await
+        );
+        let g = get_game("ffx", &pool).await.unwrap();
+        let c = get_category(&g, "any_pc", &pool).await.unwrap();
+        assert_eq!("Any% PC Steam", c.name_pretty);
+        assert!(!c.retired);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_retire_category() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+
+        assert_eq!(
+            "Category retired: Master Sword (ms) [retired]",
+            _retire_category(Arguments::new("alttp ms"), &pool).await
+        );
+        assert_eq!(
+            "Available categories for A Link To The Past:\n* Any% NMG No S&Q (nmg)",
+            _list_categories(Arguments::new("alttp"), &pool).await
+        );
+
+        // retired categories are still around for old races to point at
+        let g = get_game("alttp", &pool).await.unwrap();
+        let c = get_category(&g, "ms", &pool).await.unwrap();
+        assert!(c.retired);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_race_note() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+
+        let g = get_game("alttp", &pool).await.unwrap();
+        let c = get_category(&g, "nmg", &pool).await.unwrap();
+        let r = create_race(GUILD, &g, &c, parse_time("06/09/2021 11:10pm", Eastern).unwrap(), &pool)
+            .await
+            .unwrap();
+
+        let (reply, updated) =
+            _race_note(GUILD, Arguments::new(&format!("{} for new runners", r.id)), &pool).await;
+        assert_eq!(format!("Notes for {} set to: for new runners", r), reply);
+        assert_eq!(Some("for new runners".to_string()), updated.unwrap().notes);
+        let refreshed = Race::get_by_id(r.id, &pool).await.unwrap();
+        assert_eq!(Some("for new runners".to_string()), refreshed.notes);
+
+        let (reply, _) = _race_note(GUILD, Arguments::new(&format!("{}", r.id)), &pool).await;
+        assert_eq!(format!("Notes for {} cleared.", r), reply);
+        let refreshed = Race::get_by_id(r.id, &pool).await.unwrap();
+        assert_eq!(None, refreshed.notes);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_get_races() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+
+        let g = get_game("alttp", &pool).await.unwrap();
+        let c = get_category(&g, "nmg", &pool).await.unwrap();
+
+        let when = Local::now();
+        let later =
+            (when + CDuration::from_std(Duration::from_secs(60)).unwrap()).with_timezone(&Eastern);
+
+        // let when = Eastern::now() + CDuration::from_std(Duration::from_secs(60)).unwrap();
+        let r = create_race(GUILD, &g, &c, later, &pool).await;
+        assert!(r.is_ok());
+
+        let scheduled = get_upcoming_races(GUILD, Duration::from_secs(120), &pool).await;
+        assert_eq!(1, scheduled.len());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_races_are_per_guild() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+
+        let other_guild = GuildId(2);
+        let g = get_game("alttp", &pool).await.unwrap();
+        let c = get_category(&g, "nmg", &pool).await.unwrap();
+        let now = Local::now().with_timezone(&Eastern);
+        let mut ours = create_race(GUILD, &g, &c, now, &pool).await.unwrap();
+        // the same race at the same time is fine, as long as it's somewhere else
+        let mut theirs = create_race(other_guild, &g, &c, now, &pool).await.unwrap();
+        assert!(is_unique_violation(&create_race(GUILD, &g, &c, now, &pool).await.unwrap_err()));
+        for r in vec![&mut ours, &mut theirs] {
+            r.set_state(RaceState::ACTIVE);
+            r.save(&pool).await.unwrap();
+        }
+
+        assert_eq!(Some(GUILD), ours.get_guild_id());
+        let active: Vec<i64> = get_active_races(GUILD, &pool).await.iter().map(|r| r.id).collect();
+        assert_eq!(vec![ours.id], active);
+        assert_eq!(
+            "No valid race found.",
+            _cancel_race(GUILD, theirs.id, &pool).await.0
+        );
+        assert_eq!(
+            "No valid race found.",
+            _crew(GUILD, Arguments::new(&theirs.id.to_string()), &pool).await
+        );
+        // having one active race in each guild doesn't make !endrace ambiguous
+        assert_eq!(format!("{} completed.", ours), _end_race(GUILD, None, &pool).await.0);
+        assert_eq!(
+            RaceState::ACTIVE,
+            Race::get_by_id(theirs.id, &pool).await.unwrap().get_state()
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_cancel_race() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+
+        let g = get_game("alttp", &pool).await.unwrap();
+        let c = get_category(&g, "nmg", &pool).await.unwrap();
+        let later = (Local::now() + CDuration::from_std(Duration::from_secs(60)).unwrap())
+            .with_timezone(&Eastern);
+        let scheduled = create_race(GUILD, &g, &c, later, &pool).await.unwrap();
+        let mut active = create_race(GUILD, &g, &c, Local::now().with_timezone(&Eastern), &pool)
+            .await
+            .unwrap();
+        active.set_state(RaceState::ACTIVE);
+        active.save(&pool).await.unwrap();
+
+        assert_eq!(format!("{} cancelled.", scheduled), _cancel_race(GUILD, scheduled.id, &pool).await.0);
+        assert_eq!(format!("{} cancelled.", active), _cancel_race(GUILD, active.id, &pool).await.0);
+        assert_eq!(
+            RaceState::CANCELLED,
+            Race::get_by_id(active.id, &pool).await.unwrap().get_state()
+        );
+
+        assert!(get_upcoming_races(GUILD, Duration::from_secs(120), &pool).await.is_empty());
+        assert!(get_active_races(GUILD, &pool).await.is_empty());
+
+        let (reply, race) = _cancel_race(GUILD, active.id, &pool).await;
+        assert_eq!(format!("{} is already over.", active), reply);
+        assert!(race.is_none());
+        assert_eq!("No valid race found.", _cancel_race(GUILD, 1234, &pool).await.0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_reschedule_race() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+
+        let g = get_game("alttp", &pool).await.unwrap();
+        let c = get_category(&g, "nmg", &pool).await.unwrap();
+        let now = parse_time("06/09/2021 10:00pm", Eastern).unwrap();
+        let r = create_race(GUILD, &g, &c, parse_time("06/09/2021 11:00pm", Eastern).unwrap(), &pool)
+            .await
+            .unwrap();
+        let other = create_race(GUILD, &g, &c, parse_time("06/10/2021 11:00pm", Eastern).unwrap(), &pool)
+            .await
+            .unwrap();
+
+        let (reply, race, stale) = _reschedule_race(
+            GUILD,
+            Arguments::new(&format!("{} 06/09/2021 11:30pm", r.id)),
+            now,
+            Duration::from_secs(60 * 30),
+            Eastern,
+            &pool,
+        )
+        .await;
+        assert_eq!(format!("{} moved to <t:1623295800:F>.", r), reply);
+        assert_eq!(1623295800, race.unwrap().occurs);
+        assert_eq!(None, stale);
+
+        let (reply, race, _) = _reschedule_race(
+            GUILD,
+            Arguments::new(&format!("{} 06/10/2021 11:00pm", r.id)),
+            now,
+            Duration::from_secs(60 * 30),
+            Eastern,
+            &pool,
+        )
+        .await;
+        assert_eq!("There's already a race of that game and category at that time.", reply);
+        assert!(race.is_none());
+        assert_eq!(1623295800, Race::get_by_id(r.id, &pool).await.unwrap().occurs);
+
+        // an active race pushed back out of the notification window goes back to being scheduled
+        let mut active = Race::get_by_id(other.id, &pool).await.unwrap();
+        active.set_state(RaceState::ACTIVE);
+        active.set_active_message_id(MessageId(1234));
+        active.save(&pool).await.unwrap();
+        let (_, race, stale) = _reschedule_race(
+            GUILD,
+            Arguments::new(&format!("{} 06/11/2021 11:00pm", other.id)),
+            parse_time("06/10/2021 10:45pm", Eastern).unwrap(),
+            Duration::from_secs(60 * 30),
+            Eastern,
+            &pool,
+        )
+        .await;
+        let race = race.unwrap();
+        assert_eq!(Some(MessageId(1234)), stale);
+        assert_eq!(RaceState::SCHEDULED, race.get_state());
+        assert_eq!(race, Race::get_by_id(other.id, &pool).await.unwrap());
+        assert_eq!(None, race.get_active_message_id());
+
+        let now = parse_time("06/09/2021 10:00pm", Eastern).unwrap();
+        let (reply, race, _) = _reschedule_race(
+            GUILD,
+            Arguments::new(&format!("{} 06/09/2021 9:00pm", r.id)),
+            now,
+            Duration::from_secs(60 * 30),
+            Eastern,
+            &pool,
+        )
+        .await;
+        assert_eq!("<t:1623286800:F> is in the past.", reply);
+        assert!(race.is_none());
+
+        // too close to the other race unless we insist
+        let (reply, race, _) = _reschedule_race(
+            GUILD,
+            Arguments::new(&format!("{} 06/11/2021 9:00pm", r.id)),
+            now,
+            Duration::from_secs(60 * 30),
+            Eastern,
+            &pool,
+        )
+        .await;
+        assert_eq!(
+            format!(
+                "{} is the same game and category, at <t:1623466800:F>. Add --force to move it anyway.",
+                other
+            ),
+            reply
+        );
+        assert!(race.is_none());
+        let (_, race, _) = _reschedule_race(
+            GUILD,
+            Arguments::new(&format!("{} 06/11/2021 9:00pm --force", r.id)),
+            now,
+            Duration::from_secs(60 * 30),
+            Eastern,
+            &pool,
+        )
+        .await;
+        assert_eq!(1623459600, race.unwrap().occurs);
+    }
+
+    #[test]
+    fn test_take_force_flag() {
+        assert_eq!(
+            ("6/9/2021 11pm -- bring snacks".to_string(), false),
+            take_force_flag("6/9/2021 11pm -- bring snacks")
+        );
+        assert_eq!(
+            ("6/9/2021 11pm -- bring snacks".to_string(), true),
+            take_force_flag("6/9/2021 11pm --force -- bring snacks")
+        );
+        assert_eq!(("6/9/2021 11pm".to_string(), true), take_force_flag("--force 6/9/2021 11pm"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_check_race_time() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+
+        let g = get_game("alttp", &pool).await.unwrap();
+        let nmg = get_category(&g, "nmg", &pool).await.unwrap();
+        let ms = get_category(&g, "ms", &pool).await.unwrap();
+        let existing = create_race(GUILD, &g, &nmg, parse_time("06/09/2021 11:00pm", Eastern).unwrap(), &pool)
+            .await
+            .unwrap();
+        let now = parse_time("06/09/2021 10:00pm", Eastern).unwrap();
+        let notify = Some(Duration::from_secs(60 * 30));
+        let check = |cat_id: i64, time: &str| {
+            let candidate = Race::new(0, GUILD, g.id, cat_id, parse_time(time, Eastern).unwrap());
+            let pool = pool.clone();
+            async move { check_race_time(&candidate, now, notify, &pool).await }
+        };
+
+        assert_eq!(Ok(vec![]), check(nmg.id, "06/10/2021 11:00pm").await);
+        assert_eq!(Ok(vec![]), check(ms.id, "06/09/2021 11:00pm").await);
+        assert_eq!(
+            Err("There's already a race of that game and category at that time.".to_string()),
+            check(nmg.id, "06/09/2021 11:00pm").await
+        );
+        assert_eq!(
+            Err("<t:1623290400:F> is in the past.".to_string()),
+            check(nmg.id, "06/09/2021 10:00pm").await
+        );
+        assert_eq!(
+            Ok(vec![format!("{} is the same game and category, at <t:1623294000:F>.", existing)]),
+            check(nmg.id, "06/10/2021 1:00am").await
+        );
+        assert_eq!(
+            Ok(vec!["That's less than 30 minutes away, so people won't have long to sign up.".to_string()]),
+            check(ms.id, "06/09/2021 10:15pm").await
+        );
+        assert_eq!(
+            Ok(vec!["That's more than 6 months away.".to_string()]),
+            check(nmg.id, "01/09/2022 11:00pm").await
+        );
+        // races that are over don't get in the way
+        let mut cancelled = Race::get_by_id(existing.id, &pool).await.unwrap();
+        cancelled.set_state(RaceState::CANCELLED);
+        cancelled.save(&pool).await.unwrap();
+        assert_eq!(Ok(vec![]), check(nmg.id, "06/10/2021 1:00am").await);
+
+        // races from before guilds were tracked count too, even though the unique constraint
+        // doesn't cover them
+        let legacy = create_race(GUILD, &g, &ms, parse_time("06/11/2021 11:00pm", Eastern).unwrap(), &pool)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE race SET guild_id = NULL WHERE id = ?")
+            .bind(legacy.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(
+            Err("There's already a race of that game and category at that time.".to_string()),
+            check(ms.id, "06/11/2021 11:00pm").await
+        );
+    }
+
+    #[test]
+    fn test_same_reaction() {
+        let check = ReactionType::Unicode { name: "✅".to_string() };
+        let custom = |id, name: &str| ReactionType::Custom {
+            animated: false,
+            id: EmojiId(id),
+            name: Some(name.to_string()),
+        };
+        assert!(same_reaction(&check, &ReactionType::Unicode { name: "✅".to_string() }));
+        assert!(!same_reaction(&check, &ReactionType::Unicode { name: "tv".to_string() }));
+        // custom emoji get renamed; the id is what matters
+        assert!(same_reaction(&custom(5, "raisinghand"), &custom(5, "hand")));
+        assert!(!same_reaction(&custom(5, "raisinghand"), &custom(6, "raisinghand")));
+        assert!(!same_reaction(&check, &custom(5, "✅")));
+    }
+
+    #[test]
+    fn test_plan_role_changes() {
+        let unconfirmed = RoleId(1);
+        let confirmed = RoleId(2);
+        let unrelated = RoleId(3);
+        let managed = vec![unconfirmed, confirmed];
+
+        let mut desired = HashMap::new();
+        desired.insert(UserId(10), vec![confirmed].into_iter().collect());
+        desired.insert(UserId(11), vec![unconfirmed].into_iter().collect());
+        desired.insert(UserId(12), vec![confirmed].into_iter().collect());
+
+        let mut held = HashMap::new();
+        // has the stale unconfirmed role but should be confirmed
+        held.insert(UserId(10), vec![unconfirmed, unrelated]);
+        // already right
+        held.insert(UserId(11), vec![unconfirmed]);
+        // not in any active race
+        held.insert(UserId(13), vec![confirmed, unrelated]);
+        // user 12 isn't in the cache at all
+
+        let (to_add, to_remove) = plan_role_changes(&desired, &held, &managed);
+        assert_eq!(vec![(UserId(10), confirmed), (UserId(12), confirmed)], to_add);
+        assert_eq!(vec![(UserId(10), unconfirmed), (UserId(13), confirmed)], to_remove);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_get_race_by_message_id() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+
+        let g = get_game("alttp", &pool).await.unwrap();
+        let c = get_category(&g, "nmg", &pool).await.unwrap();
+        let mut r = create_race(GUILD, &g, &c, parse_time("06/09/2021 11:00pm", Eastern).unwrap(), &pool)
+            .await
+            .unwrap();
+        r.set_scheduling_message_id(MessageId(10));
+        r.set_active_message_id(MessageId(20));
+        r.save(&pool).await.unwrap();
+
+        assert_eq!(Some(r.id), get_race_by_scheduling_message_id(MessageId(10), &pool).await.map(|r| r.id));
+        assert_eq!(None, get_race_by_scheduling_message_id(MessageId(20), &pool).await);
+        assert_eq!(Some(r.id), get_race_by_active_message_id(MessageId(20), &pool).await.map(|r| r.id));
+        assert_eq!(None, get_race_by_active_message_id(MessageId(10), &pool).await);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_entrants() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+
+        let g = get_game("alttp", &pool).await.unwrap();
+        let c = get_category(&g, "nmg", &pool).await.unwrap();
+        let r = create_race(GUILD, &g, &c, parse_time("06/09/2021 11:00pm", Eastern).unwrap(), &pool)
+            .await
+            .unwrap();
+        let racer = UserId(u64::MAX);
+        let other = UserId(2);
+
+        assert_eq!(None, get_entrant(r.id, racer, EntrantKind::RACER, &pool).await);
+        set_entrant_status(r.id, racer, EntrantKind::RACER, EntrantStatus::INTERESTED, &pool).await;
+        set_entrant_status(r.id, other, EntrantKind::RACER, EntrantStatus::INTERESTED, &pool).await;
+        set_entrant_status(r.id, racer, EntrantKind::RACER, EntrantStatus::CONFIRMED, &pool).await;
+
+        let entrant = get_entrant(r.id, racer, EntrantKind::RACER, &pool).await.unwrap();
+        assert_eq!(Some(racer), entrant.get_user_id());
+        assert_eq!(EntrantStatus::CONFIRMED, entrant.get_status());
+        assert_eq!(2, get_entrants(r.id, EntrantKind::RACER, &pool).await.len());
+
+        set_entrant_status(r.id, racer, EntrantKind::RACER, EntrantStatus::WITHDRAWN, &pool).await;
+        let refreshed = get_entrant(r.id, racer, EntrantKind::RACER, &pool).await.unwrap();
+        assert_eq!(EntrantStatus::WITHDRAWN, refreshed.get_status());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_set_confirmation() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+
+        let g = get_game("alttp", &pool).await.unwrap();
+        let c = get_category(&g, "nmg", &pool).await.unwrap();
+        let r = create_race(GUILD, &g, &c, parse_time("06/09/2021 11:00pm", Eastern).unwrap(), &pool)
+            .await
+            .unwrap();
+        let commentator = UserId(3);
+        let walk_in = UserId(4);
+        set_entrant_status(r.id, commentator, EntrantKind::COMMENTATOR, EntrantStatus::INTERESTED, &pool).await;
+
+        assert_eq!(
+            vec![EntrantKind::COMMENTATOR],
+            set_confirmation(r.id, commentator, true, &pool).await
+        );
+        assert_eq!(None, get_entrant(r.id, commentator, EntrantKind::RACER, &pool).await);
+        assert_eq!(
+            EntrantStatus::CONFIRMED,
+            get_entrant(r.id, commentator, EntrantKind::COMMENTATOR, &pool).await.unwrap().get_status()
+        );
+
+        // readying up without signing up first makes you a racer
+        assert_eq!(vec![EntrantKind::RACER], set_confirmation(r.id, walk_in, true, &pool).await);
+        assert_eq!(vec![EntrantKind::RACER], set_confirmation(r.id, walk_in, false, &pool).await);
+        assert_eq!(
+            EntrantStatus::INTERESTED,
+            get_entrant(r.id, walk_in, EntrantKind::RACER, &pool).await.unwrap().get_status()
+        );
+
+        assert_eq!(
+            format!(
+                "Crew for {}:\nRacers: <@4>\nCommentators: <@3> (confirmed)\nRestreamers: nobody yet",
+                r
+            ),
+            _crew(GUILD, Arguments::new(&r.id.to_string()), &pool).await
+        );
+    }
+
+    #[test]
+    fn test_parse_user_mention() {
+        assert_eq!(Some(UserId(1234)), parse_user_mention("<@1234>"));
+        assert_eq!(Some(UserId(1234)), parse_user_mention("<@!1234>"));
+        assert_eq!(None, parse_user_mention("<@&1234>"));
+        assert_eq!(None, parse_user_mention("1234"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_assign() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+
+        let g = get_game("alttp", &pool).await.unwrap();
+        let c = get_category(&g, "nmg", &pool).await.unwrap();
+        let r = create_race(GUILD, &g, &c, parse_time("06/09/2021 11:00pm", Eastern).unwrap(), &pool)
+            .await
+            .unwrap();
+        set_entrant_status(r.id, UserId(5), EntrantKind::RESTREAMER, EntrantStatus::INTERESTED, &pool).await;
+        set_entrant_status(r.id, UserId(6), EntrantKind::RACER, EntrantStatus::INTERESTED, &pool).await;
+
+        let args = format!("restream {} <@6>", r.id);
+        assert_eq!(
+            (format!("<@6> hasn't volunteered to restream {}.", r), None),
+            _assign(GUILD, Arguments::new(&args), &pool).await
+        );
+        let args = format!("commentary {} <@5>", r.id);
+        assert_eq!(
+            (format!("<@5> hasn't volunteered to commentate {}.", r), None),
+            _assign(GUILD, Arguments::new(&args), &pool).await
+        );
+
+        let args = format!("restream {} <@!5>", r.id);
+        let (reply, assigned) = _assign(GUILD, Arguments::new(&args), &pool).await;
+        assert_eq!(format!("<@5> will restream {}.", r), reply);
+        assert_eq!(r.id, assigned.unwrap().0.id);
+        assert_eq!(
+            (format!("<@5> is already assigned to restream {}.", r), None),
+            _assign(GUILD, Arguments::new(&args), &pool).await
+        );
+        assert_eq!(1, get_assigned_crew(r.id, EntrantKind::RESTREAMER, &pool).await.len());
+        assert_eq!(
+            format!(
+                "Crew for {}:\nRacers: <@6>\nCommentators: nobody yet\nRestreamers: <@5> (assigned)",
+                r
+            ),
+            _crew(GUILD, Arguments::new(&r.id.to_string()), &pool).await
+        );
+
+        // confirming doesn't change the assignment, but backing out does
+        set_entrant_status(r.id, UserId(5), EntrantKind::RESTREAMER, EntrantStatus::CONFIRMED, &pool).await;
+        assert_eq!(1, get_assigned_crew(r.id, EntrantKind::RESTREAMER, &pool).await.len());
+        set_entrant_status(r.id, UserId(5), EntrantKind::RESTREAMER, EntrantStatus::WITHDRAWN, &pool).await;
+        assert!(get_assigned_crew(r.id, EntrantKind::RESTREAMER, &pool).await.is_empty());
+
+        assert_eq!(
+            (
+                "Please use the following format: !assign <commentary|restream> <race id> @user".to_string(),
+                None
+            ),
+            _assign(GUILD, Arguments::new("racing 1 <@5>"), &pool).await
+        );
+    }
+
+    #[test]
+    fn test_paginate() {
+        let lines: Vec<String> = vec!["aaaa", "bbb", "cc", "dddddddddd"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        assert_eq!(vec!["aaaa\nbbb", "cc", "dddddddd", "dd"], paginate(&lines, 8));
+        assert_eq!(vec!["aaaa\nbbb\ncc\ndddddddddd"], paginate(&lines, 2000));
+        assert!(paginate(&[], 2000).is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_races() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+
+        assert_eq!(
+            vec!["There are no races scheduled.".to_string()],
+            _races(GUILD, Arguments::new(""), &pool).await
+        );
+
+        let g = get_game("alttp", &pool).await.unwrap();
+        let nmg = get_category(&g, "nmg", &pool).await.unwrap();
+        let ms = get_category(&g, "ms", &pool).await.unwrap();
+        let later = create_race(GUILD, &g, &nmg, parse_time("06/10/2021 11:00pm", Eastern).unwrap(), &pool)
+            .await
+            .unwrap();
+        let sooner = create_race(GUILD, &g, &ms, parse_time("06/09/2021 11:00pm", Eastern).unwrap(), &pool)
+            .await
+            .unwrap();
+        let mut done = create_race(GUILD, &g, &nmg, parse_time("06/01/2021 11:00pm", Eastern).unwrap(), &pool)
+            .await
+            .unwrap();
+        done.set_state(RaceState::COMPLETED);
+        done.save(&pool).await.unwrap();
+        create_race(GuildId(2), &g, &nmg, parse_time("06/11/2021 11:00pm", Eastern).unwrap(), &pool)
+            .await
+            .unwrap();
+        set_entrant_status(sooner.id, UserId(5), EntrantKind::RACER, EntrantStatus::INTERESTED, &pool).await;
+        set_entrant_status(sooner.id, UserId(6), EntrantKind::RACER, EntrantStatus::CONFIRMED, &pool).await;
+        set_entrant_status(sooner.id, UserId(7), EntrantKind::RACER, EntrantStatus::WITHDRAWN, &pool).await;
+        set_entrant_status(later.id, UserId(5), EntrantKind::RACER, EntrantStatus::INTERESTED, &pool).await;
+
+        let sooner_line = format!(
+            "{}: A Link To The Past - Master Sword, <t:1623294000:F>. 2 racers",
+            sooner
+        );
+        let later_line = format!(
+            "{}: A Link To The Past - Any% NMG No S&Q, <t:1623380400:F>. 1 racer",
+            later
+        );
+        assert_eq!(
+            vec![format!("Upcoming races:\n{}\n{}", sooner_line, later_line)],
+            _races(GUILD, Arguments::new(""), &pool).await
+        );
+        assert_eq!(
+            vec![format!("Upcoming races:\n{}", later_line)],
+            _races(GUILD, Arguments::new("alttp nmg"), &pool).await
+        );
+        assert_eq!(
+            vec![format!("Upcoming races:\n{}", sooner_line)],
+            _races(GUILD, Arguments::new("1 alttp"), &pool).await
+        );
+        assert_eq!(
+            vec![format!(
+                "Past races:\n{}: A Link To The Past - Any% NMG No S&Q, <t:1622602800:F>. 0 racers",
+                done
+            )],
+            _races(GUILD, Arguments::new("past alttp"), &pool).await
+        );
+        assert_eq!(
+            vec!["No races have happened yet.".to_string()],
+            _races(GUILD, Arguments::new("alttp ms past"), &pool).await
+        );
+        assert_eq!(
+            vec!["I don't know what \"nmg\" is. Please use the following format: \
+                !races [game [category]] [past] [count]"
+                .to_string()],
+            _races(GUILD, Arguments::new("nmg"), &pool).await
+        );
+        assert_eq!(
+            vec!["The number of races has to be at least 1.".to_string()],
+            _races(GUILD, Arguments::new("past 0"), &pool).await
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_race() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+
+        let g = get_game("alttp", &pool).await.unwrap();
+        let c = get_category(&g, "nmg", &pool).await.unwrap();
+        let mut r = create_race(GUILD, &g, &c, parse_time("06/09/2021 11:00pm", Eastern).unwrap(), &pool)
+            .await
+            .unwrap();
+        r.notes = Some("bring snacks".to_string());
+        r.set_scheduling_message_id(MessageId(20));
+        r.save(&pool).await.unwrap();
+        set_entrant_status(r.id, UserId(5), EntrantKind::RACER, EntrantStatus::CONFIRMED, &pool).await;
+        set_entrant_status(r.id, UserId(6), EntrantKind::RACER, EntrantStatus::INTERESTED, &pool).await;
+
+        assert_eq!(
+            vec![format!(
+                "{}: A Link To The Past - Any% NMG No S&Q\n\
+                When: <t:1623294000:F> (<t:1623294000:R>)\n\
+                State: SCHEDULED\n\
+                Notes: bring snacks\n\
+                Racers (2): <@5> (confirmed), <@6>\n\
+                Commentators: nobody yet\n\
+                Restreamers: nobody yet\n\
+                Scheduling message: https://discord.com/channels/1/10/20",
+                r
+            )],
+            _race(GUILD, Arguments::new(&r.id.to_string()), Some(ChannelId(10)), Some(ChannelId(11)), &pool)
+                .await
+        );
+        assert_eq!(
+            vec!["No valid race found.".to_string()],
+            _race(GuildId(2), Arguments::new(&r.id.to_string()), None, None, &pool).await
+        );
+        assert_eq!(
+            vec!["Please use the following format: !race <race id>".to_string()],
+            _race(GUILD, Arguments::new("soon"), None, None, &pool).await
+        );
+    }
+
+    #[test]
+    fn test_ordinal() {
+        let ordinals: Vec<String> = vec![1, 2, 3, 4, 11, 12, 13, 21, 22, 101, 111]
+            .into_iter()
+            .map(ordinal)
+            .collect();
+        assert_eq!(
+            vec!["1st", "2nd", "3rd", "4th", "11th", "12th", "13th", "21st", "22nd", "101st", "111th"],
+            ordinals
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_results() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+
+        let g = get_game("alttp", &pool).await.unwrap();
+        let c = get_category(&g, "nmg", &pool).await.unwrap();
+        let mut r = create_race(GUILD, &g, &c, parse_time("06/09/2021 11:00pm", Eastern).unwrap(), &pool)
+            .await
+            .unwrap();
+        for u in 5..=8 {
+            set_entrant_status(r.id, UserId(u), EntrantKind::RACER, EntrantStatus::CONFIRMED, &pool).await;
+        }
+        set_entrant_status(r.id, UserId(9), EntrantKind::COMMENTATOR, EntrantStatus::CONFIRMED, &pool).await;
+        let now = parse_time("06/10/2021 1:00am", Eastern).unwrap();
+
+        assert_eq!(
+            "You aren't in any race that's going on right now.",
+            _done(GUILD, UserId(5), Arguments::new("1:23:45"), now, &pool).await
+        );
+        r.set_state(RaceState::ACTIVE);
+        r.save(&pool).await.unwrap();
+
+        assert_eq!(
+            "Please use the following format: !done <time, like 1:23:45> [race id] [-- comment]",
+            _done(GUILD, UserId(5), Arguments::new(""), now, &pool).await
+        );
+        assert_eq!(
+            "Couldn't understand the time `83`. Try something like 1:23:45.",
+            _done(GUILD, UserId(5), Arguments::new("83"), now, &pool).await
+        );
+        assert_eq!(
+            format!("{} hasn't been going for 2:01:00 yet.", r),
+            _done(GUILD, UserId(5), Arguments::new("2:01:00"), now, &pool).await
+        );
+        assert_eq!(
+            "You aren't in any race that's going on right now.",
+            _done(GUILD, UserId(9), Arguments::new("1:23:45"), now, &pool).await
+        );
+        assert_eq!(
+            format!("You finished {} in 1:30:00 (1st so far).", r),
+            _done(GUILD, UserId(5), Arguments::new("1:30:00"), now, &pool).await
+        );
+        let args = format!("1:23:45 {} -- died to moldorm", r.id);
+        assert_eq!(
+            format!("You finished {} in 1:23:45 (1st so far).", r),
+            _done(GUILD, UserId(6), Arguments::new(&args), now, &pool).await
+        );
+        assert_eq!(
+            format!("You forfeited {}.", r),
+            _forfeit(GUILD, UserId(7), Arguments::new("-- softlocked"), &pool).await
+        );
+        // finishing after forfeiting takes the forfeit back
+        assert_eq!(
+            format!("You finished {} in 1:30:00 (2nd so far).", r),
+            _done(GUILD, UserId(7), Arguments::new("1:30:00"), now, &pool).await
+        );
+        assert_eq!(
+            format!("<@7> forfeited {}.", r),
+            _set_result(GUILD, Arguments::new(&format!("{} <@7> forfeit -- softlocked", r.id)), &pool).await
+        );
+        assert_eq!(
+            vec![(Some(1), false), (Some(2), false), (None, true)],
+            get_results(r.id, &pool)
+                .await
+                .iter()
+                .map(|r| (r.placement, r.forfeit))
+                .collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            vec![
+                format!("Results for {} (A Link To The Past - Any% NMG No S&Q):", r),
+                "1. <@6> 1:23:45 - died to moldorm".to_string(),
+                "2. <@5> 1:30:00".to_string(),
+                "FF <@7> - softlocked".to_string(),
+                "No result: <@8>".to_string(),
+            ],
+            results_table(&r, &pool).await
+        );
+
+        // moderators can fix things after the race is over
+        r.set_state(RaceState::COMPLETED);
+        r.save(&pool).await.unwrap();
+        assert_eq!(
+            format!("{} is not currently active.", r),
+            _done(GUILD, UserId(8), Arguments::new(&format!("1:00:00 {}", r.id)), now, &pool).await
+        );
+        assert_eq!(
+            format!("<@8> finished {} in 1:23:45.", r),
+            _set_result(GUILD, Arguments::new(&format!("{} <@8> 1:23:45", r.id)), &pool).await
+        );
+        assert_eq!(
+            format!("Cleared <@5>'s result for {}.", r),
+            _set_result(GUILD, Arguments::new(&format!("{} <@5> clear", r.id)), &pool).await
+        );
+        assert_eq!(
+            vec![
+                format!("Results for {} (A Link To The Past - Any% NMG No S&Q):", r),
+                "1. <@6> 1:23:45 - died to moldorm".to_string(),
+                "1. <@8> 1:23:45".to_string(),
+                "FF <@7> - softlocked".to_string(),
+                "No result: <@5>".to_string(),
+            ],
+            results_table(&r, &pool).await
+        );
+        assert_eq!(
+            "Please use the following format: !setresult <race id> @user <time|forfeit|clear> [-- comment]",
+            _set_result(GUILD, Arguments::new("<@8> 1:23:45"), &pool).await
+        );
+        assert_eq!(
+            "No valid race found.",
+            _set_result(GuildId(2), Arguments::new(&format!("{} <@8> 1:23:45", r.id)), &pool).await
+        );
+
+        let empty = create_race(GUILD, &g, &c, parse_time("06/12/2021 11:00pm", Eastern).unwrap(), &pool)
+            .await
+            .unwrap();
+        assert!(results_table(&empty, &pool).await.is_empty());
+        assert_eq!(
+            format!("{} hasn't started.", empty),
+            _set_result(GUILD, Arguments::new(&format!("{} <@8> 1:23:45", empty.id)), &pool).await
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_leaderboard() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+
+        let g = get_game("alttp", &pool).await.unwrap();
+        let nmg = get_category(&g, "nmg", &pool).await.unwrap();
+        let ms = get_category(&g, "ms", &pool).await.unwrap();
+        assert_eq!(
+            vec!["Nobody has raced A Link To The Past - Any% NMG No S&Q yet.".to_string()],
+            _leaderboard(GUILD, Arguments::new("alttp nmg"), &pool).await
+        );
+
+        let mut races = vec![];
+        for (cat, time) in [
+            (&nmg, "06/02/2021 11:00pm"),
+            (&nmg, "06/09/2021 11:00pm"),
+            (&ms, "06/09/2021 11:00pm"),
+            (&nmg, "06/16/2021 11:00pm"),
+        ] {
+            races.push(
+                create_race(GUILD, &g, cat, parse_time(time, Eastern).unwrap(), &pool)
+                    .await
+                    .unwrap(),
+            );
+        }
+        for r in races.iter_mut().take(3) {
+            r.set_state(RaceState::COMPLETED);
+            r.save(&pool).await.unwrap();
+        }
+        let (first, second, other, active) = (&races[0], &races[1], &races[2], &races[3]);
+        set_result(first.id, UserId(5), Some(5400), None, &pool).await.unwrap();
+        set_result(first.id, UserId(6), Some(5000), Some("good run"), &pool).await.unwrap();
+        set_result(first.id, UserId(7), None, None, &pool).await.unwrap();
+        set_result(second.id, UserId(5), Some(4800), None, &pool).await.unwrap();
+        set_result(second.id, UserId(6), None, Some("softlocked"), &pool).await.unwrap();
+        // neither a different category nor a race that hasn't finished counts
+        set_result(other.id, UserId(7), Some(1000), None, &pool).await.unwrap();
+        set_result(active.id, UserId(7), Some(1000), None, &pool).await.unwrap();
+
+        assert_eq!(
+            vec![
+                "A Link To The Past - Any% NMG No S&Q leaderboard:\n\
+                1. <@5> PB 1:20:00, 2 races, 1 win, average place 1.5\n\
+                2. <@6> PB 1:23:20, 2 races, 1 win, average place 1.0\n\
+                -. <@7> no finishes, 1 race"
+                    .to_string()
+            ],
+            _leaderboard(GUILD, Arguments::new("alttp nmg"), &pool).await
+        );
+        assert_eq!(
+            vec!["There's only one page.".to_string()],
+            _leaderboard(GUILD, Arguments::new("alttp nmg 2"), &pool).await
+        );
+        assert_eq!(
+            vec!["Please use the following format: !leaderboard <game> <category> [page]".to_string()],
+            _leaderboard(GUILD, Arguments::new("alttp"), &pool).await
+        );
+        assert_eq!(
+            vec!["No matching category found. try !listcategories alttp".to_string()],
+            _leaderboard(GUILD, Arguments::new("alttp 100"), &pool).await
+        );
+        assert_eq!(
+            vec!["Nobody has raced A Link To The Past - Any% NMG No S&Q yet.".to_string()],
+            _leaderboard(GuildId(2), Arguments::new("alttp nmg"), &pool).await
+        );
+
+        assert_eq!(
+            vec![format!(
+                "<@6>'s A Link To The Past - Any% NMG No S&Q PB is 1:23:20, from {} on <t:1622689200:D>.\n\
+                {} <t:1623294000:D>: forfeit - softlocked\n\
+                {} <t:1622689200:D>: 1:23:20, 1st - good run",
+                first, second, first
+            )],
+            _pb(GUILD, UserId(5), Arguments::new("alttp nmg <@6>"), &pool).await
+        );
+        assert_eq!(
+            vec![format!(
+                "<@5>'s A Link To The Past - Any% NMG No S&Q PB is 1:20:00, from {} on <t:1623294000:D>.\n\
+                {} <t:1623294000:D>: 1:20:00, 1st\n\
+                {} <t:1622689200:D>: 1:30:00, 2nd",
+                second, second, first
+            )],
+            _pb(GUILD, UserId(5), Arguments::new("alttp nmg"), &pool).await
+        );
+        assert_eq!(
+            vec![format!(
+                "<@7> hasn't finished A Link To The Past - Any% NMG No S&Q yet.\n\
+                {} <t:1622689200:D>: forfeit",
+                first
+            )],
+            _pb(GUILD, UserId(7), Arguments::new("alttp nmg"), &pool).await
+        );
+        assert_eq!(
+            vec!["<@8> hasn't raced A Link To The Past - Any% NMG No S&Q yet.".to_string()],
+            _pb(GUILD, UserId(8), Arguments::new("alttp nmg"), &pool).await
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_ratings() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+
+        let g = get_game("alttp", &pool).await.unwrap();
+        let c = get_category(&g, "nmg", &pool).await.unwrap();
+        assert_eq!(
+            vec!["Nobody has been rated in A Link To The Past - Any% NMG No S&Q yet.".to_string()],
+            _ratings(GUILD, Arguments::new("alttp nmg"), &pool).await
+        );
+        assert_eq!(
+            vec!["<@5> doesn't have a rating yet.".to_string()],
+            _rating(GUILD, UserId(5), Arguments::new(""), &pool).await
+        );
+
+        let mut races = vec![];
+        for time in ["05/26/2021 11:00pm", "06/02/2021 11:00pm", "06/09/2021 11:00pm"] {
+            let mut r = create_race(GUILD, &g, &c, parse_time(time, Eastern).unwrap(), &pool)
+                .await
+                .unwrap();
+            r.set_state(RaceState::ACTIVE);
+            r.save(&pool).await.unwrap();
+            races.push(r);
+        }
+        let (early, first, second) = (&races[0], &races[1], &races[2]);
+        set_result(first.id, UserId(5), Some(4000), None, &pool).await.unwrap();
+        set_result(first.id, UserId(6), Some(5000), None, &pool).await.unwrap();
+        _end_race(GUILD, Some(first.id), &pool).await;
+        set_result(second.id, UserId(5), None, None, &pool).await.unwrap();
+        set_result(second.id, UserId(6), Some(5000), None, &pool).await.unwrap();
+        // readied up but never said how it went
+        set_entrant_status(second.id, UserId(7), EntrantKind::RACER, EntrantStatus::CONFIRMED, &pool).await;
+        set_entrant_status(second.id, UserId(8), EntrantKind::RACER, EntrantStatus::INTERESTED, &pool).await;
+        _end_race(GUILD, Some(second.id), &pool).await;
+
+        assert_eq!(
+            vec!["A Link To The Past - Any% NMG No S&Q ratings:\n\
+                1. <@5> 1507 (2 races)\n\
+                2. <@6> 1501 (2 races)\n\
+                3. <@7> 1492 (1 race)"
+                .to_string()],
+            _ratings(GUILD, Arguments::new("alttp nmg"), &pool).await
+        );
+        assert_eq!(
+            vec![format!(
+                "<@5> is rated 1507 in A Link To The Past - Any% NMG No S&Q after 2 races, #1 of 3.\n\
+                Race #{} <t:1623294000:D>: 1516 → 1507 (-9)\n\
+                Race #{} <t:1622689200:D>: 1500 → 1516 (+16)",
+                second.id, first.id
+            )],
+            _rating(GUILD, UserId(5), Arguments::new("alttp nmg"), &pool).await
+        );
+        assert_eq!(
+            vec!["<@7>'s ratings:\nA Link To The Past - Any% NMG No S&Q: 1492 after 1 race".to_string()],
+            _rating(GUILD, UserId(5), Arguments::new("<@7>"), &pool).await
+        );
+        assert_eq!(
+            vec!["<@8> hasn't been rated in A Link To The Past - Any% NMG No S&Q yet.".to_string()],
+            _rating(GUILD, UserId(5), Arguments::new("alttp nmg <@8>"), &pool).await
+        );
+        assert_eq!(
+            vec!["Please use the following format: !rating [@user] [game category]".to_string()],
+            _rating(GUILD, UserId(5), Arguments::new("alttp"), &pool).await
+        );
+
+        // an older race finishing late gets rated before the others
+        set_result(early.id, UserId(6), Some(3000), None, &pool).await.unwrap();
+        set_result(early.id, UserId(7), Some(4000), None, &pool).await.unwrap();
+        _end_race(GUILD, Some(early.id), &pool).await;
+        let summary = |changes: Vec<crate::models::RatingChange>| {
+            changes
+                .into_iter()
+                .map(|c| (c.race_id, c.user_id, c.rating_before.round() as i64, c.rating_after.round() as i64))
+                .collect::<Vec<_>>()
+        };
+        let changes = summary(get_rating_changes(GUILD, c.id, &pool).await);
+        assert_eq!(
+            vec![
+                (early.id, "6".to_string(), 1500, 1516),
+                (early.id, "7".to_string(), 1500, 1484),
+                (first.id, "5".to_string(), 1500, 1517),
+                (first.id, "6".to_string(), 1516, 1499),
+            ],
+            changes[..4].to_vec()
+        );
+        assert_eq!(7, changes.len());
+        recompute_ratings(GUILD, c.id, &pool).await.unwrap();
+        assert_eq!(changes, summary(get_rating_changes(GUILD, c.id, &pool).await));
+
+        // fixing a result after the fact fixes the ratings too
+        _set_result(GUILD, Arguments::new(&format!("{} <@7> 1:00:00", second.id)), &pool).await;
+        let changes = summary(get_rating_changes(GUILD, c.id, &pool).await);
+        assert_eq!(7, changes.len());
+        let (_, _, before, after) = changes
+            .iter()
+            .find(|c| c.0 == second.id && c.1 == "7")
+            .unwrap();
+        assert!(after > before);
+        assert!(get_rating_changes(GuildId(2), c.id, &pool).await.is_empty());
     }
 
-    // N.B. any test that hits the database needs this annotation. the flavor="multi_thread" part is
-    // required to allow get_pool() to resolve, which eventually bottoms out doing something
-    // blocking, apparently.
     #[tokio::test(flavor = "multi_thread")]
-    async fn test_race_operations() {
+    async fn test_settings() {
         init();
         let pool = get_pool().await.unwrap();
         initdb(&pool).await;
-        let g = get_game("alttp", &pool).await.unwrap();
-        let c = get_category(&g, "nmg", &pool).await.unwrap();
-        let when = parse_time("06/09/2021 11:10pm").unwrap();
-        let r = create_race(&g, &c, when, &pool).await;
-        assert!(r.is_some());
-        let mut race = r.unwrap();
-        assert_eq!(race.occurs, when.timestamp());
-        assert_eq!(race.category_id, c.id);
-        assert_eq!(race.game_id, g.id);
-        assert_eq!(race.get_scheduling_message_id(), None);
-        assert_eq!(race.get_state(), RaceState::SCHEDULED);
-        let mid = MessageId(u64::MAX);
-        race.set_scheduling_message_id(mid);
-        race.set_state(RaceState::ACTIVE);
-        race.save(&pool).await;
 
-        // it would be reasonable to add a get_race_by_id() kind of message, but I don't think it's
-        // actually useful yet.
-        let q = sqlx::query_as::<_, Race>("SELECT * FROM race WHERE id = ?").bind(race.id);
-        let race_refreshed = q.fetch_one(&pool).await.unwrap();
-        assert_eq!(race, race_refreshed);
+        assert_eq!(None, get_setting("cleanse_message_id", &pool).await);
+        set_setting("cleanse_message_id", "1234", &pool).await;
+        assert_eq!(Some("1234".to_string()), get_setting("cleanse_message_id", &pool).await);
+        set_setting("cleanse_message_id", "5678", &pool).await;
+        assert_eq!(Some("5678".to_string()), get_setting("cleanse_message_id", &pool).await);
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn test_get_races() {
+    async fn test_get_current_signups() {
         init();
         let pool = get_pool().await.unwrap();
         initdb(&pool).await;
 
         let g = get_game("alttp", &pool).await.unwrap();
         let c = get_category(&g, "nmg", &pool).await.unwrap();
+        let scheduled = create_race(GUILD, &g, &c, parse_time("06/09/2021 11:00pm", Eastern).unwrap(), &pool)
+            .await
+            .unwrap();
+        let mut completed = create_race(GUILD, &g, &c, parse_time("06/08/2021 11:00pm", Eastern).unwrap(), &pool)
+            .await
+            .unwrap();
+        completed.set_state(RaceState::COMPLETED);
+        completed.save(&pool).await.unwrap();
+        let withdrawn = create_race(GUILD, &g, &c, parse_time("06/10/2021 11:00pm", Eastern).unwrap(), &pool)
+            .await
+            .unwrap();
 
-        let when = Local::now();
-        let later =
-            (when + CDuration::from_std(Duration::from_secs(60)).unwrap()).with_timezone(&Eastern);
-
-        // let when = Eastern::now() + CDuration::from_std(Duration::from_secs(60)).unwrap();
-        let r = create_race(&g, &c, later, &pool).await;
-        assert!(r.is_some());
+        let user = UserId(7);
+        for r in vec![&scheduled, &completed, &withdrawn] {
+            set_entrant_status(r.id, user, EntrantKind::RACER, EntrantStatus::INTERESTED, &pool).await;
+        }
+        set_entrant_status(withdrawn.id, user, EntrantKind::RACER, EntrantStatus::WITHDRAWN, &pool).await;
+        set_entrant_status(scheduled.id, UserId(8), EntrantKind::RACER, EntrantStatus::INTERESTED, &pool).await;
 
-        let scheduled = get_upcoming_races(Duration::from_secs(120), &pool).await;
-        assert_eq!(1, scheduled.len());
+        let signups = get_current_signups(GUILD, user, &pool).await;
+        assert_eq!(1, signups.len());
+        assert_eq!(scheduled.id, signups[0].race_id);
     }
 
     #[tokio::test(flavor = "multi_thread")]
@@ -1646,21 +8113,21 @@ mod test {
 
         let g = get_game("alttp", &pool).await.unwrap();
         let c = get_category(&g, "nmg", &pool).await.unwrap();
-        let mut r = create_race(&g, &c, Local::now().with_timezone(&Eastern), &pool)
+        let mut r = create_race(GUILD, &g, &c, Local::now().with_timezone(&Eastern), &pool)
             .await
             .unwrap();
 
         assert_eq!(
             format!("{} is not currently active.", r),
-            _end_race(Some(r.id), &pool).await
+            _end_race(GUILD, Some(r.id), &pool).await.0
         );
 
         r.set_state(RaceState::COMPLETED);
-        r.save(&pool).await;
+        r.save(&pool).await.unwrap();
 
         assert_eq!(
             format!("{} is not currently active.", r),
-            _end_race(Some(r.id), &pool).await
+            _end_race(GUILD, Some(r.id), &pool).await.0
         );
     }
 
@@ -1672,7 +8139,7 @@ mod test {
 
         assert_eq!(
             format!("No valid race found."),
-            _end_race(Some(1234), &pool).await
+            _end_race(GUILD, Some(1234), &pool).await.0
         );
     }
 
@@ -1684,16 +8151,16 @@ mod test {
 
         let g = get_game("alttp", &pool).await.unwrap();
         let c = get_category(&g, "nmg", &pool).await.unwrap();
-        let mut r = create_race(&g, &c, Local::now().with_timezone(&Eastern), &pool)
+        let mut r = create_race(GUILD, &g, &c, Local::now().with_timezone(&Eastern), &pool)
             .await
             .unwrap();
 
         r.set_state(RaceState::ACTIVE);
-        r.save(&pool).await;
+        r.save(&pool).await.unwrap();
 
         assert_eq!(
             format!("{} completed.", r),
-            _end_race(Some(r.id), &pool).await
+            _end_race(GUILD, Some(r.id), &pool).await.0
         );
 
         let refreshed = Race::get_by_id(r.id, &pool).await.unwrap();
@@ -1708,14 +8175,14 @@ mod test {
 
         let g = get_game("alttp", &pool).await.unwrap();
         let c = get_category(&g, "nmg", &pool).await.unwrap();
-        let mut r = create_race(&g, &c, Local::now().with_timezone(&Eastern), &pool)
+        let mut r = create_race(GUILD, &g, &c, Local::now().with_timezone(&Eastern), &pool)
             .await
             .unwrap();
 
         r.set_state(RaceState::ACTIVE);
-        r.save(&pool).await;
+        r.save(&pool).await.unwrap();
 
-        assert_eq!(format!("{} completed.", r), _end_race(None, &pool).await);
+        assert_eq!(format!("{} completed.", r), _end_race(GUILD, None, &pool).await.0);
 
         let refreshed = Race::get_by_id(r.id, &pool).await.unwrap();
         assert_eq!(RaceState::COMPLETED, refreshed.get_state());
@@ -1729,15 +8196,16 @@ mod test {
 
         let g = get_game("alttp", &pool).await.unwrap();
         let c = get_category(&g, "nmg", &pool).await.unwrap();
-        let mut r = create_race(&g, &c, Local::now().with_timezone(&Eastern), &pool)
+        let mut r = create_race(GUILD, &g, &c, Local::now().with_timezone(&Eastern), &pool)
             .await
             .unwrap();
 
         r.set_state(RaceState::ACTIVE);
-        r.save(&pool).await;
+        r.save(&pool).await.unwrap();
 
         let time_add = CDuration::from_std(Duration::from_secs(60)).unwrap();
         let mut r2 = create_race(
+            GUILD,
             &g,
             &c,
             (Local::now() + time_add).with_timezone(&Eastern),
@@ -1747,11 +8215,11 @@ mod test {
         .unwrap();
 
         r2.set_state(RaceState::ACTIVE);
-        r2.save(&pool).await;
+        r2.save(&pool).await.unwrap();
 
         assert_eq!(
             format!("No valid race found."),
-            _end_race(None, &pool).await
+            _end_race(GUILD, None, &pool).await.0
         );
 
         let refreshed = Race::get_by_id(r.id, &pool).await.unwrap();
@@ -1768,7 +8236,552 @@ mod test {
 
         assert_eq!(
             format!("No valid race found."),
-            _end_race(None, &pool).await
+            _end_race(GUILD, None, &pool).await.0
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_guild_settings() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+        let config = Config::default();
+        let defaults = GuildSettings::from_config(&config);
+        let roles = vec!["Mods".to_string(), "Race Organizer".to_string()];
+
+        let (reply, updated) =
+            _settings(GUILD, Arguments::new("show"), defaults.clone(), &roles, &pool).await;
+        assert!(reply.contains("* nag_times: 15,30,60"));
+        assert_eq!(None, updated);
+
+        assert_eq!(
+            ("Couldn't change nag_times: nag_times must go from smallest to largest".to_string(), None),
+            _settings(
+                GUILD,
+                Arguments::new("set nag_times 60,30"),
+                defaults.clone(),
+                &roles,
+                &pool
+            )
+            .await
+        );
+        assert!(
+            _settings(GUILD, Arguments::new("bogus"), defaults.clone(), &roles, &pool)
+                .await
+                .0
+                .starts_with("Please use the following format")
+        );
+
+        let (reply, updated) = _settings(
+            GUILD,
+            Arguments::new("set moderator_role_names Mods, Race Organizer"),
+            defaults.clone(),
+            &roles,
+            &pool,
+        )
+        .await;
+        assert_eq!("moderator_role_names is now Mods,Race Organizer", reply);
+        assert_eq!(
+            (
+                "Couldn't change moderator_role_names: there's no role called Typo".to_string(),
+                None
+            ),
+            _settings(
+                GUILD,
+                Arguments::new("set moderator_role_names Mods, Typo"),
+                defaults.clone(),
+                &roles,
+                &pool,
+            )
+            .await
+        );
+        let updated = updated.unwrap();
+        assert_eq!(vec!["Mods", "Race Organizer"], updated.moderator_role_names);
+
+        // saved settings only apply to their own guild
+        assert_eq!(updated, load_guild_settings(GUILD, &config, &pool).await);
+        assert_eq!(defaults, load_guild_settings(GuildId(2), &config, &pool).await);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_autocomplete() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+        let data = |json: &str| serde_json::from_str(json).unwrap();
+
+        assert_eq!(
+            vec![("A Link To The Past (alttp)".to_string(), "alttp".to_string())],
+            _autocomplete(
+                &data(r#"{"name":"newrace","options":[{"name":"game","value":"LINK","focused":true}]}"#),
+                &pool
+            )
+            .await
+        );
+        assert_eq!(
+            vec![("Master Sword (ms)".to_string(), "ms".to_string())],
+            _autocomplete(
+                &data(
+                    r#"{"name":"newrace","options":[{"name":"game","value":"alttp"},
+                    {"name":"category","value":"sword","focused":true}]}"#
+                ),
+                &pool
+            )
+            .await
+        );
+        // categories need to know the game first
+        assert!(_autocomplete(
+            &data(r#"{"name":"newrace","options":[{"name":"category","value":"","focused":true}]}"#),
+            &pool
+        )
+        .await
+        .is_empty());
+    }
+
+    #[test]
+    fn test_button_ids() {
+        for action in vec![
+            ButtonAction::SignUp(EntrantKind::RACER),
+            ButtonAction::SignUp(EntrantKind::RESTREAMER),
+            ButtonAction::Withdraw,
+            ButtonAction::Confirm,
+        ] {
+            let id = button_id(12, &action);
+            assert!(id.len() <= 100, "custom ids are limited to 100 characters");
+            assert_eq!(Some((12, action)), parse_button_id(&id));
+        }
+        assert_eq!(None, parse_button_id("signup:12"));
+        assert_eq!(None, parse_button_id("signup:12:JUGGLER"));
+        assert_eq!(None, parse_button_id("withdraw:twelve"));
+        assert_eq!(None, parse_button_id("confirm:12:extra"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_signup_status() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+
+        let g = get_game("alttp", &pool).await.unwrap();
+        let c = get_category(&g, "nmg", &pool).await.unwrap();
+        let r = create_race(GUILD, &g, &c, parse_time("06/09/2021 11:00pm", Eastern).unwrap(), &pool)
+            .await
+            .unwrap();
+        assert!(r.signup_buttons);
+        assert_eq!(format!("You're not signed up for {}.", r), signup_status(&r, UserId(5), &pool).await);
+
+        set_entrant_status(r.id, UserId(5), EntrantKind::RACER, EntrantStatus::CONFIRMED, &pool).await;
+        set_entrant_status(r.id, UserId(5), EntrantKind::RESTREAMER, EntrantStatus::INTERESTED, &pool).await;
+        set_entrant_status(r.id, UserId(5), EntrantKind::COMMENTATOR, EntrantStatus::WITHDRAWN, &pool).await;
+        assert_eq!(
+            format!("You're signed up for {} as: racer (confirmed), restreamer.", r),
+            signup_status(&r, UserId(5), &pool).await
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_series() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+        let week = Duration::from_secs(7 * 24 * 60 * 60);
+
+        assert_eq!(
+            "There are no race series running.",
+            _list_series(GUILD, parse_time("03/01/2021 12:00pm", Eastern).unwrap(), &pool).await
+        );
+        let now = parse_time("03/01/2021 12:00pm", Eastern).unwrap();
+        assert!(_new_series(GUILD, Arguments::new("alttp nmg fortnightly 03/06/2021 8:00pm"), now, 7, Eastern, &pool)
+            .await
+            .starts_with("Please use the following format"));
+        assert!(_new_series(GUILD, Arguments::new("alttp nmg monthly 03/29/2021 8:00pm"), now, 7, Eastern, &pool)
+            .await
+            .starts_with("Monthly series happen in the same week of every month"));
+        assert!(get_active_series(GUILD, &pool).await.is_empty());
+
+        let reply = _new_series(
+            GUILD,
+            Arguments::new("alttp nmg Weekly 03/06/2021 8:00pm -- bring snacks"),
+            now, 7, Eastern,
+            &pool,
+        )
+        .await;
+        let series = get_active_series(GUILD, &pool).await.pop().unwrap();
+        assert_eq!(
+            format!(
+                "{} created: A Link To The Past - Any% NMG No S&Q every week, starting <t:1615078800:F>. \
+                Races get posted 7 days ahead of time.",
+                series
+            ),
+            reply
+        );
+
+        let races = _schedule_series(GUILD, parse_time("03/05/2021 12:00pm", Eastern).unwrap(), week, &pool).await;
+        assert_eq!(vec![1615078800], races.iter().map(|r| r.occurs).collect::<Vec<i64>>());
+        assert_eq!(Some(series.id), races[0].series_id);
+        assert_eq!(Some("bring snacks".to_string()), races[0].notes);
+        assert!(races[0].signup_buttons);
+        // nothing new yet
+        assert!(_schedule_series(GUILD, parse_time("03/05/2021 12:01pm", Eastern).unwrap(), week, &pool)
+            .await
+            .is_empty());
+
+        let races = _schedule_series(GUILD, parse_time("03/13/2021 12:00pm", Eastern).unwrap(), week, &pool).await;
+        assert_eq!(vec![1615683600], races.iter().map(|r| r.occurs).collect::<Vec<i64>>());
+        // DST starts on 3/14, and the race should still be at 8pm Eastern
+        let races = _schedule_series(GUILD, parse_time("03/15/2021 12:00pm", Eastern).unwrap(), week, &pool).await;
+        assert_eq!(vec![1616284800], races.iter().map(|r| r.occurs).collect::<Vec<i64>>());
+
+        // a race somebody already scheduled by hand doesn't get duplicated
+        let g = get_game("alttp", &pool).await.unwrap();
+        let c = get_category(&g, "nmg", &pool).await.unwrap();
+        create_race(GUILD, &g, &c, parse_time("03/27/2021 8:00pm", Eastern).unwrap(), &pool)
+            .await
+            .unwrap();
+        assert!(_schedule_series(GUILD, parse_time("03/22/2021 12:00pm", Eastern).unwrap(), week, &pool)
+            .await
+            .is_empty());
+
+        assert_eq!(
+            format!(
+                "Race series:\n{}: A Link To The Past - Any% NMG No S&Q every week. \
+                Next race: <t:1616889600:F> Notes: bring snacks",
+                series
+            ),
+            _list_series(GUILD, parse_time("03/22/2021 12:00pm", Eastern).unwrap(), &pool).await
+        );
+
+        assert_eq!(
+            "No valid series found.",
+            _end_series(GuildId(2), Arguments::new(&series.id.to_string()), &pool).await
+        );
+        assert!(_end_series(GUILD, Arguments::new(&series.id.to_string()), &pool)
+            .await
+            .starts_with(&format!("{} ended.", series)));
+        assert_eq!(
+            format!("{} has already ended.", series),
+            _end_series(GUILD, Arguments::new(&series.id.to_string()), &pool).await
+        );
+        assert!(_schedule_series(GUILD, parse_time("04/01/2021 12:00pm", Eastern).unwrap(), week, &pool)
+            .await
+            .is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_timezone() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+        let user = UserId(7);
+
+        assert_eq!(Eastern, get_user_timezone(user, &pool).await);
+        assert!(_timezone(user, Arguments::new(""), &pool)
+            .await
+            .starts_with("Your timezone is US/Eastern."));
+        assert!(_timezone(user, Arguments::new("Mars/Olympus_Mons"), &pool)
+            .await
+            .starts_with("I don't know a timezone called Mars/Olympus_Mons."));
+        assert_eq!(
+            "Your timezone is now America/Chicago.",
+            _timezone(user, Arguments::new("america/chicago"), &pool).await
+        );
+        assert_eq!(chrono_tz::America::Chicago, get_user_timezone(user, &pool).await);
+        assert_eq!(
+            "Your timezone is now Europe/Berlin.",
+            _timezone(user, Arguments::new("Europe/Berlin"), &pool).await
+        );
+        assert_eq!(chrono_tz::Europe::Berlin, get_user_timezone(user, &pool).await);
+        assert_eq!(Eastern, get_user_timezone(UserId(8), &pool).await);
+    }
+
+    /// Opens rooms without going anywhere. Every room is in whatever state `room` says, or
+    /// can't be found if that's None.
+    struct MockRacetime {
+        opened: std::sync::Mutex<Vec<NewRoom>>,
+        room: std::sync::Mutex<Option<RoomData>>,
+    }
+
+    impl RacetimeClient for MockRacetime {
+        fn start_race<'a>(&'a self, room: &'a NewRoom) -> BoxFuture<'a, Result<String, RacetimeError>> {
+            let mut opened = self.opened.lock().unwrap();
+            opened.push(room.clone());
+            let url = format!("https://racetime.test/{}/room-{}", room.category, opened.len());
+            Box::pin(async move { Ok(url) })
+        }
+
+        fn get_race<'a>(&'a self, _url: &'a str) -> BoxFuture<'a, Result<RoomData, RacetimeError>> {
+            let room = self.room.lock().unwrap().clone();
+            Box::pin(async move {
+                room.ok_or(RacetimeError::Rejected {
+                    status: 404,
+                    body: "".to_string(),
+                })
+            })
+        }
+    }
+
+    fn room_data(status: &str, entrants: Value) -> Option<RoomData> {
+        Some(serde_json::from_value(json!({"status": {"value": status}, "entrants": entrants})).unwrap())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_racetime() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+
+        assert_eq!(
+            "Category updated: Any% NMG No S&Q (nmg). Its races get rooms in the alttp category on \
+            racetime.gg, with the goal Any% NMG No S&Q.",
+            _edit_category(Arguments::new("alttp nmg racetime alttp"), &pool).await
+        );
+        assert_eq!(
+            "Category updated: Any% NMG No S&Q (nmg). Its races get rooms in the alttp category on \
+            racetime.gg, with the goal Beat the game.",
+            _edit_category(Arguments::new("alttp nmg goal Beat the game"), &pool).await
+        );
+        assert_eq!(
+            "Category updated: Master Sword (ms). Its races don't get racetime.gg rooms.",
+            _edit_category(Arguments::new("alttp ms racetime none"), &pool).await
+        );
+
+        let client = MockRacetime {
+            opened: Default::default(),
+            room: Default::default(),
+        };
+        let g = get_game("alttp", &pool).await.unwrap();
+        let nmg = get_category(&g, "nmg", &pool).await.unwrap();
+        let ms = get_category(&g, "ms", &pool).await.unwrap();
+        let when = parse_time("06/09/2021 11:00pm", Eastern).unwrap();
+        let mut r = create_race(GUILD, &g, &nmg, when, &pool).await.unwrap();
+        r.notes = Some("for new runners".to_string());
+        assert_eq!(
+            Some("https://racetime.test/alttp/room-1".to_string()),
+            open_racetime_room(&mut r, &client, &pool).await
+        );
+        assert_eq!(Some("https://racetime.test/alttp/room-1".to_string()), r.racetime_room);
+        assert_eq!(
+            vec![NewRoom {
+                category: "alttp".to_string(),
+                goal: Some("Beat the game".to_string()),
+                custom_goal: "Any% NMG No S&Q".to_string(),
+                info: format!("{}: A Link To The Past - Any% NMG No S&Q (for new runners)", r),
+            }],
+            *client.opened.lock().unwrap()
+        );
+        let mut other = create_race(GUILD, &g, &ms, when, &pool).await.unwrap();
+        assert_eq!(None, open_racetime_room(&mut other, &client, &pool).await);
+        assert_eq!(1, client.opened.lock().unwrap().len());
+
+        r.set_state(RaceState::ACTIVE);
+        r.save(&pool).await.unwrap();
+        for u in 5..=7 {
+            set_entrant_status(r.id, UserId(u), EntrantKind::RACER, EntrantStatus::CONFIRMED, &pool).await;
+        }
+
+        assert_eq!(
+            "You haven't linked a racetime.gg account. Link one with !racetime <name>, e.g. \
+            `!racetime Fox#1234`, so your results in race rooms get recorded here.",
+            _racetime(UserId(5), false, Arguments::new(""), &pool).await
+        );
+        assert_eq!(
+            "Your racetime.gg account is now Fox#1234. Your results in race rooms will be recorded here.",
+            _racetime(UserId(5), false, Arguments::new("Fox#1234"), &pool).await
+        );
+        assert_eq!(
+            "Your racetime.gg account is Fox#1234. Change it with !racetime <name>, e.g. `!racetime Fox#1234`.",
+            _racetime(UserId(5), false, Arguments::new(""), &pool).await
+        );
+        assert_eq!(
+            "Someone else has already linked fox#1234. Ask a moderator if that's wrong.",
+            _racetime(UserId(6), false, Arguments::new("fox#1234"), &pool).await
+        );
+        assert_eq!(
+            "Your racetime.gg account is now bird. Your results in race rooms will be recorded here.",
+            _racetime(UserId(6), false, Arguments::new("bird"), &pool).await
+        );
+        assert_eq!(
+            "Your racetime.gg account is now Fox. Your results in race rooms will be recorded here.",
+            _racetime(UserId(7), false, Arguments::new("Fox"), &pool).await
+        );
+
+        // nothing happens while the room can't be found or is still going
+        let mut failures = HashMap::new();
+        assert!(check_racetime_room(GUILD, &mut r, &client, &mut failures, &pool).await.is_none());
+        assert_eq!(Some(&1), failures.get(&r.id));
+        *client.room.lock().unwrap() = room_data("in_progress", json!([]));
+        assert!(check_racetime_room(GUILD, &mut r, &client, &mut failures, &pool).await.is_none());
+        assert!(failures.is_empty());
+        assert_eq!(RaceState::ACTIVE, get_active_races(GUILD, &pool).await[0].get_state());
+
+        *client.room.lock().unwrap() = room_data(
+            "finished",
+            json!([
+                {
+                    "user": {"name": "Fox", "full_name": "Fox#1234"},
+                    "status": {"value": "done"},
+                    "finish_time": "P0DT01H23M45.678901S",
+                    "comment": "gg"
+                },
+                {
+                    "user": {"name": "Bird", "full_name": "Bird"},
+                    "status": {"value": "dnf"},
+                    "finish_time": null,
+                    "comment": null
+                },
+                {
+                    "user": {"name": "Stranger", "full_name": "Stranger#9999"},
+                    "status": {"value": "done"},
+                    "finish_time": "P0DT01H00M00S",
+                    "comment": null
+                }
+            ]),
+        );
+        let ended = check_racetime_room(GUILD, &mut r, &client, &mut failures, &pool).await.unwrap();
+        assert_eq!(r.id, ended.id);
+        assert_eq!(RaceState::COMPLETED, ended.get_state());
+        assert_eq!(
+            vec![
+                format!("Results for {} (A Link To The Past - Any% NMG No S&Q):", r),
+                "1. <@5> 1:23:45 - gg".to_string(),
+                "FF <@6>".to_string(),
+                "No result: <@7>".to_string(),
+            ],
+            results_table(&ended, &pool).await
+        );
+
+        // moderators can hand an account to whoever it really belongs to
+        assert_eq!(
+            "Only moderators can link someone else's racetime.gg account.",
+            _racetime(UserId(7), false, Arguments::new("Fox#1234 <@7>"), &pool).await
+        );
+        assert_eq!(
+            "<@7>'s racetime.gg account is now fox#1234.",
+            _racetime(UserId(1), true, Arguments::new("fox#1234 <@7>"), &pool).await
+        );
+        assert_eq!(Some(UserId(7)), get_racetime_link("Fox#1234", &pool).await);
+        assert_eq!(None, get_racetime_name(UserId(5), &pool).await);
+
+        // cancelled rooms get forgotten, so the race ends on its own
+        let mut r2 = create_race(GUILD, &g, &nmg, when + CDuration::days(1), &pool).await.unwrap();
+        open_racetime_room(&mut r2, &client, &pool).await.unwrap();
+        r2.set_state(RaceState::ACTIVE);
+        r2.save(&pool).await.unwrap();
+        *client.room.lock().unwrap() = room_data("cancelled", json!([]));
+        assert!(check_racetime_room(GUILD, &mut r2, &client, &mut failures, &pool).await.is_none());
+        let active = get_active_races(GUILD, &pool).await;
+        assert_eq!(1, active.len());
+        assert_eq!(r2.id, active[0].id);
+        assert_eq!(None, active[0].racetime_room);
+
+        // and so do rooms that keep failing to load
+        open_racetime_room(&mut r2, &client, &pool).await.unwrap();
+        r2.save(&pool).await.unwrap();
+        *client.room.lock().unwrap() = None;
+        for _ in 1..MAX_ROOM_FAILURES {
+            assert!(check_racetime_room(GUILD, &mut r2, &client, &mut failures, &pool).await.is_none());
+        }
+        assert!(get_active_races(GUILD, &pool).await[0].racetime_room.is_some());
+        assert!(check_racetime_room(GUILD, &mut r2, &client, &mut failures, &pool).await.is_none());
+        assert_eq!(None, get_active_races(GUILD, &pool).await[0].racetime_room);
+        assert!(failures.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_race_timer() {
+        init();
+        let pool = get_pool().await.unwrap();
+        initdb(&pool).await;
+
+        assert_eq!(
+            "Category updated: Any% NMG No S&Q (nmg). Its races get ended automatically after 4:30:00.",
+            _edit_category(Arguments::new("alttp nmg max 4h30m"), &pool).await
         );
+        assert_eq!(
+            "Category updated: Any% NMG No S&Q (nmg). Its races usually take about 1:45:00 and get ended \
+            automatically after 4:30:00.",
+            _edit_category(Arguments::new("alttp nmg expected 1:45:00"), &pool).await
+        );
+        assert_eq!(
+            "Races can't be expected to take 1:45:00 if they get ended after 1:00:00.",
+            _edit_category(Arguments::new("alttp nmg max 1h"), &pool).await
+        );
+        assert_eq!(
+            "Couldn't understand the duration `forever`. Try something like 4h30m or 4:30:00.",
+            _edit_category(Arguments::new("alttp nmg max forever"), &pool).await
+        );
+        assert_eq!(
+            "Category updated: Master Sword (ms). Its races get ended automatically after 3:00:00 (the default).",
+            _edit_category(Arguments::new("alttp ms max none"), &pool).await
+        );
+
+        let g = get_game("alttp", &pool).await.unwrap();
+        let nmg = get_category(&g, "nmg", &pool).await.unwrap();
+        let ms = get_category(&g, "ms", &pool).await.unwrap();
+        let when = parse_time("06/09/2021 11:00pm", Eastern).unwrap();
+        let mut r = create_race(GUILD, &g, &nmg, when, &pool).await.unwrap();
+        let mut short = create_race(GUILD, &g, &ms, when, &pool).await.unwrap();
+        for race in [&mut r, &mut short] {
+            race.set_state(RaceState::ACTIVE);
+            race.save(&pool).await.unwrap();
+        }
+        for u in 5..=6 {
+            set_entrant_status(r.id, UserId(u), EntrantKind::RACER, EntrantStatus::CONFIRMED, &pool).await;
+        }
+        set_entrant_status(r.id, UserId(7), EntrantKind::RACER, EntrantStatus::INTERESTED, &pool).await;
+
+        // nobody used !start, so the timers go from the scheduled time
+        let hours_in = |h: i64| when.timestamp() + h * 3600;
+        assert_eq!(RaceTimer::Running, race_timer(&r, hours_in(4), &pool).await);
+        assert_eq!(RaceTimer::EndingSoon(hours_in(3)), race_timer(&short, hours_in(3) - 60, &pool).await);
+        assert_eq!(RaceTimer::Over, race_timer(&short, hours_in(3), &pool).await);
+        assert_eq!(
+            RaceTimer::EndingSoon(hours_in(4) + 1800),
+            race_timer(&r, hours_in(4) + 900, &pool).await
+        );
+
+        let now = hours_in(0) + 600;
+        assert_eq!(
+            "Please use the following format: !start [race id]",
+            _start(GUILD, UserId(5), false, Arguments::new("soon"), now, &pool).await.0
+        );
+        assert_eq!(
+            format!("You aren't racing in {}.", short),
+            _start(GUILD, UserId(5), false, Arguments::new(&short.id.to_string()), now, &pool).await.0
+        );
+        assert_eq!(
+            format!("Only confirmed racers can start {}. Confirm first, or ask a moderator.", r),
+            _start(GUILD, UserId(7), false, Arguments::new(""), now, &pool).await.0
+        );
+        let (reply, started) = _start(GUILD, UserId(5), false, Arguments::new(""), now, &pool).await;
+        assert_eq!(format!("{} starts <t:{}:R>. Good luck!", r, now + 10), reply);
+        assert_eq!(Some(now + 10), started.unwrap().started);
+        assert_eq!(
+            format!("{} already started <t:{}:R>.", r, now + 10),
+            _start(GUILD, UserId(6), false, Arguments::new(""), now, &pool).await.0
+        );
+        // moderators can start races they aren't in
+        assert_eq!(
+            format!("{} starts <t:{}:R>. Good luck!", short, now + 10),
+            _start(GUILD, UserId(9), true, Arguments::new(&short.id.to_string()), now, &pool).await.0
+        );
+
+        // finish times and the timer go from the countdown, not the scheduled time
+        let r = get_race(GUILD, r.id, &pool).await.unwrap();
+        assert_eq!(RaceTimer::Running, race_timer(&r, hours_in(4) + 900, &pool).await);
+        assert_eq!(RaceTimer::Over, race_timer(&r, now + 10 + 4 * 3600 + 1800, &pool).await);
+        let done_at = Eastern.timestamp(now + 10 + 3600, 0);
+        assert_eq!(
+            format!("{} hasn't been going for 1:00:01 yet.", r),
+            _done(GUILD, UserId(5), Arguments::new("1:00:01"), done_at, &pool).await
+        );
+        assert_eq!(
+            format!("You finished {} in 1:00:00 (1st so far).", r),
+            _done(GUILD, UserId(5), Arguments::new("1:00:00"), done_at, &pool).await
+        );
+
+        let lines = _race(GUILD, Arguments::new(&r.id.to_string()), None, None, &pool).await.join("\n");
+        assert!(lines.contains(&format!("\nStarted: <t:{}:T> (<t:{}:R>)\n", now + 10, now + 10)), "{}", lines);
+        assert!(lines.contains("\nExpected length: about 1:45:00\n"), "{}", lines);
     }
 }
@@ -0,0 +1,888 @@
+//! Slash commands and buttons. twilight 0.3 predates interactions, so we do them by hand: the
+//! gateway hands us INTERACTION_CREATE dispatches as raw shard payloads, and we answer with raw
+//! HTTP requests. Commands still run through the same code as their `!` versions; the options
+//! just get turned back into the argument string that code already knows how to parse.
+
+use custom_error::custom_error;
+use hyper::Method;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use twilight_http::request::Request;
+use twilight_http::routing::Path;
+use twilight_http::Client;
+use twilight_model::id::{ApplicationId, ChannelId, GuildId, MessageId, RoleId, UserId};
+
+// interaction types
+pub(crate) const APPLICATION_COMMAND: u8 = 2;
+pub(crate) const MESSAGE_COMPONENT: u8 = 3;
+pub(crate) const APPLICATION_COMMAND_AUTOCOMPLETE: u8 = 4;
+
+// interaction callback types
+const CHANNEL_MESSAGE_WITH_SOURCE: u8 = 4;
+const DEFERRED_CHANNEL_MESSAGE_WITH_SOURCE: u8 = 5;
+const APPLICATION_COMMAND_AUTOCOMPLETE_RESULT: u8 = 8;
+
+// message flags
+const EPHEMERAL: u64 = 1 << 6;
+
+// component types
+const ACTION_ROW: u8 = 1;
+const BUTTON: u8 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ButtonStyle {
+    Primary = 1,
+    Secondary = 2,
+    Success = 3,
+    Danger = 4,
+}
+
+// discord won't show more suggestions than this
+const MAX_CHOICES: usize = 25;
+
+custom_error! { pub(crate) InteractionError
+    Http{source: twilight_http::Error} = "Error talking to Discord: {source}",
+    Rejected{status: u16, body: String} = "Discord rejected the request ({status}): {body}",
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct Interaction {
+    pub(crate) id: String,
+    pub(crate) application_id: ApplicationId,
+    #[serde(rename = "type")]
+    pub(crate) kind: u8,
+    pub(crate) token: String,
+    pub(crate) guild_id: Option<GuildId>,
+    pub(crate) channel_id: Option<ChannelId>,
+    /// Only present for interactions in a guild
+    pub(crate) member: Option<InteractionMember>,
+    pub(crate) data: Option<CommandData>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct InteractionMember {
+    pub(crate) user: InteractionUser,
+    pub(crate) roles: Vec<RoleId>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct InteractionUser {
+    pub(crate) id: UserId,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct CommandData {
+    /// The slash command's name. Empty for buttons.
+    #[serde(default)]
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) options: Vec<CommandOption>,
+    /// Which button was pressed
+    #[serde(default)]
+    pub(crate) custom_id: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct CommandOption {
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) value: Option<Value>,
+    /// Set on the option being typed in, for autocomplete
+    #[serde(default)]
+    pub(crate) focused: bool,
+}
+
+impl CommandData {
+    /// The option's value as text, however discord sent it
+    pub(crate) fn get(&self, name: &str) -> Option<String> {
+        let value = self
+            .options
+            .iter()
+            .find(|o| o.name == name)?
+            .value
+            .as_ref()?;
+        match value {
+            Value::String(s) => Some(s.clone()),
+            Value::Null => None,
+            other => Some(other.to_string()),
+        }
+    }
+
+    /// The option autocomplete was asked for
+    pub(crate) fn focused(&self) -> Option<&CommandOption> {
+        self.options.iter().find(|o| o.focused)
+    }
+}
+
+#[derive(Deserialize)]
+struct Dispatch {
+    t: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct InteractionDispatch {
+    d: Interaction,
+}
+
+/// Picks interactions out of the raw gateway traffic. Everything else is handled by twilight.
+pub(crate) fn parse_interaction(bytes: &[u8]) -> Option<Interaction> {
+    let dispatch = serde_json::from_slice::<Dispatch>(bytes).ok()?;
+    if dispatch.t.as_deref() != Some("INTERACTION_CREATE") {
+        return None;
+    }
+    match serde_json::from_slice::<InteractionDispatch>(bytes) {
+        Ok(i) => Some(i.d),
+        Err(e) => {
+            warn!("Error parsing interaction: {}", e);
+            None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum OptionKind {
+    String,
+    Integer,
+    // passed along as a `--<name>` flag when true
+    Boolean,
+    User,
+}
+
+impl OptionKind {
+    fn api_type(&self) -> u8 {
+        match self {
+            OptionKind::String => 3,
+            OptionKind::Integer => 4,
+            OptionKind::Boolean => 5,
+            OptionKind::User => 6,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct SlashOption {
+    name: &'static str,
+    description: &'static str,
+    kind: OptionKind,
+    required: bool,
+    autocomplete: bool,
+    choices: Vec<&'static str>,
+    // put in front of the value when building the argument string, e.g. `--` before race notes
+    prefix: Option<&'static str>,
+}
+
+impl SlashOption {
+    fn new(name: &'static str, description: &'static str, kind: OptionKind) -> Self {
+        SlashOption {
+            name,
+            description,
+            kind,
+            required: false,
+            autocomplete: false,
+            choices: vec![],
+            prefix: None,
+        }
+    }
+
+    fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    fn autocomplete(mut self) -> Self {
+        self.autocomplete = true;
+        self
+    }
+
+    fn choices(mut self, choices: &[&'static str]) -> Self {
+        self.choices = choices.to_vec();
+        self
+    }
+
+    fn prefix(mut self, prefix: &'static str) -> Self {
+        self.prefix = Some(prefix);
+        self
+    }
+
+    fn to_json(&self) -> Value {
+        let mut option = json!({
+            "type": self.kind.api_type(),
+            "name": self.name,
+            "description": self.description,
+            "required": self.required,
+        });
+        if self.autocomplete {
+            option["autocomplete"] = json!(true);
+        }
+        if !self.choices.is_empty() {
+            option["choices"] = self
+                .choices
+                .iter()
+                .map(|c| json!({"name": c, "value": c}))
+                .collect();
+        }
+        option
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct SlashCommand {
+    pub(crate) name: &'static str,
+    description: &'static str,
+    options: Vec<SlashOption>,
+}
+
+impl SlashCommand {
+    fn new(name: &'static str, description: &'static str, options: Vec<SlashOption>) -> Self {
+        SlashCommand {
+            name,
+            description,
+            options,
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "description": self.description,
+            "options": self.options.iter().map(SlashOption::to_json).collect::<Vec<Value>>(),
+        })
+    }
+
+    /// Rebuilds the arguments the `!` version of this command would have gotten
+    pub(crate) fn to_args(&self, data: &CommandData) -> String {
+        let mut args = vec![];
+        for option in &self.options {
+            let value = match data.get(option.name) {
+                Some(v) => v,
+                None => {
+                    continue;
+                }
+            };
+            if let Some(prefix) = option.prefix {
+                args.push(prefix.to_string());
+            }
+            match option.kind {
+                OptionKind::User => args.push(format!("<@{}>", value)),
+                OptionKind::Boolean => {
+                    if value == "true" {
+                        args.push(format!("--{}", option.name));
+                    }
+                }
+                OptionKind::String | OptionKind::Integer => args.push(value),
+            }
+        }
+        args.join(" ")
+    }
+}
+
+/// Every slash command we register. Option order matches the argument order of the `!` command.
+pub(crate) fn slash_commands(setting_keys: &[&'static str]) -> Vec<SlashCommand> {
+    let game = || {
+        SlashOption::new("game", "Game alias", OptionKind::String)
+            .required()
+            .autocomplete()
+    };
+    let category = || {
+        SlashOption::new("category", "Category alias", OptionKind::String)
+            .required()
+            .autocomplete()
+    };
+    let race_id = || SlashOption::new("race_id", "Race number", OptionKind::Integer).required();
+    let time = || {
+        SlashOption::new(
+            "time",
+            "In your timezone (see /timezone), like 6/9/2021 11:00pm",
+            OptionKind::String,
+        )
+        .required()
+    };
+    let force = || {
+        SlashOption::new(
+            "force",
+            "Schedule it even if the time looks wrong",
+            OptionKind::Boolean,
+        )
+    };
+    vec![
+        SlashCommand::new("listgames", "List the games races can be for", vec![]),
+        SlashCommand::new("listcategories", "List a game's categories", vec![game()]),
+        SlashCommand::new(
+            "newrace",
+            "Schedule a race",
+            vec![
+                game(),
+                category(),
+                time(),
+                force(),
+                SlashOption::new("notes", "Anything racers should know", OptionKind::String)
+                    .prefix("--"),
+            ],
+        ),
+        SlashCommand::new(
+            "racenote",
+            "Set or clear a race's notes",
+            vec![
+                race_id(),
+                SlashOption::new("notes", "Leave empty to clear", OptionKind::String),
+            ],
+        ),
+        SlashCommand::new(
+            "endrace",
+            "End a race",
+            vec![SlashOption::new(
+                "race_id",
+                "Defaults to the active race",
+                OptionKind::Integer,
+            )],
+        ),
+        SlashCommand::new(
+            "cancelrace",
+            "Cancel a race",
+            vec![
+                race_id(),
+                SlashOption::new("reason", "Shown to signed up racers", OptionKind::String),
+            ],
+        ),
+        SlashCommand::new(
+            "reschedule",
+            "Move a race to a new time",
+            vec![race_id(), time(), force()],
+        ),
+        SlashCommand::new(
+            "newseries",
+            "Schedule a race that happens regularly",
+            vec![
+                game(),
+                category(),
+                SlashOption::new("recurrence", "How often", OptionKind::String)
+                    .required()
+                    .choices(&["weekly", "biweekly", "monthly"]),
+                SlashOption::new(
+                    "time",
+                    "When the first race is, like 6/12/2021 8:00pm",
+                    OptionKind::String,
+                )
+                .required(),
+                SlashOption::new("notes", "Anything racers should know", OptionKind::String)
+                    .prefix("--"),
+            ],
+        ),
+        SlashCommand::new("listseries", "List the races that happen regularly", vec![]),
+        SlashCommand::new(
+            "endseries",
+            "Stop scheduling a series' races",
+            vec![SlashOption::new("series_id", "Series number", OptionKind::Integer).required()],
+        ),
+        SlashCommand::new("resync", "Fix up race roles", vec![]),
+        SlashCommand::new(
+            "races",
+            "List upcoming or past races",
+            vec![
+                SlashOption::new("game", "Only this game", OptionKind::String).autocomplete(),
+                SlashOption::new("category", "Only this category", OptionKind::String)
+                    .autocomplete(),
+                SlashOption::new("when", "Defaults to upcoming", OptionKind::String)
+                    .choices(&["upcoming", "past"]),
+                SlashOption::new("count", "How many races to show", OptionKind::Integer),
+            ],
+        ),
+        SlashCommand::new("race", "Show everything about a race", vec![race_id()]),
+        SlashCommand::new(
+            "start",
+            "Count down and start the race timer",
+            vec![SlashOption::new(
+                "race_id",
+                "Only needed if you're in more than one race",
+                OptionKind::Integer,
+            )],
+        ),
+        SlashCommand::new(
+            "done",
+            "Report your finish time",
+            vec![
+                SlashOption::new("time", "Like 1:23:45", OptionKind::String).required(),
+                SlashOption::new(
+                    "race_id",
+                    "Only needed if you're in more than one race",
+                    OptionKind::Integer,
+                ),
+                SlashOption::new("comment", "How it went", OptionKind::String).prefix("--"),
+            ],
+        ),
+        SlashCommand::new(
+            "forfeit",
+            "Forfeit the race you're in",
+            vec![
+                SlashOption::new(
+                    "race_id",
+                    "Only needed if you're in more than one race",
+                    OptionKind::Integer,
+                ),
+                SlashOption::new("comment", "What happened", OptionKind::String).prefix("--"),
+            ],
+        ),
+        SlashCommand::new(
+            "setresult",
+            "Change someone's result",
+            vec![
+                race_id(),
+                SlashOption::new("user", "Whose result", OptionKind::User).required(),
+                SlashOption::new(
+                    "result",
+                    "A time like 1:23:45, forfeit, or clear",
+                    OptionKind::String,
+                )
+                .required(),
+                SlashOption::new("comment", "Shown with the result", OptionKind::String)
+                    .prefix("--"),
+            ],
+        ),
+        SlashCommand::new(
+            "leaderboard",
+            "Rank everyone who has raced a category",
+            vec![
+                game(),
+                category(),
+                SlashOption::new("page", "Defaults to the first", OptionKind::Integer),
+            ],
+        ),
+        SlashCommand::new(
+            "pb",
+            "Show someone's best time and recent races in a category",
+            vec![
+                game(),
+                category(),
+                SlashOption::new("user", "Defaults to you", OptionKind::User),
+            ],
+        ),
+        SlashCommand::new(
+            "rating",
+            "Show someone's ratings, or how one category's has changed",
+            vec![
+                SlashOption::new("user", "Defaults to you", OptionKind::User),
+                SlashOption::new("game", "Game alias", OptionKind::String).autocomplete(),
+                SlashOption::new("category", "Category alias", OptionKind::String)
+                    .autocomplete(),
+            ],
+        ),
+        SlashCommand::new(
+            "ratings",
+            "Rank everyone rated in a category",
+            vec![game(), category()],
+        ),
+        SlashCommand::new("crew", "List a race's volunteers", vec![race_id()]),
+        SlashCommand::new(
+            "assign",
+            "Pick a volunteer for a race",
+            vec![
+                SlashOption::new("job", "What they'll do", OptionKind::String)
+                    .required()
+                    .choices(&["commentary", "restream"]),
+                race_id(),
+                SlashOption::new("user", "Who", OptionKind::User).required(),
+            ],
+        ),
+        SlashCommand::new(
+            "settings",
+            "Show or change this server's settings",
+            vec![
+                SlashOption::new("action", "What to do", OptionKind::String)
+                    .required()
+                    .choices(&["show", "set"]),
+                SlashOption::new("setting", "Which setting to change", OptionKind::String)
+                    .choices(setting_keys),
+                SlashOption::new(
+                    "value",
+                    "The new value. Lists are comma separated",
+                    OptionKind::String,
+                ),
+            ],
+        ),
+        SlashCommand::new(
+            "timezone",
+            "Show or change the timezone you type race times in",
+            vec![
+                SlashOption::new("timezone", "Like America/Chicago", OptionKind::String)
+                    .autocomplete(),
+            ],
+        ),
+        SlashCommand::new(
+            "racetime",
+            "Show or change the racetime.gg account your results get recorded under",
+            vec![
+                SlashOption::new("name", "Like Fox#1234", OptionKind::String),
+                SlashOption::new(
+                    "user",
+                    "Moderators only: link it to someone else",
+                    OptionKind::User,
+                ),
+            ],
+        ),
+        SlashCommand::new(
+            "addgame",
+            "Add a game",
+            vec![
+                SlashOption::new("alias", "Lowercase short name", OptionKind::String).required(),
+                SlashOption::new("name", "Full name", OptionKind::String).required(),
+            ],
+        ),
+        SlashCommand::new(
+            "addcategory",
+            "Add a category to a game",
+            vec![
+                game(),
+                SlashOption::new("alias", "Lowercase short name", OptionKind::String).required(),
+                SlashOption::new("name", "Full name", OptionKind::String).required(),
+            ],
+        ),
+        SlashCommand::new(
+            "editgame",
+            "Change a game's alias or name",
+            vec![
+                game(),
+                SlashOption::new("field", "What to change", OptionKind::String)
+                    .required()
+                    .choices(&["alias", "name"]),
+                SlashOption::new("value", "The new value", OptionKind::String).required(),
+            ],
+        ),
+        SlashCommand::new(
+            "editcategory",
+            "Change a category's alias, name, racetime.gg room settings or race length",
+            vec![
+                game(),
+                category(),
+                SlashOption::new("field", "What to change", OptionKind::String)
+                    .required()
+                    .choices(&["alias", "name", "racetime", "goal", "expected", "max"]),
+                SlashOption::new("value", "The new value, or none to clear anything but alias and name", OptionKind::String)
+                    .required(),
+            ],
+        ),
+        SlashCommand::new(
+            "retirecategory",
+            "Stop offering a category",
+            vec![game(), category()],
+        ),
+    ]
+}
+
+/// A button that sends us a MESSAGE_COMPONENT interaction with `custom_id` when pressed
+pub(crate) fn button(custom_id: String, label: &str, style: ButtonStyle) -> Value {
+    json!({
+        "type": BUTTON,
+        "style": style as u8,
+        "label": label,
+        "custom_id": custom_id,
+    })
+}
+
+/// Components have to be wrapped in rows, up to 5 buttons each
+pub(crate) fn action_row(components: Vec<Value>) -> Value {
+    json!({
+        "type": ACTION_ROW,
+        "components": components,
+    })
+}
+
+async fn send(
+    http: &Client,
+    method: Method,
+    path: Path,
+    path_str: String,
+    body: Value,
+) -> Result<(), InteractionError> {
+    let request = Request {
+        body: Some(body.to_string().into_bytes()),
+        form: None,
+        headers: None,
+        method,
+        path,
+        path_str: path_str.into(),
+    };
+    let resp = http.raw(request).await?;
+    if resp.status().is_success() {
+        return Ok(());
+    }
+    let status = resp.status().as_u16();
+    let body = hyper::body::to_bytes(resp.into_body())
+        .await
+        .map(|b| String::from_utf8_lossy(&b).to_string())
+        .unwrap_or_default();
+    Err(InteractionError::Rejected { status, body })
+}
+
+/// Replaces the guild's slash commands with `commands`. Guild commands show up right away,
+/// unlike global ones.
+pub(crate) async fn register_commands(
+    http: &Client,
+    application_id: u64,
+    guild_id: GuildId,
+    commands: &[SlashCommand],
+) -> Result<(), InteractionError> {
+    let body = commands.iter().map(SlashCommand::to_json).collect();
+    send(
+        http,
+        Method::PUT,
+        Path::GuildsId(guild_id.0),
+        format!(
+            "applications/{}/guilds/{}/commands",
+            application_id, guild_id
+        ),
+        body,
+    )
+    .await
+}
+
+fn message_data(content: &str, quiet: bool) -> Value {
+    let mut data = json!({ "content": content });
+    if quiet {
+        data["allowed_mentions"] = json!({ "parse": [] });
+    }
+    data
+}
+
+/// Acknowledges a slash command straight away, so it can take longer than the few seconds
+/// Discord gives us to answer. Discord shows "thinking..." until `edit_response` replaces it.
+pub(crate) async fn defer(http: &Client, interaction: &Interaction) -> Result<(), InteractionError> {
+    send(
+        http,
+        Method::POST,
+        Path::WebhooksId(interaction.application_id.0),
+        format!(
+            "interactions/{}/{}/callback",
+            interaction.id, interaction.token
+        ),
+        json!({ "type": DEFERRED_CHANNEL_MESSAGE_WITH_SOURCE }),
+    )
+    .await
+}
+
+/// The reply to a deferred slash command. `quiet` replies don't ping anyone they mention.
+pub(crate) async fn edit_response(
+    http: &Client,
+    interaction: &Interaction,
+    content: &str,
+    quiet: bool,
+) -> Result<(), InteractionError> {
+    send(
+        http,
+        Method::PATCH,
+        Path::WebhooksIdTokenMessageId(interaction.application_id.0),
+        format!(
+            "webhooks/{}/{}/messages/@original",
+            interaction.application_id, interaction.token
+        ),
+        message_data(content, quiet),
+    )
+    .await
+}
+
+/// Answers a button press with a message only the person who pressed it sees
+pub(crate) async fn respond_privately(
+    http: &Client,
+    interaction: &Interaction,
+    content: &str,
+) -> Result<(), InteractionError> {
+    let mut data = message_data(content, true);
+    data["flags"] = json!(EPHEMERAL);
+    send(
+        http,
+        Method::POST,
+        Path::WebhooksId(interaction.application_id.0),
+        format!("interactions/{}/{}/callback", interaction.id, interaction.token),
+        json!({
+            "type": CHANNEL_MESSAGE_WITH_SOURCE,
+            "data": data,
+        }),
+    )
+    .await
+}
+
+/// Any messages after the first reply
+pub(crate) async fn followup(
+    http: &Client,
+    interaction: &Interaction,
+    content: &str,
+    quiet: bool,
+) -> Result<(), InteractionError> {
+    send(
+        http,
+        Method::POST,
+        Path::WebhooksId(interaction.application_id.0),
+        format!(
+            "webhooks/{}/{}",
+            interaction.application_id, interaction.token
+        ),
+        message_data(content, quiet),
+    )
+    .await
+}
+
+/// Replaces the components on one of our messages. twilight can't send components, so messages
+/// get created without them and have them added afterwards. An empty `rows` removes them.
+pub(crate) async fn set_components(
+    http: &Client,
+    channel_id: ChannelId,
+    message_id: MessageId,
+    rows: Vec<Value>,
+) -> Result<(), InteractionError> {
+    send(
+        http,
+        Method::PATCH,
+        Path::ChannelsIdMessagesId(Method::PATCH, channel_id.0),
+        format!("channels/{}/messages/{}", channel_id, message_id),
+        json!({ "components": rows }),
+    )
+    .await
+}
+
+/// Answers an autocomplete request with `(name shown, value used)` pairs
+pub(crate) async fn suggest(
+    http: &Client,
+    interaction: &Interaction,
+    choices: Vec<(String, String)>,
+) -> Result<(), InteractionError> {
+    let choices = choices
+        .into_iter()
+        .take(MAX_CHOICES)
+        .map(|(name, value)| json!({"name": name, "value": value}))
+        .collect::<Vec<Value>>();
+    send(
+        http,
+        Method::POST,
+        Path::WebhooksId(interaction.application_id.0),
+        format!(
+            "interactions/{}/{}/callback",
+            interaction.id, interaction.token
+        ),
+        json!({
+            "type": APPLICATION_COMMAND_AUTOCOMPLETE_RESULT,
+            "data": { "choices": choices },
+        }),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::SETTING_KEYS;
+    use crate::interactions::{parse_interaction, slash_commands, APPLICATION_COMMAND, MAX_CHOICES};
+    use twilight_model::id::{GuildId, RoleId};
+
+    #[test]
+    fn test_parse_interaction() {
+        let payload = br#"{"op":0,"s":42,"t":"INTERACTION_CREATE","d":{
+            "id":"111","application_id":"222","type":2,"token":"abc","guild_id":"1",
+            "channel_id":"3","member":{"user":{"id":"5"},"roles":["7","8"]},
+            "data":{"id":"9","name":"newrace","options":[
+                {"name":"game","type":3,"value":"alttp"},
+                {"name":"category","type":3,"value":"nmg"},
+                {"name":"time","type":3,"value":"6/9/2021 11:00pm"}]}}}"#;
+        let interaction = parse_interaction(payload).unwrap();
+        assert_eq!(APPLICATION_COMMAND, interaction.kind);
+        assert_eq!(Some(GuildId(1)), interaction.guild_id);
+        assert_eq!(
+            vec![RoleId(7), RoleId(8)],
+            interaction.member.unwrap().roles
+        );
+        let data = interaction.data.unwrap();
+        assert_eq!(Some("alttp".to_string()), data.get("game"));
+
+        let not_interaction = br#"{"op":0,"s":43,"t":"MESSAGE_CREATE","d":{}}"#;
+        assert!(parse_interaction(not_interaction).is_none());
+        assert!(parse_interaction(br#"{"op":11,"d":null}"#).is_none());
+    }
+
+    /// Discord rejects the whole batch in register_commands if any one command breaks its rules
+    #[test]
+    fn test_commands_are_valid() {
+        for command in slash_commands(&SETTING_KEYS) {
+            assert!(command.description.len() <= 100, "/{} description is too long", command.name);
+            let mut optional = None;
+            for option in &command.options {
+                assert!(
+                    option.description.len() <= 100,
+                    "/{} {} description is too long",
+                    command.name,
+                    option.name
+                );
+                assert!(
+                    option.choices.len() <= MAX_CHOICES,
+                    "/{} {} has too many choices",
+                    command.name,
+                    option.name
+                );
+                match (option.required, optional) {
+                    (true, Some(earlier)) => panic!(
+                        "/{} {} is required but comes after the optional {}",
+                        command.name, option.name, earlier
+                    ),
+                    (false, None) => optional = Some(option.name),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_to_args() {
+        let commands = slash_commands(&["nag_times"]);
+        let find = |name: &str| commands.iter().find(|c| c.name == name).unwrap().clone();
+        let data = |json: &str| serde_json::from_str(json).unwrap();
+
+        let newrace = find("newrace");
+        assert_eq!(
+            "alttp nmg 6/9/2021 11:00pm",
+            newrace.to_args(&data(
+                r#"{"name":"newrace","options":[{"name":"time","value":"6/9/2021 11:00pm"},
+                {"name":"game","value":"alttp"},{"name":"category","value":"nmg"}]}"#
+            ))
+        );
+        assert_eq!(
+            "alttp nmg 6/9/2021 11:00pm -- bring snacks",
+            newrace.to_args(&data(
+                r#"{"name":"newrace","options":[{"name":"game","value":"alttp"},
+                {"name":"category","value":"nmg"},{"name":"time","value":"6/9/2021 11:00pm"},
+                {"name":"notes","value":"bring snacks"}]}"#
+            ))
+        );
+        assert_eq!(
+            "alttp nmg 6/9/2021 11:00pm --force -- bring snacks",
+            newrace.to_args(&data(
+                r#"{"name":"newrace","options":[{"name":"game","value":"alttp"},
+                {"name":"category","value":"nmg"},{"name":"time","value":"6/9/2021 11:00pm"},
+                {"name":"force","value":true},{"name":"notes","value":"bring snacks"}]}"#
+            ))
+        );
+        assert_eq!(
+            "12 6/9/2021 11:00pm",
+            find("reschedule").to_args(&data(
+                r#"{"name":"reschedule","options":[{"name":"race_id","value":12},
+                {"name":"time","value":"6/9/2021 11:00pm"},{"name":"force","value":false}]}"#
+            ))
+        );
+        assert_eq!(
+            "1:23:45 12 -- gg",
+            find("done").to_args(&data(
+                r#"{"name":"done","options":[{"name":"race_id","value":12},
+                {"name":"time","value":"1:23:45"},{"name":"comment","value":"gg"}]}"#
+            ))
+        );
+        assert_eq!(
+            "alttp past 5",
+            find("races").to_args(&data(
+                r#"{"name":"races","options":[{"name":"count","value":5},
+                {"name":"when","value":"past"},{"name":"game","value":"alttp"}]}"#
+            ))
+        );
+        assert_eq!(
+            "restream 12 <@5>",
+            find("assign").to_args(&data(
+                r#"{"name":"assign","options":[{"name":"job","value":"restream"},
+                {"name":"race_id","value":12},{"name":"user","value":"5"}]}"#
+            ))
+        );
+    }
+}
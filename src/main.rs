@@ -1,10 +1,15 @@
-use crate::constants::{CLIENT_ID};
+use crate::config::Config;
 use crate::discord::run_bot;
 use twilight_model::guild::Permissions;
 
+mod config;
 mod constants;
 mod discord;
+mod interactions;
 mod models;
+mod racetime;
+mod ratings;
+mod time_parser;
 
 extern crate chrono;
 extern crate chrono_tz;
@@ -20,6 +25,16 @@ extern crate env_logger;
 async fn main() {
     env_logger::init();
 
+    // fail before we connect to anything if the config is wrong
+    let config = match Config::load() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("{}", e);
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
     let required_permissions = Permissions::MANAGE_CHANNELS
         | Permissions::ADD_REACTIONS
         | Permissions::VIEW_CHANNEL
@@ -31,10 +46,10 @@ async fn main() {
 
     // TODO: probably need some user management powers here
     let url = format!(
-        "https://discord.com/oauth2/authorize?client_id={}&scope=bot&permissions={}",
-        CLIENT_ID, required_permissions.bits()
+        "https://discord.com/oauth2/authorize?client_id={}&scope=bot%20applications.commands&permissions={}",
+        config.client_id, required_permissions.bits()
     );
     println!("{}", url);
-    let jh = tokio::spawn(run_bot());
+    let jh = tokio::spawn(run_bot(config));
     jh.await.unwrap().unwrap();
 }
@@ -1,6 +1,8 @@
-use chrono::{DateTime, Utc, TimeZone};
+use chrono::{
+    DateTime, Datelike, Duration, LocalResult, NaiveDate, NaiveTime, TimeZone, Utc,
+};
 use procm::model;
-use twilight_model::id::MessageId;
+use twilight_model::id::{GuildId, MessageId, UserId};
 
 use chrono_tz::Tz;
 use chrono_tz::US::Eastern;
@@ -14,6 +16,7 @@ pub(crate) enum RaceState {
     SCHEDULED,
     ACTIVE,
     COMPLETED,
+    CANCELLED,
 }
 
 impl Display for RaceState {
@@ -25,6 +28,7 @@ impl Display for RaceState {
                 RaceState::SCHEDULED => "SCHEDULED",
                 RaceState::ACTIVE => "ACTIVE",
                 RaceState::COMPLETED => "COMPLETED",
+                RaceState::CANCELLED => "CANCELLED",
             }
         )
     }
@@ -47,11 +51,118 @@ impl FromStr for RaceState {
             "SCHEDULED" => Ok(RaceState::SCHEDULED),
             "ACTIVE" => Ok(RaceState::ACTIVE),
             "COMPLETED" => Ok(RaceState::COMPLETED),
+            "CANCELLED" => Ok(RaceState::CANCELLED),
             _ => Err(ParseError),
         }
     }
 }
 
+/// What someone signed up for a race to do
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub(crate) enum EntrantKind {
+    RACER,
+    COMMENTATOR,
+    RESTREAMER,
+}
+
+impl Display for EntrantKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                EntrantKind::RACER => "RACER",
+                EntrantKind::COMMENTATOR => "COMMENTATOR",
+                EntrantKind::RESTREAMER => "RESTREAMER",
+            }
+        )
+    }
+}
+
+impl FromStr for EntrantKind {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "RACER" => Ok(EntrantKind::RACER),
+            "COMMENTATOR" => Ok(EntrantKind::COMMENTATOR),
+            "RESTREAMER" => Ok(EntrantKind::RESTREAMER),
+            _ => Err(ParseError),
+        }
+    }
+}
+
+/// INTERESTED means they reacted on the scheduling message, CONFIRMED means they also readied up
+/// on the confirmation message. WITHDRAWN entrants took their reaction back.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum EntrantStatus {
+    INTERESTED,
+    CONFIRMED,
+    WITHDRAWN,
+}
+
+impl Display for EntrantStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                EntrantStatus::INTERESTED => "INTERESTED",
+                EntrantStatus::CONFIRMED => "CONFIRMED",
+                EntrantStatus::WITHDRAWN => "WITHDRAWN",
+            }
+        )
+    }
+}
+
+impl FromStr for EntrantStatus {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "INTERESTED" => Ok(EntrantStatus::INTERESTED),
+            "CONFIRMED" => Ok(EntrantStatus::CONFIRMED),
+            "WITHDRAWN" => Ok(EntrantStatus::WITHDRAWN),
+            _ => Err(ParseError),
+        }
+    }
+}
+
+/// How often a RaceSeries happens. Monthly series go by week of the month (e.g. the 2nd
+/// Saturday) rather than by date, since that's how people plan races.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum Recurrence {
+    WEEKLY,
+    BIWEEKLY,
+    MONTHLY,
+}
+
+impl Display for Recurrence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Recurrence::WEEKLY => "WEEKLY",
+                Recurrence::BIWEEKLY => "BIWEEKLY",
+                Recurrence::MONTHLY => "MONTHLY",
+            }
+        )
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "WEEKLY" => Ok(Recurrence::WEEKLY),
+            "BIWEEKLY" => Ok(Recurrence::BIWEEKLY),
+            "MONTHLY" => Ok(Recurrence::MONTHLY),
+            _ => Err(ParseError),
+        }
+    }
+}
 
 // TODO: hmmm... how to handle FKs? i think *for now* it's fine to just do stuff top down.
 //       probably eventually we want some kind of hydration
@@ -74,12 +185,27 @@ pub(crate) struct Category {
     pub(crate)   game_id: i64,
     pub(crate)   name: String,
     pub(crate)   name_pretty: String,
+    /// Retired categories can't have new races created for them, but old races keep pointing here
+    pub(crate)   retired: bool,
+    /// The racetime.gg category slug, e.g. "alttp". Races only get racetime.gg rooms if this is set.
+    pub(crate)   racetime_category: Option<String>,
+    /// The racetime.gg goal for rooms. Rooms use the category's name as a custom goal without one.
+    pub(crate)   racetime_goal: Option<String>,
+    /// How long races usually take, in seconds
+    pub(crate)   expected_duration: Option<i64>,
+    /// How long races can go before they get ended automatically, in seconds
+    pub(crate)   max_duration: Option<i64>,
 }
 }
 
 model! {
 pub(crate) struct Race {
     pub(crate) id: i64,
+
+    // Null for races created before the bot ran in more than one guild.
+    /// use get/set_guild_id() functions
+    pub(crate) guild_id: Option<String>,
+
     // N.B. game_id is not strictly necessary in this struct
     pub(crate) game_id: i64,
     pub(crate) category_id: i64,
@@ -96,22 +222,321 @@ pub(crate) struct Race {
 
     /// use get/set_active_message_id() functions
     pub(crate) active_message_id: Option<String>,
+
+    /// Free-form text shown alongside the race, e.g. "for new runners"
+    pub(crate) notes: Option<String>,
+
+    /// People sign up with buttons on the race's messages. Races scheduled before buttons existed
+    /// still use reactions.
+    pub(crate) signup_buttons: bool,
+
+    /// The RaceSeries that created this race, if any
+    pub(crate) series_id: Option<i64>,
+
+    /// Full URL of the race's racetime.gg room, if it has one
+    pub(crate) racetime_room: Option<String>,
+
+    /// When someone used !start, as seconds-since-epoch. Use start_timestamp()
+    pub(crate) started: Option<i64>,
+}
+}
+
+model! {
+pub(crate) struct RaceEntrant {
+    pub(crate) id: i64,
+    pub(crate) race_id: i64,
+
+    // user ids are u64s too, see Race::scheduling_message_id
+    /// use get_user_id()
+    pub(crate) user_id: String,
+
+    /// use get_kind()
+    pub(crate) kind: String,
+
+    /// use get_status(). Changes go through set_entrant_status(), which also unassigns people
+    /// who withdraw.
+    pub(crate) status: String,
+
+    /// Whether a moderator has picked this person for the race's crew. Only meaningful for
+    /// commentators and restreamers.
+    pub(crate) assigned: bool,
+
+    // Serialized as seconds-since-epoch
+    pub(crate) created: i64,
+    pub(crate) updated: i64,
+}
 }
+
+impl RaceEntrant {
+    pub(crate) fn get_user_id(&self) -> Option<UserId> {
+        match self.user_id.parse::<u64>() {
+            Ok(id) => Some(UserId(id)),
+            Err(e) => {
+                warn!("Error parsing user id {}: {}", self.user_id, e);
+                None
+            }
+        }
+    }
+
+    pub(crate) fn get_kind(&self) -> EntrantKind {
+        EntrantKind::from_str(&self.kind).unwrap()
+    }
+
+    pub(crate) fn get_status(&self) -> EntrantStatus {
+        EntrantStatus::from_str(&self.status).unwrap()
+    }
+}
+
+model! {
+pub(crate) struct RaceResult {
+    pub(crate) id: i64,
+    pub(crate) race_id: i64,
+
+    // see Race::scheduling_message_id
+    pub(crate) user_id: String,
+
+    /// Seconds from the start of the race. Null for forfeits.
+    pub(crate) finish_time: Option<i64>,
+    pub(crate) forfeit: bool,
+
+    /// 1 for the winner. Null for forfeits.
+    pub(crate) placement: Option<i64>,
+
+    pub(crate) comment: Option<String>,
+
+    // Serialized as seconds-since-epoch
+    pub(crate) created: i64,
+    pub(crate) updated: i64,
+}
+}
+
+// not a model!, because query_as! would read the ratings back as f32s
+#[derive(sqlx::FromRow, Debug, PartialEq)]
+pub(crate) struct RatingChange {
+    pub(crate) id: i64,
+    pub(crate) guild_id: String,
+    pub(crate) category_id: i64,
+    pub(crate) race_id: i64,
+
+    // see Race::scheduling_message_id
+    pub(crate) user_id: String,
+
+    pub(crate) rating_before: f64,
+    pub(crate) rating_after: f64,
+
+    // Serialized as seconds-since-epoch
+    pub(crate) created: i64,
+}
+
+model! {
+pub(crate) struct RaceSeries {
+    pub(crate) id: i64,
+
+    /// use get_guild_id()
+    pub(crate) guild_id: String,
+
+    pub(crate) game_id: i64,
+    pub(crate) category_id: i64,
+
+    /// use get_recurrence()
+    pub(crate) recurrence: String,
+
+    // The date and time are local to `timezone`, so races stay at the same wall-clock time
+    // when DST starts or ends.
+    /// The date of the first race, like 2021-06-09. use get_first_date()
+    pub(crate) first_date: String,
+
+    /// When each race starts, like 23:00. use get_time_of_day()
+    pub(crate) time_of_day: String,
+
+    /// An IANA name, like US/Eastern. use get_timezone()
+    pub(crate) timezone: String,
+
+    /// Copied onto each race the series creates
+    pub(crate) notes: Option<String>,
+
+    /// Ended series don't create any more races
+    pub(crate) active: bool,
+
+    // Serialized as seconds-since-epoch
+    /// The start time of the latest race created for this series
+    pub(crate) scheduled_through: Option<i64>,
+}
+}
+
+const SERIES_DATE_FORMAT: &str = "%Y-%m-%d";
+const SERIES_TIME_FORMAT: &str = "%H:%M";
+
+impl RaceSeries {
+    /// Creates a series whose first race is at `first`. Does not persist.
+    pub(crate) fn new(
+        id: i64,
+        guild_id: GuildId,
+        game_id: i64,
+        category_id: i64,
+        recurrence: Recurrence,
+        first: DateTime<Tz>,
+    ) -> Self {
+        RaceSeries {
+            id,
+            guild_id: guild_id.to_string(),
+            game_id,
+            category_id,
+            recurrence: recurrence.to_string(),
+            first_date: first.format(SERIES_DATE_FORMAT).to_string(),
+            time_of_day: first.format(SERIES_TIME_FORMAT).to_string(),
+            timezone: first.timezone().name().to_string(),
+            notes: None,
+            active: true,
+            scheduled_through: None,
+        }
+    }
+
+    pub(crate) fn get_guild_id(&self) -> Option<GuildId> {
+        match self.guild_id.parse::<u64>() {
+            Ok(id) => Some(GuildId(id)),
+            Err(e) => {
+                warn!("Error parsing guild id {}: {}", self.guild_id, e);
+                None
+            }
+        }
+    }
+
+    pub(crate) fn get_recurrence(&self) -> Recurrence {
+        Recurrence::from_str(&self.recurrence).unwrap()
+    }
+
+    pub(crate) fn get_first_date(&self) -> NaiveDate {
+        NaiveDate::parse_from_str(&self.first_date, SERIES_DATE_FORMAT).unwrap()
+    }
+
+    pub(crate) fn get_time_of_day(&self) -> NaiveTime {
+        NaiveTime::parse_from_str(&self.time_of_day, SERIES_TIME_FORMAT).unwrap()
+    }
+
+    pub(crate) fn get_timezone(&self) -> Tz {
+        match self.timezone.parse::<Tz>() {
+            Ok(tz) => tz,
+            Err(e) => {
+                warn!("Error parsing timezone {}: {}", self.timezone, e);
+                Eastern
+            }
+        }
+    }
+
+    /// The date of the `n`th race, counting from 0
+    fn nth_date(&self, n: u32) -> Option<NaiveDate> {
+        let first = self.get_first_date();
+        match self.get_recurrence() {
+            Recurrence::WEEKLY => Some(first + Duration::weeks(n as i64)),
+            Recurrence::BIWEEKLY => Some(first + Duration::weeks(2 * n as i64)),
+            Recurrence::MONTHLY => {
+                let months = first.month0() + n;
+                NaiveDate::from_weekday_of_month_opt(
+                    first.year() + (months / 12) as i32,
+                    months % 12 + 1,
+                    first.weekday(),
+                    (first.day0() / 7 + 1) as u8,
+                )
+            }
+        }
+    }
+
+    /// When the `n`th race starts, counting from 0
+    pub(crate) fn nth_occurrence(&self, n: u32) -> Option<DateTime<Tz>> {
+        let local = self.nth_date(n)?.and_time(self.get_time_of_day());
+        let tz = self.get_timezone();
+        match tz.from_local_datetime(&local) {
+            LocalResult::Single(dt) => Some(dt),
+            // the clocks fall back over this time, so it happens twice. go with the first one.
+            LocalResult::Ambiguous(earlier, _) => Some(earlier),
+            // the clocks spring forward over this time, so start once they have
+            LocalResult::None => tz
+                .from_local_datetime(&(local + Duration::hours(1)))
+                .earliest(),
+        }
+    }
+
+    /// Start times of the races in this series that are after `after` and no later than `until`
+    pub(crate) fn occurrences_between(
+        &self,
+        after: DateTime<Tz>,
+        until: DateTime<Tz>,
+    ) -> Vec<DateTime<Tz>> {
+        let mut times = vec![];
+        for n in 0.. {
+            match self.nth_occurrence(n) {
+                Some(t) if t > until => break,
+                Some(t) if t > after => times.push(t),
+                Some(_) => {}
+                None => break,
+            }
+        }
+        times
+    }
+
+    /// When the first race after `after` starts
+    pub(crate) fn next_occurrence(&self, after: DateTime<Tz>) -> Option<DateTime<Tz>> {
+        for n in 0.. {
+            match self.nth_occurrence(n) {
+                Some(t) if t > after => return Some(t),
+                Some(_) => {}
+                None => break,
+            }
+        }
+        None
+    }
+}
+
+impl Display for Game {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.name_pretty, self.name)
+    }
+}
+
+impl Display for Category {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.retired {
+            write!(f, "{} ({}) [retired]", self.name_pretty, self.name)
+        } else {
+            write!(f, "{} ({})", self.name_pretty, self.name)
+        }
+    }
 }
 
 impl Race {
 
     /// Creates a new race with the initial parameters. Does not persist.
     /// State will be set to SCHEDULED.
-    pub(crate) fn new(id: i64, game_id: i64, category_id: i64, occurs: DateTime<Tz>) -> Self {
+    pub(crate) fn new(id: i64, guild_id: GuildId, game_id: i64, category_id: i64, occurs: DateTime<Tz>) -> Self {
         let mut r = Race {
-            id, game_id, category_id, state: "".to_string(), occurs: 0, scheduling_message_id: None, active_message_id: None,
+            id, guild_id: None, game_id, category_id, state: "".to_string(), occurs: 0, scheduling_message_id: None, active_message_id: None,
+            notes: None, signup_buttons: true, series_id: None, racetime_room: None,
+            started: None,
         };
+        r.set_guild_id(guild_id);
         r.set_state(RaceState::SCHEDULED);
         r.set_occurs(occurs);
         r
     }
 
+    pub(crate) fn get_guild_id(&self) -> Option<GuildId> {
+        match &self.guild_id {
+            Some(s) => match s.parse::<u64>() {
+                Ok(id) => Some(GuildId(id)),
+                Err(e) => {
+                    warn!("Error parsing guild id {}: {}", s, e);
+                    None
+                }
+            },
+            None => None,
+        }
+    }
+
+    pub(crate) fn set_guild_id(&mut self, id: GuildId) {
+        self.guild_id = Some(id.to_string());
+    }
+
     pub(crate) fn get_scheduling_message_id(&self) -> Option<MessageId> {
         match &self.scheduling_message_id {
             Some(s) => match s.parse::<u64>() {
@@ -152,6 +577,11 @@ impl Race {
         Utc.timestamp(self.occurs, 0).with_timezone(&Eastern)
     }
 
+    /// When the race actually started, or was supposed to if nobody used !start
+    pub(crate) fn start_timestamp(&self) -> i64 {
+        self.started.unwrap_or(self.occurs)
+    }
+
     pub(crate) fn set_occurs(&mut self, occurs: DateTime<Tz>) {
         self.occurs = occurs.timestamp();
     }
@@ -165,6 +595,12 @@ impl Race {
     }
 }
 
+impl Display for RaceSeries {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Series #{}", self.id)
+    }
+}
+
 impl Display for Race {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         // TODO: hydrate game/cat and print them in here?
@@ -172,16 +608,53 @@ impl Display for Race {
     }
 }
 
+#[cfg(test)]
 mod tests {
-    use crate::models::Race;
-    use chrono::{Local, Timelike};
+    use crate::models::{Race, RaceSeries, Recurrence};
+    use chrono::{Local, TimeZone, Timelike};
+    use chrono_tz::US::Eastern;
+    use twilight_model::id::GuildId;
 
     #[test]
     fn test_timezone_roundtrip() {
-        let mut r = Race::new(1, 1, 1, Local::now().with_timezone(&chrono_tz::US::Eastern));
+        let mut r = Race::new(1, GuildId(1), 1, 1, Local::now().with_timezone(&chrono_tz::US::Eastern));
         let time = Local::now().with_timezone(&chrono_tz::US::Eastern);
         r.set_occurs(time);
         assert_eq!(r.get_occurs().timestamp(), time.timestamp());
         assert_eq!(time.with_nanosecond(0).unwrap(), r.get_occurs());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_series_occurrences_across_dst() {
+        // DST started on 3/14/2021 and ended on 11/7/2021
+        let first = Eastern.ymd(2021, 3, 6).and_hms(20, 0, 0);
+        let weekly = RaceSeries::new(1, GuildId(1), 1, 1, Recurrence::WEEKLY, first);
+        let times: Vec<String> = weekly
+            .occurrences_between(first - chrono::Duration::seconds(1), Eastern.ymd(2021, 3, 20).and_hms(20, 0, 0))
+            .iter()
+            .map(|t| t.to_rfc3339())
+            .collect();
+        assert_eq!(
+            vec!["2021-03-06T20:00:00-05:00", "2021-03-13T20:00:00-05:00", "2021-03-20T20:00:00-04:00"],
+            times
+        );
+
+        // 2:30am doesn't exist on the day DST starts, and 1:30am happens twice on the day it ends
+        let early = RaceSeries::new(1, GuildId(1), 1, 1, Recurrence::WEEKLY, Eastern.ymd(2021, 3, 7).and_hms(2, 30, 0));
+        assert_eq!("2021-03-14T03:30:00-04:00", early.nth_occurrence(1).unwrap().to_rfc3339());
+        let late = RaceSeries::new(1, GuildId(1), 1, 1, Recurrence::BIWEEKLY, Eastern.ymd(2021, 10, 24).and_hms(1, 30, 0));
+        assert_eq!("2021-11-07T01:30:00-04:00", late.nth_occurrence(1).unwrap().to_rfc3339());
+        assert_eq!("2021-11-21T01:30:00-05:00", late.nth_occurrence(2).unwrap().to_rfc3339());
+    }
+
+    #[test]
+    fn test_monthly_series() {
+        // the 2nd Saturday of each month
+        let first = Eastern.ymd(2021, 11, 13).and_hms(20, 0, 0);
+        let monthly = RaceSeries::new(1, GuildId(1), 1, 1, Recurrence::MONTHLY, first);
+        assert_eq!("2021-11-13T20:00:00-05:00", monthly.nth_occurrence(0).unwrap().to_rfc3339());
+        assert_eq!("2021-12-11T20:00:00-05:00", monthly.nth_occurrence(1).unwrap().to_rfc3339());
+        assert_eq!("2022-01-08T20:00:00-05:00", monthly.nth_occurrence(2).unwrap().to_rfc3339());
+        assert_eq!("2022-04-09T20:00:00-04:00", monthly.nth_occurrence(5).unwrap().to_rfc3339());
+    }
+}
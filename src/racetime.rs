@@ -0,0 +1,373 @@
+//! Opening race rooms on racetime.gg and reading the results back out of them. The bot talks to
+//! racetime.gg through the `RacetimeClient` trait so tests can swap in their own.
+
+use std::time::{Duration, Instant};
+
+use custom_error::custom_error;
+use futures::future::BoxFuture;
+use hyper::body::Bytes;
+use hyper::client::HttpConnector;
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE, LOCATION};
+use hyper::{Body, HeaderMap, Request};
+use hyper_rustls::HttpsConnector;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+const FORM: &str = "application/x-www-form-urlencoded";
+// seconds between everyone readying up and the race starting
+const START_DELAY: &str = "15";
+// hours before racetime.gg gives up on a room
+const TIME_LIMIT: &str = "24";
+
+custom_error! { pub(crate) RacetimeError
+    Http{source: hyper::Error} = "Error talking to racetime.gg: {source}",
+    Request{source: hyper::http::Error} = "Error building a racetime.gg request: {source}",
+    Rejected{status: u16, body: String} = "racetime.gg rejected the request ({status}): {body}",
+    Parse{source: serde_json::Error} = "Couldn't read racetime.gg's response: {source}",
+    NoLocation = "racetime.gg didn't say where the new room is",
+}
+
+/// What we need to open a room
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct NewRoom {
+    /// The racetime.gg category slug
+    pub(crate) category: String,
+    pub(crate) goal: Option<String>,
+    /// Used when there's no goal
+    pub(crate) custom_goal: String,
+    /// Shown at the top of the room
+    pub(crate) info: String,
+}
+
+/// The parts of a room's `/data` we care about
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct RoomData {
+    pub(crate) status: Status,
+    pub(crate) entrants: Vec<Entrant>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct Status {
+    pub(crate) value: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct Entrant {
+    pub(crate) user: User,
+    pub(crate) status: Status,
+    /// An ISO 8601 duration, like "P0DT01H23M45.678901S"
+    pub(crate) finish_time: Option<String>,
+    pub(crate) comment: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub(crate) struct User {
+    /// The name with its discriminator, like "Fox#1234"
+    pub(crate) full_name: String,
+}
+
+impl RoomData {
+    /// Whether the room is finished or cancelled, so nothing's going to change any more
+    pub(crate) fn is_over(&self) -> bool {
+        matches!(self.status.value.as_str(), "finished" | "cancelled")
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.status.value == "cancelled"
+    }
+}
+
+impl Entrant {
+    /// Their time in seconds, if they finished
+    pub(crate) fn finish_seconds(&self) -> Option<i64> {
+        if self.status.value != "done" {
+            return None;
+        }
+        self.finish_time.as_deref().and_then(parse_duration)
+    }
+
+    /// Whether they forfeited or got disqualified
+    pub(crate) fn is_forfeit(&self) -> bool {
+        matches!(self.status.value.as_str(), "dnf" | "dq")
+    }
+}
+
+/// Parses the ISO 8601 durations racetime.gg uses for finish times, e.g. "P0DT01H23M45.678901S".
+/// Fractions of a second are dropped.
+pub(crate) fn parse_duration(s: &str) -> Option<i64> {
+    let rest = s.strip_prefix('P')?;
+    let mut seconds = 0.0;
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in rest.chars() {
+        match c {
+            '0'..='9' | '.' => number.push(c),
+            'T' if !in_time && number.is_empty() => in_time = true,
+            'D' | 'H' | 'M' | 'S' => {
+                let n: f64 = number.parse().ok()?;
+                number.clear();
+                seconds += n * match (c, in_time) {
+                    ('D', false) => 86400.0,
+                    ('H', true) => 3600.0,
+                    ('M', true) => 60.0,
+                    ('S', true) => 1.0,
+                    _ => return None,
+                };
+            }
+            _ => return None,
+        }
+    }
+    if !number.is_empty() {
+        return None;
+    }
+    Some(seconds as i64)
+}
+
+pub(crate) trait RacetimeClient {
+    /// Opens a room and returns its URL
+    fn start_race<'a>(&'a self, room: &'a NewRoom) -> BoxFuture<'a, Result<String, RacetimeError>>;
+
+    /// Fetches the room at `url`, as returned by `start_race`
+    fn get_race<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<RoomData, RacetimeError>>;
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+}
+
+/// Talks to the real racetime.gg (or whatever `base_url` points at) as an OAuth2 application
+pub(crate) struct HttpRacetimeClient {
+    base_url: String,
+    client_id: String,
+    client_secret: String,
+    http: hyper::Client<HttpsConnector<HttpConnector>>,
+    // the access token and when it runs out
+    token: Mutex<Option<(String, Instant)>>,
+}
+
+fn form(fields: &[(&str, &str)]) -> String {
+    url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(fields)
+        .finish()
+}
+
+impl HttpRacetimeClient {
+    pub(crate) fn new(base_url: &str, client_id: String, client_secret: String) -> Self {
+        HttpRacetimeClient {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client_id,
+            client_secret,
+            http: hyper::Client::builder().build(HttpsConnector::with_native_roots()),
+            token: Mutex::new(None),
+        }
+    }
+
+    /// Sends the request and returns the response's headers and body. Anything but a 2xx is an
+    /// error.
+    async fn send(&self, request: Request<Body>) -> Result<(HeaderMap, Bytes), RacetimeError> {
+        let (parts, body) = self.http.request(request).await?.into_parts();
+        let body = hyper::body::to_bytes(body).await?;
+        if !parts.status.is_success() {
+            return Err(RacetimeError::Rejected {
+                status: parts.status.as_u16(),
+                body: String::from_utf8_lossy(&body).to_string(),
+            });
+        }
+        Ok((parts.headers, body))
+    }
+
+    /// Gets a new access token if we don't have one that's still good
+    async fn access_token(&self) -> Result<String, RacetimeError> {
+        let mut token = self.token.lock().await;
+        if let Some((t, expires)) = &*token {
+            if Instant::now() < *expires {
+                return Ok(t.clone());
+            }
+        }
+        let body = form(&[
+            ("client_id", &self.client_id),
+            ("client_secret", &self.client_secret),
+            ("grant_type", "client_credentials"),
+        ]);
+        let request = Request::post(format!("{}/o/token", self.base_url))
+            .header(CONTENT_TYPE, FORM)
+            .body(Body::from(body))?;
+        let (_, body) = self.send(request).await?;
+        let resp: TokenResponse = serde_json::from_slice(&body)?;
+        // a minute early, so it doesn't run out halfway through a request
+        let lifetime = resp.expires_in.unwrap_or(3600).saturating_sub(60);
+        *token = Some((resp.access_token.clone(), Instant::now() + Duration::from_secs(lifetime)));
+        Ok(resp.access_token)
+    }
+}
+
+impl RacetimeClient for HttpRacetimeClient {
+    fn start_race<'a>(&'a self, room: &'a NewRoom) -> BoxFuture<'a, Result<String, RacetimeError>> {
+        Box::pin(async move {
+            let token = self.access_token().await?;
+            let mut fields = vec![
+                ("info_user", room.info.as_str()),
+                ("start_delay", START_DELAY),
+                ("time_limit", TIME_LIMIT),
+                ("chat_message_delay", "0"),
+                ("allow_comments", "true"),
+                ("allow_prerace_chat", "true"),
+                ("allow_midrace_chat", "true"),
+                ("allow_non_entrant_chat", "true"),
+            ];
+            match &room.goal {
+                Some(g) => fields.push(("goal", g)),
+                None => fields.push(("custom_goal", &room.custom_goal)),
+            }
+            let request = Request::post(format!("{}/o/{}/startrace", self.base_url, room.category))
+                .header(AUTHORIZATION, format!("Bearer {}", token))
+                .header(CONTENT_TYPE, FORM)
+                .body(Body::from(form(&fields)))?;
+            let (headers, _) = self.send(request).await?;
+            // the room's path, like /alttp/clever-link-1234
+            match headers.get(LOCATION).and_then(|l| l.to_str().ok()) {
+                Some(path) => Ok(format!("{}{}", self.base_url, path)),
+                None => Err(RacetimeError::NoLocation),
+            }
+        })
+    }
+
+    fn get_race<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<RoomData, RacetimeError>> {
+        Box::pin(async move {
+            let request = Request::get(format!("{}/data", url)).body(Body::empty())?;
+            let (_, body) = self.send(request).await?;
+            Ok(serde_json::from_slice(&body)?)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::racetime::{parse_duration, HttpRacetimeClient, NewRoom, RacetimeClient};
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server, StatusCode};
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(Some(5025), parse_duration("P0DT01H23M45.678901S"));
+        assert_eq!(Some(5025), parse_duration("PT1H23M45S"));
+        assert_eq!(Some(90000), parse_duration("P1DT1H"));
+        assert_eq!(Some(59), parse_duration("PT59.999S"));
+        assert_eq!(None, parse_duration("01:23:45"));
+        assert_eq!(None, parse_duration("P1H"));
+        assert_eq!(None, parse_duration("PT1H23"));
+        assert_eq!(None, parse_duration("PTxS"));
+    }
+
+    const ROOM_DATA: &str = r#"{
+        "name": "alttp/clever-link-1234",
+        "status": {"value": "finished", "verbose_value": "Finished"},
+        "entrants": [
+            {
+                "user": {"id": "abc", "name": "Fox", "discriminator": "1234", "full_name": "Fox#1234"},
+                "status": {"value": "done"},
+                "finish_time": "P0DT01H23M45.678901S",
+                "place": 1,
+                "comment": "gg"
+            },
+            {
+                "user": {"id": "def", "name": "Bird", "discriminator": null, "full_name": "Bird"},
+                "status": {"value": "dnf"},
+                "finish_time": null,
+                "place": null,
+                "comment": null
+            }
+        ]
+    }"#;
+
+    /// A pretend racetime.gg that only knows about one room
+    async fn fake_racetime(req: Request<Body>, token_requests: Arc<AtomicUsize>) -> Response<Body> {
+        let method = req.method().to_string();
+        let path = req.uri().path().to_string();
+        let auth = req
+            .headers()
+            .get("authorization")
+            .map(|a| a.to_str().unwrap().to_string());
+        let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+        let body = String::from_utf8_lossy(&body).to_string();
+        let mut resp = Response::new(Body::empty());
+        match (method.as_str(), path.as_str()) {
+            ("POST", "/o/token") if body.contains("grant_type=client_credentials") => {
+                token_requests.fetch_add(1, Ordering::SeqCst);
+                *resp.body_mut() = Body::from(r#"{"access_token": "tok", "expires_in": 36000}"#);
+            }
+            ("POST", "/o/alttp/startrace") if auth.as_deref() == Some("Bearer tok") => {
+                assert!(body.contains("goal=Beat+the+game"), "{}", body);
+                assert!(body.contains("info_user=Race+%231"), "{}", body);
+                *resp.status_mut() = StatusCode::CREATED;
+                resp.headers_mut()
+                    .insert("location", "/alttp/clever-link-1234".parse().unwrap());
+            }
+            ("GET", "/alttp/clever-link-1234/data") => {
+                *resp.body_mut() = Body::from(ROOM_DATA);
+            }
+            _ => {
+                *resp.status_mut() = StatusCode::FORBIDDEN;
+            }
+        }
+        resp
+    }
+
+    async fn start_server(token_requests: Arc<AtomicUsize>) -> SocketAddr {
+        let make_service = make_service_fn(move |_| {
+            let token_requests = token_requests.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let token_requests = token_requests.clone();
+                    async move { Ok::<_, Infallible>(fake_racetime(req, token_requests).await) }
+                }))
+            }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        addr
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_http_client() {
+        let token_requests = Arc::new(AtomicUsize::new(0));
+        let addr = start_server(token_requests.clone()).await;
+        let base_url = format!("http://{}", addr);
+        let client = HttpRacetimeClient::new(&format!("{}/", base_url), "id".into(), "secret".into());
+
+        let room = NewRoom {
+            category: "alttp".to_string(),
+            goal: Some("Beat the game".to_string()),
+            custom_goal: "Any% NMG No S&Q".to_string(),
+            info: "Race #1".to_string(),
+        };
+        let url = client.start_race(&room).await.unwrap();
+        assert_eq!(format!("{}/alttp/clever-link-1234", base_url), url);
+        // the token gets reused
+        client.start_race(&room).await.unwrap();
+        assert_eq!(1, token_requests.load(Ordering::SeqCst));
+
+        let data = client.get_race(&url).await.unwrap();
+        assert!(data.is_over());
+        assert!(!data.is_cancelled());
+        assert_eq!(2, data.entrants.len());
+        assert_eq!("Fox#1234", data.entrants[0].user.full_name);
+        assert_eq!(Some(5025), data.entrants[0].finish_seconds());
+        assert!(!data.entrants[0].is_forfeit());
+        assert_eq!(None, data.entrants[1].finish_seconds());
+        assert!(data.entrants[1].is_forfeit());
+
+        let missing = client.get_race(&format!("{}/alttp/nope", base_url)).await;
+        assert_eq!(
+            "racetime.gg rejected the request (403): ",
+            missing.err().unwrap().to_string()
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! Elo ratings for races with any number of people in them. Everyone in a race is scored as if
+//! they'd played a 1v1 against each of the others: a faster time beats a slower one, finishing
+//! beats forfeiting, and equal times or two forfeits are a draw.
+
+pub(crate) const INITIAL_RATING: f64 = 1500.0;
+/// The most a single race can move someone's rating
+const K_FACTOR: f64 = 32.0;
+
+/// How someone did in a race, as far as ratings care
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Outcome {
+    // in seconds
+    Finished(i64),
+    Forfeit,
+}
+
+/// 1 if `a` beat `b`, 0 if they lost and 0.5 for a draw
+fn score(a: Outcome, b: Outcome) -> f64 {
+    match (a, b) {
+        (Outcome::Finished(x), Outcome::Finished(y)) => {
+            if x < y {
+                1.0
+            } else if x > y {
+                0.0
+            } else {
+                0.5
+            }
+        }
+        (Outcome::Finished(_), Outcome::Forfeit) => 1.0,
+        (Outcome::Forfeit, Outcome::Finished(_)) => 0.0,
+        (Outcome::Forfeit, Outcome::Forfeit) => 0.5,
+    }
+}
+
+/// The chance someone rated `a` beats someone rated `b`
+fn expected(a: f64, b: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((b - a) / 400.0))
+}
+
+/// Everyone's new rating after a race, in the same order they were given. K is split across
+/// each racer's matchups so a big race doesn't move ratings any more than a 1v1 would.
+pub(crate) fn rate_race(racers: &[(f64, Outcome)]) -> Vec<f64> {
+    if racers.len() < 2 {
+        return racers.iter().map(|(rating, _)| *rating).collect();
+    }
+    let k = K_FACTOR / (racers.len() - 1) as f64;
+    racers
+        .iter()
+        .enumerate()
+        .map(|(i, (rating, outcome))| {
+            let delta: f64 = racers
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, (other, other_outcome))| {
+                    score(*outcome, *other_outcome) - expected(*rating, *other)
+                })
+                .sum();
+            rating + k * delta
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ratings::{rate_race, Outcome, INITIAL_RATING};
+
+    fn rounded(ratings: Vec<f64>) -> Vec<i64> {
+        ratings.into_iter().map(|r| r.round() as i64).collect()
+    }
+
+    #[test]
+    fn test_rate_race() {
+        let even = (INITIAL_RATING, Outcome::Finished(5000));
+        assert_eq!(
+            vec![1516, 1484],
+            rounded(rate_race(&[(INITIAL_RATING, Outcome::Finished(4000)), even]))
+        );
+        assert_eq!(vec![1500, 1500], rounded(rate_race(&[even, even])));
+        // finishing at all beats a forfeit, and forfeits draw with each other
+        assert_eq!(
+            vec![1516, 1492, 1492],
+            rounded(rate_race(&[
+                (INITIAL_RATING, Outcome::Finished(9000)),
+                (INITIAL_RATING, Outcome::Forfeit),
+                (INITIAL_RATING, Outcome::Forfeit),
+            ]))
+        );
+        // an upset moves things more than the expected result does
+        assert_eq!(
+            vec![1608, 1392],
+            rounded(rate_race(&[
+                (1600.0, Outcome::Finished(4000)),
+                (1400.0, Outcome::Finished(5000))
+            ]))
+        );
+        assert_eq!(
+            vec![1576, 1424],
+            rounded(rate_race(&[
+                (1600.0, Outcome::Finished(5000)),
+                (1400.0, Outcome::Finished(4000))
+            ]))
+        );
+        // nobody to be compared to
+        assert_eq!(vec![1600], rounded(rate_race(&[(1600.0, Outcome::Forfeit)])));
+
+        let before = [
+            (1500.0, Outcome::Finished(4000)),
+            (1620.0, Outcome::Finished(4100)),
+            (1450.0, Outcome::Forfeit),
+            (1700.0, Outcome::Finished(3900)),
+        ];
+        let after = rate_race(&before);
+        let total_before: f64 = before.iter().map(|(r, _)| r).sum();
+        let total_after: f64 = after.iter().sum();
+        assert!((total_before - total_after).abs() < 1e-9);
+    }
+}
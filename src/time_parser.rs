@@ -0,0 +1,703 @@
+//! Turns what people type for a race time into an actual time. Handles things like
+//! `6/9/2021 11pm`, `tomorrow 8pm`, `sat 20:00`, `2021-06-09T23:00:00-04:00`, unix timestamps and
+//! pasted Discord `<t:1623294000:F>` tags. Anything without an explicit timezone is read in the
+//! zone of whoever typed it.
+//!
+//! Also reads finish times like `1:23:45` or `1h23m45s`, which are durations rather than times.
+
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, LocalResult, NaiveDate, NaiveDateTime, NaiveTime,
+    Offset, TimeZone, Utc, Weekday,
+};
+use chrono_tz::{Tz, TZ_VARIANTS};
+use custom_error::custom_error;
+
+custom_error! { pub(crate) TimeParseError
+    Empty = "Please give a time, like `6/9/2021 11pm` or `tomorrow 8pm`.",
+    Date{input: String} = "Couldn't understand the date `{input}`. Try something like 6/9/2021, 2021-06-09, tomorrow or sat.",
+    Time{input: String} = "Couldn't understand the time `{input}`. Try something like 11pm, 11:30pm or 23:30.",
+    Timezone{input: String} = "Unknown timezone `{input}`. Try something like EST, UTC, +02:00 or Europe/Berlin.",
+    Timestamp{input: String} = "`{input}` isn't a valid timestamp.",
+    MissingDate = "Which day? Put a date before the time, like `tomorrow 8pm`.",
+    MissingTime = "What time? Put a time after the date, like `6/9/2021 11pm`.",
+    Extra{input: String} = "Not sure what `{input}` means. Try something like `6/9/2021 11pm`.",
+    Ambiguous{local: String, tz: String, earlier: String, later: String} = "{local} happens twice in {tz} because the clocks go back that night. Add the UTC offset to say which one you mean: `{earlier}` or `{later}`.",
+    Skipped{local: String, tz: String} = "{local} doesn't exist in {tz} because the clocks skip ahead that night. Please pick a different time.",
+}
+
+/// Parses `input` as a point in time. `tz` is used unless the input says otherwise, and
+/// relative dates like `tomorrow` count from `now`. The result is in `tz` either way.
+pub(crate) fn parse_time(
+    input: &str,
+    tz: Tz,
+    now: DateTime<Tz>,
+) -> Result<DateTime<Tz>, TimeParseError> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(TimeParseError::Empty);
+    }
+    if let Some(ts) = parse_timestamp(input)? {
+        return Ok(ts.with_timezone(&tz));
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc3339(input) {
+        return Ok(dt.with_timezone(&tz));
+    }
+
+    let mut tokens = tokenize(input);
+    let zone = match tokens.last().and_then(|t| parse_zone(t)) {
+        Some(z) => {
+            tokens.pop();
+            z
+        }
+        None => Zone::Named(tz),
+    };
+    // `2021-06-09T23:00` is just a date and time glued together
+    if tokens.len() == 1 && tokens[0].starts_with(|c: char| c.is_ascii_digit()) {
+        if let Some(idx) = tokens[0].find('t') {
+            let (date, time) = tokens[0].split_at(idx);
+            tokens = vec![date.to_string(), time[1..].to_string()];
+        }
+    }
+
+    let (day, time, day_token) = match tokens.as_slice() {
+        [] => {
+            return Err(TimeParseError::Empty);
+        }
+        [only] => {
+            return Err(if parse_time_of_day(only).is_some() {
+                TimeParseError::MissingDate
+            } else if parse_day(only).is_some() {
+                TimeParseError::MissingTime
+            } else {
+                TimeParseError::Date {
+                    input: only.clone(),
+                }
+            });
+        }
+        [first, second] => match (parse_day(first), parse_time_of_day(second)) {
+            (Some(d), Some(t)) => (d, t, first),
+            (Some(_), None) => {
+                return Err(TimeParseError::Time {
+                    input: second.clone(),
+                });
+            }
+            (None, _) => match (parse_time_of_day(first), parse_day(second)) {
+                // `8pm tomorrow` works too
+                (Some(t), Some(d)) => (d, t, second),
+                (Some(_), None) => {
+                    return Err(TimeParseError::Date {
+                        input: second.clone(),
+                    });
+                }
+                (None, _) => {
+                    return Err(TimeParseError::Date {
+                        input: first.clone(),
+                    });
+                }
+            },
+        },
+        [_, _, rest @ ..] => {
+            // if the last token was supposed to be a timezone, that's what's wrong
+            let last = rest.last().unwrap();
+            return Err(if looks_like_zone(last) {
+                TimeParseError::Timezone {
+                    input: last.clone(),
+                }
+            } else {
+                TimeParseError::Extra {
+                    input: rest.join(" "),
+                }
+            });
+        }
+    };
+
+    let local_now = zone.local_now(&now);
+    let date = day
+        .resolve(local_now, time)
+        .ok_or_else(|| TimeParseError::Date {
+            input: day_token.clone(),
+        })?;
+    zone.localize(date.and_time(time))
+        .map(|dt| dt.with_timezone(&tz))
+}
+
+/// Looks up an IANA timezone name, ignoring case
+pub(crate) fn find_timezone(name: &str) -> Option<Tz> {
+    TZ_VARIANTS
+        .iter()
+        .find(|tz| tz.name().eq_ignore_ascii_case(name))
+        .copied()
+}
+
+/// A bare unix timestamp, or a Discord timestamp tag like `<t:1623294000:F>`
+fn parse_timestamp(input: &str) -> Result<Option<DateTime<Utc>>, TimeParseError> {
+    let digits = if input.starts_with("<t:") && input.ends_with('>') {
+        input[3..input.len() - 1]
+            .split(':')
+            .next()
+            .unwrap_or_default()
+    } else if input.len() >= 9 && input.chars().all(|c| c.is_ascii_digit()) {
+        input
+    } else {
+        return Ok(None);
+    };
+    digits
+        .parse::<i64>()
+        .ok()
+        .and_then(|secs| Utc.timestamp_opt(secs, 0).single())
+        .map(Some)
+        .ok_or_else(|| TimeParseError::Timestamp {
+            input: input.to_string(),
+        })
+}
+
+/// Lowercases and splits on whitespace, gluing a separate `am`/`pm` back onto its time and
+/// dropping filler like "at"
+fn tokenize(input: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = vec![];
+    for word in input.split_whitespace() {
+        let word = word.to_lowercase();
+        match word.as_str() {
+            "at" | "on" => {}
+            "am" | "pm" if !tokens.is_empty() => tokens.last_mut().unwrap().push_str(&word),
+            _ => tokens.push(word),
+        }
+    }
+    tokens
+}
+
+/// The timezone a time is read in
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Zone {
+    Named(Tz),
+    Fixed(FixedOffset),
+}
+
+impl Zone {
+    fn local_now(&self, now: &DateTime<Tz>) -> NaiveDateTime {
+        match self {
+            Zone::Named(tz) => now.with_timezone(tz).naive_local(),
+            Zone::Fixed(offset) => now.with_timezone(offset).naive_local(),
+        }
+    }
+
+    fn localize(&self, local: NaiveDateTime) -> Result<DateTime<Utc>, TimeParseError> {
+        let tz = match self {
+            Zone::Named(tz) => tz,
+            Zone::Fixed(offset) => {
+                return Ok(offset
+                    .from_local_datetime(&local)
+                    .unwrap()
+                    .with_timezone(&Utc));
+            }
+        };
+        let pretty = local.format("%-m/%-d/%Y %-I:%M%P").to_string();
+        match tz.from_local_datetime(&local) {
+            LocalResult::Single(dt) => Ok(dt.with_timezone(&Utc)),
+            LocalResult::Ambiguous(earlier, later) => Err(TimeParseError::Ambiguous {
+                // spelled out from scratch, since whatever zone they typed has to go
+                earlier: format!("{} {}", pretty, earlier.offset().fix()),
+                later: format!("{} {}", pretty, later.offset().fix()),
+                local: pretty,
+                tz: tz.name().to_string(),
+            }),
+            LocalResult::None => Err(TimeParseError::Skipped {
+                local: pretty,
+                tz: tz.name().to_string(),
+            }),
+        }
+    }
+}
+
+/// Things like `EST`, `UTC`, `+02:00`, `-0500` or `Europe/Berlin`
+fn parse_zone(token: &str) -> Option<Zone> {
+    // people say EST all year round when they mean Eastern time, so the US abbreviations mean the
+    // zone, DST and all, rather than a fixed offset
+    let named = match token {
+        "utc" | "gmt" | "z" => Some(Tz::UTC),
+        "et" | "est" | "edt" => Some(Tz::US__Eastern),
+        "ct" | "cst" | "cdt" => Some(Tz::US__Central),
+        "mt" | "mst" | "mdt" => Some(Tz::US__Mountain),
+        "pt" | "pst" | "pdt" => Some(Tz::US__Pacific),
+        _ => None,
+    };
+    if let Some(tz) = named {
+        return Some(Zone::Named(tz));
+    }
+    if token.contains('/') && !token.starts_with(|c: char| c.is_ascii_digit()) {
+        return find_timezone(token).map(Zone::Named);
+    }
+    parse_offset(token).map(Zone::Fixed)
+}
+
+/// Whether a token was probably meant as a timezone, for error messages
+fn looks_like_zone(token: &str) -> bool {
+    token.starts_with('+')
+        || token.starts_with('-')
+        || (token.contains('/') && !token.starts_with(|c: char| c.is_ascii_digit()))
+        || (token.len() <= 4 && token.chars().all(|c| c.is_ascii_alphabetic()))
+}
+
+/// `+02:00`, `-0500` or `+2`
+fn parse_offset(token: &str) -> Option<FixedOffset> {
+    let sign = match token.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => {
+            return None;
+        }
+    };
+    let digits: String = token[1..].chars().filter(|c| *c != ':').collect();
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes) = match digits.len() {
+        1 | 2 => (digits.parse::<i32>().ok()?, 0),
+        4 => (
+            digits[..2].parse::<i32>().ok()?,
+            digits[2..].parse::<i32>().ok()?,
+        ),
+        _ => {
+            return None;
+        }
+    };
+    if hours > 14 || minutes >= 60 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// A day as typed, before we know enough to turn it into a date
+#[derive(Debug, PartialEq)]
+enum Day {
+    Date(NaiveDate),
+    /// Relative to today: 0 is today, 1 is tomorrow
+    DaysFromNow(i64),
+    /// The next one of these
+    Weekday(Weekday),
+    /// A month and day without a year means the next one
+    NoYear(u32, u32),
+}
+
+impl Day {
+    /// The date this means, if it's a real date
+    fn resolve(&self, now: NaiveDateTime, time: NaiveTime) -> Option<NaiveDate> {
+        let today = now.date();
+        match self {
+            Day::Date(d) => Some(*d),
+            Day::DaysFromNow(n) => Some(today + Duration::days(*n)),
+            Day::Weekday(wd) => {
+                let ahead = (7 + wd.num_days_from_monday() as i64
+                    - today.weekday().num_days_from_monday() as i64)
+                    % 7;
+                let date = today + Duration::days(ahead);
+                // saying "sat" on a Saturday evening means next week
+                if date.and_time(time) <= now {
+                    Some(date + Duration::weeks(1))
+                } else {
+                    Some(date)
+                }
+            }
+            Day::NoYear(month, day) => {
+                let date = NaiveDate::from_ymd_opt(today.year(), *month, *day)?;
+                if date.and_time(time) <= now {
+                    NaiveDate::from_ymd_opt(today.year() + 1, *month, *day)
+                } else {
+                    Some(date)
+                }
+            }
+        }
+    }
+}
+
+fn parse_day(token: &str) -> Option<Day> {
+    match token {
+        "today" | "tonight" => {
+            return Some(Day::DaysFromNow(0));
+        }
+        "tomorrow" => {
+            return Some(Day::DaysFromNow(1));
+        }
+        _ => {}
+    }
+    if let Some(wd) = parse_weekday(token) {
+        return Some(Day::Weekday(wd));
+    }
+
+    let number = |s: &str| {
+        if !s.is_empty() && s.chars().all(|c| c.is_ascii_digit()) {
+            s.parse::<u32>().ok()
+        } else {
+            None
+        }
+    };
+    let slashes: Vec<&str> = token.split('/').collect();
+    let dashes: Vec<&str> = token.split('-').collect();
+    match (slashes.as_slice(), dashes.as_slice()) {
+        ([m, d], _) => {
+            let (m, d) = (number(m)?, number(d)?);
+            // make sure it's a real day in at least one year
+            NaiveDate::from_ymd_opt(2000, m, d)?;
+            Some(Day::NoYear(m, d))
+        }
+        ([m, d, y], _) => {
+            let year = match y.len() {
+                2 => 2000 + number(y)?,
+                4 => number(y)?,
+                _ => {
+                    return None;
+                }
+            };
+            NaiveDate::from_ymd_opt(year as i32, number(m)?, number(d)?).map(Day::Date)
+        }
+        (_, [y, m, d]) if y.len() == 4 => {
+            NaiveDate::from_ymd_opt(number(y)? as i32, number(m)?, number(d)?).map(Day::Date)
+        }
+        _ => None,
+    }
+}
+
+fn parse_weekday(token: &str) -> Option<Weekday> {
+    let weekday = match token.trim_end_matches(',') {
+        "mon" | "monday" => Weekday::Mon,
+        "tue" | "tues" | "tuesday" => Weekday::Tue,
+        "wed" | "wednesday" => Weekday::Wed,
+        "thu" | "thur" | "thurs" | "thursday" => Weekday::Thu,
+        "fri" | "friday" => Weekday::Fri,
+        "sat" | "saturday" => Weekday::Sat,
+        "sun" | "sunday" => Weekday::Sun,
+        _ => {
+            return None;
+        }
+    };
+    Some(weekday)
+}
+
+/// `11pm`, `11:30pm`, `23:30`, `23:30:00`, `noon` or `midnight`
+fn parse_time_of_day(token: &str) -> Option<NaiveTime> {
+    match token {
+        "noon" => {
+            return Some(NaiveTime::from_hms(12, 0, 0));
+        }
+        "midnight" => {
+            return Some(NaiveTime::from_hms(0, 0, 0));
+        }
+        _ => {}
+    }
+    let (clock, pm) = if let Some(c) = token.strip_suffix("am") {
+        (c, Some(false))
+    } else if let Some(c) = token.strip_suffix("pm") {
+        (c, Some(true))
+    } else {
+        (token, None)
+    };
+
+    let mut parts = vec![];
+    for part in clock.split(':') {
+        if part.is_empty() || part.len() > 2 || !part.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        parts.push(part.parse::<u32>().ok()?);
+    }
+    let (hour, minute, second) = match parts.as_slice() {
+        // a bare "20" could mean anything, but "8pm" is clear enough
+        [h] if pm.is_some() => (*h, 0, 0),
+        [h, m] => (*h, *m, 0),
+        [h, m, s] => (*h, *m, *s),
+        _ => {
+            return None;
+        }
+    };
+    let hour = match pm {
+        Some(_) if hour == 0 || hour > 12 => {
+            return None;
+        }
+        Some(false) => hour % 12,
+        Some(true) => hour % 12 + 12,
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, second)
+}
+
+// no race takes anywhere near this long, so anything longer is a typo
+const MAX_FINISH_SECS: i64 = 3 * 24 * 60 * 60;
+
+/// Parses how long someone took to finish a race, in seconds. Takes `h:mm:ss`, `m:ss` or units
+/// like `1h23m45s`, up to MAX_FINISH_SECS.
+pub(crate) fn parse_finish_time(input: &str) -> Option<i64> {
+    let input = input.trim().to_lowercase();
+    let secs = if input.contains(':') {
+        let parts = input
+            .split(':')
+            .map(|p| p.parse::<i64>().ok())
+            .collect::<Option<Vec<i64>>>()?;
+        let (h, m, s) = match parts.as_slice() {
+            [m, s] => (0, *m, *s),
+            [h, m, s] if *m < 60 => (*h, *m, *s),
+            _ => {
+                return None;
+            }
+        };
+        if s >= 60 || h < 0 || m < 0 || s < 0 {
+            return None;
+        }
+        h.checked_mul(3600)?
+            .checked_add(m.checked_mul(60)?)?
+            .checked_add(s)?
+    } else {
+        let mut total: i64 = 0;
+        let mut number = String::new();
+        for c in input.chars() {
+            if c.is_ascii_digit() {
+                number.push(c);
+                continue;
+            }
+            let unit = match c {
+                'h' => 3600,
+                'm' => 60,
+                's' => 1,
+                _ => {
+                    return None;
+                }
+            };
+            total = total.checked_add(number.parse::<i64>().ok()?.checked_mul(unit)?)?;
+            number.clear();
+        }
+        // a bare number doesn't say whether it's minutes or seconds
+        if !number.is_empty() {
+            return None;
+        }
+        total
+    };
+    if secs > 0 && secs <= MAX_FINISH_SECS {
+        Some(secs)
+    } else {
+        None
+    }
+}
+
+/// Shows a finish time as `h:mm:ss`
+pub(crate) fn format_finish_time(secs: i64) -> String {
+    format!("{}:{:02}:{:02}", secs / 3600, secs % 3600 / 60, secs % 60)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::time_parser::{format_finish_time, parse_finish_time, parse_time, TimeParseError};
+    use chrono::{DateTime, TimeZone};
+    use chrono_tz::Tz;
+    use chrono_tz::US::Eastern;
+
+    // Friday, 6/4/2021 at 9:00pm Eastern
+    fn now() -> DateTime<Tz> {
+        Eastern.ymd(2021, 6, 4).and_hms(21, 0, 0)
+    }
+
+    fn parse(input: &str) -> Result<String, String> {
+        parse_time(input, Eastern, now())
+            .map(|dt| dt.to_rfc3339())
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_dates_and_times() {
+        let expected = Ok("2021-06-09T23:00:00-04:00".to_string());
+        for input in [
+            "06/09/2021 11:00pm",
+            "6/9/2021 11pm",
+            "6/9/21 11 PM",
+            "6/9 11pm",
+            "6/9/2021 23:00",
+            "2021-06-09 23:00:00",
+            "2021-06-09T23:00",
+            "2021-06-09T23:00:00-04:00",
+            "11pm 6/9/2021",
+            "wed 11pm",
+            "Wednesday at 11:00pm",
+            "1623294000",
+            "<t:1623294000:F>",
+            "<t:1623294000>",
+        ]
+        .iter()
+        {
+            assert_eq!(expected, parse(input), "{}", input);
+        }
+
+        assert_eq!(
+            Ok("2021-06-04T21:30:00-04:00".to_string()),
+            parse("today 9:30pm")
+        );
+        assert_eq!(
+            Ok("2021-06-05T20:00:00-04:00".to_string()),
+            parse("tomorrow 8pm")
+        );
+        assert_eq!(
+            Ok("2021-06-05T00:00:00-04:00".to_string()),
+            parse("sat midnight")
+        );
+        assert_eq!(
+            Ok("2021-06-05T12:00:00-04:00".to_string()),
+            parse("sat noon")
+        );
+        assert_eq!(
+            Ok("2021-06-05T00:30:00-04:00".to_string()),
+            parse("sat 12:30am")
+        );
+        // it's already past 8pm on Friday, so that means next week
+        assert_eq!(
+            Ok("2021-06-11T20:00:00-04:00".to_string()),
+            parse("fri 20:00")
+        );
+        assert_eq!(
+            Ok("2021-06-04T22:00:00-04:00".to_string()),
+            parse("fri 22:00")
+        );
+        // same with dates that don't say which year
+        assert_eq!(
+            Ok("2022-01-02T20:00:00-05:00".to_string()),
+            parse("1/2 8pm")
+        );
+    }
+
+    #[test]
+    fn test_timezones() {
+        assert_eq!(
+            Ok("2021-06-09T19:00:00-04:00".to_string()),
+            parse("6/9/2021 23:00 UTC")
+        );
+        assert_eq!(
+            Ok("2021-06-09T17:00:00-04:00".to_string()),
+            parse("6/9/2021 11pm +02:00")
+        );
+        assert_eq!(
+            Ok("2021-06-09T17:00:00-04:00".to_string()),
+            parse("6/9/2021 11pm +0200")
+        );
+        assert_eq!(
+            Ok("2021-06-09T17:00:00-04:00".to_string()),
+            parse("6/9/2021 11pm Europe/Berlin")
+        );
+        assert_eq!(
+            Ok("2021-06-10T02:00:00-04:00".to_string()),
+            parse("6/9/2021 11pm PST")
+        );
+        // EST in June still means Eastern time
+        assert_eq!(
+            Ok("2021-06-09T23:00:00-04:00".to_string()),
+            parse("6/9/2021 11pm EST")
+        );
+        assert_eq!(
+            Ok("2021-06-09T23:00:00+02:00".to_string()),
+            parse_time("6/9/2021 11pm", chrono_tz::Europe::Berlin, now())
+                .map(|dt| dt.to_rfc3339())
+                .map_err(|e| e.to_string())
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            parse_time(" ", Eastern, now()),
+            Err(TimeParseError::Empty)
+        ));
+        assert!(matches!(
+            parse_time("11pm", Eastern, now()),
+            Err(TimeParseError::MissingDate)
+        ));
+        assert!(matches!(
+            parse_time("tomorrow", Eastern, now()),
+            Err(TimeParseError::MissingTime)
+        ));
+        assert_eq!(
+            Err("Couldn't understand the date `13/45/2021`. Try something like 6/9/2021, 2021-06-09, tomorrow or sat.".to_string()),
+            parse("13/45/2021 11pm")
+        );
+        assert_eq!(
+            Err(
+                "Couldn't understand the time `25pm`. Try something like 11pm, 11:30pm or 23:30."
+                    .to_string()
+            ),
+            parse("6/9/2021 25pm")
+        );
+        assert!(matches!(
+            parse_time("6/9/2021 20", Eastern, now()),
+            Err(TimeParseError::Time { .. })
+        ));
+        assert!(matches!(
+            parse_time("someday 8pm", Eastern, now()),
+            Err(TimeParseError::Date { .. })
+        ));
+        assert!(matches!(
+            parse_time("8pm someday", Eastern, now()),
+            Err(TimeParseError::Date { .. })
+        ));
+        assert_eq!(
+            Err(
+                "Unknown timezone `xyz`. Try something like EST, UTC, +02:00 or Europe/Berlin."
+                    .to_string()
+            ),
+            parse("6/9/2021 11pm XYZ")
+        );
+        assert!(matches!(
+            parse_time("6/9/2021 11pm please", Eastern, now()),
+            Err(TimeParseError::Extra { .. })
+        ));
+        assert!(matches!(
+            parse_time("<t:soon:F>", Eastern, now()),
+            Err(TimeParseError::Timestamp { .. })
+        ));
+    }
+
+    #[test]
+    fn test_dst() {
+        // clocks went back at 2am on 11/7/2021, so 1:30am happened twice
+        assert_eq!(
+            Err("11/7/2021 1:30am happens twice in US/Eastern because the clocks go back that night. \
+            Add the UTC offset to say which one you mean: `11/7/2021 1:30am -04:00` or `11/7/2021 1:30am -05:00`."
+                .to_string()),
+            parse("11/7/2021 1:30am")
+        );
+        // the suggestions replace a zone that was typed, rather than adding a second one
+        assert_eq!(parse("11/7/2021 1:30am"), parse("11/7/2021 1:30am EST"));
+        assert_eq!(
+            Ok("2021-11-07T01:30:00-04:00".to_string()),
+            parse("11/7/2021 1:30am -04:00")
+        );
+        assert_eq!(
+            Ok("2021-11-07T01:30:00-05:00".to_string()),
+            parse("11/7/2021 1:30am -05:00")
+        );
+        // and they skipped ahead at 2am on 3/14/2021
+        assert_eq!(
+            Err("3/14/2021 2:30am doesn't exist in US/Eastern because the clocks skip ahead that night. \
+            Please pick a different time."
+                .to_string()),
+            parse("3/14/2021 2:30am")
+        );
+    }
+
+    #[test]
+    fn test_finish_times() {
+        for input in ["1:23:45", "83:45", "1h23m45s", "1H23M45S", "5025s", "83m45s"] {
+            assert_eq!(Some(5025), parse_finish_time(input), "{}", input);
+        }
+        assert_eq!(Some(45), parse_finish_time("0:45"));
+        for input in ["", "83", "1:60", "1:60:00", "1:2:3:4", "0:00", "1h23x", "-1:00", "soon"] {
+            assert_eq!(None, parse_finish_time(input), "{}", input);
+        }
+        // way too long, up to numbers that don't fit in an i64 once they're in seconds
+        assert_eq!(Some(72 * 3600), parse_finish_time("72:00:00"));
+        for input in [
+            "72:00:01",
+            "1000h",
+            "9223372036854775807:00",
+            "9223372036854775807h",
+            "99999999999999999999s",
+        ] {
+            assert_eq!(None, parse_finish_time(input), "{}", input);
+        }
+        assert_eq!("1:23:45", format_finish_time(5025));
+        assert_eq!("0:00:45", format_finish_time(45));
+        assert_eq!("12:00:00", format_finish_time(43200));
+    }
+}